@@ -1,6 +1,10 @@
 // src/commands.rs
 use crate::database::ann_search;
-use crate::database::search::SearchFilters;
+use crate::database::history;
+use crate::database::interactions::{self, InteractionKind};
+use crate::database::rules::{replace_exact_path_rules_sync, RuleType};
+use crate::database::search::{SearchFilters, SearchResponse};
+use crate::database::settings;
 use crate::database::SearchResult;
 use crate::file_scanner;
 use crate::search_window::toggle_search_window_impl;
@@ -11,7 +15,12 @@ use tauri::AppHandle;
 use tauri::Manager;
 use tauri::State;
 use rusqlite::Connection;
+use crate::file_scanner::scoring::{self, ScoringProfile};
+use crate::file_scanner::explain::{self, PathExplanation};
 use crate::file_scanner::types::ScannedFile;
+use crate::file_scanner::volume_registry::{self, TrackedVolume, VolumePolicy};
+use crate::file_scanner::volumes::{self, Volume};
+use crate::file_scanner::walker;
 use crate::file_ops::{open_file_impl, open_file_with_impl, show_file_in_explorer_impl};
 
 #[derive(Clone, serde::Serialize)]
@@ -91,7 +100,7 @@ pub async fn hide_search_window(app: tauri::AppHandle) -> Result<(), String> {
 #[tauri::command]
 pub async fn scan_text_files(app: AppHandle) -> Result<Vec<ScannedFile>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         file_scanner::discover_files_with_rules(&db, &app).map_err(|e| e.to_string())
     })
     .await
@@ -103,9 +112,12 @@ pub async fn read_text_files(
     paths: Vec<String>,
     max_chars: Option<usize>,
 ) -> Result<Vec<file_scanner::types::FileContent>, String> {
-    tokio::task::spawn_blocking(move || Ok(file_scanner::content::read_files_content(&paths, max_chars)))
-        .await
-        .map_err(|e| format!("Task spawn error: {}", e))?
+    tokio::task::spawn_blocking(move || {
+        let settings = settings::load_settings(&crate::database::get_read_connection()?);
+        Ok(file_scanner::content::read_files_content(&paths, max_chars, &settings))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
 }
 
 #[tauri::command]
@@ -116,7 +128,8 @@ pub async fn get_file_content(
     max_chars: Option<usize>,
 ) -> Result<Option<file_scanner::types::FileContent>, String> {
     tokio::task::spawn_blocking(move || {
-        let results = file_scanner::content::read_files_content(&[path], max_chars);
+        let settings = settings::load_settings(&crate::database::get_read_connection()?);
+        let results = file_scanner::content::read_files_content(&[path], max_chars, &settings);
         Ok(results.into_iter().next())
     })
     .await
@@ -126,30 +139,30 @@ pub async fn get_file_content(
 #[tauri::command]
 pub async fn scan_and_store_files(path: String, app: AppHandle) -> Result<usize, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
-        crate::file_scanner::scan_and_store_files(&db, &path, None, Some(50_000_000), app)
+        crate::file_scanner::scan_and_store_files(&path, None, Some(50_000_000), app)
     })
     .await
     .map_err(|e| format!("Task spawn error: {}", e))?
 }
 
+/// The scan roots (included paths) and excluded paths, as plain path rules.
 #[tauri::command]
 pub async fn load_scan_settings() -> Result<ScanSettings, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
-
-        // Get excluded paths from database
-        let mut stmt = db
-            .prepare("SELECT path FROM path_rules WHERE rule_type = 'exclude'")
-            .map_err(|e| format!("Database error: {}", e))?;
-        let ignored_folders: Result<Vec<String>, _> = stmt
-            .query_map([], |row| row.get(0))
+        let db = crate::database::get_read_connection()?;
+        let mut scan_paths: Vec<String> = crate::database::rules::get_included_paths_sync(&db)
             .map_err(|e| format!("Database error: {}", e))?
+            .into_iter()
             .collect();
-
+        let mut ignored_folders: Vec<String> = crate::database::rules::get_excluded_paths_sync(&db)
+            .map_err(|e| format!("Database error: {}", e))?
+            .into_iter()
+            .collect();
+        scan_paths.sort();
+        ignored_folders.sort();
         Ok(ScanSettings {
-            scan_paths: vec!["C://Users/Jai Mishra/OneDrive/Documents".to_string()],
-            ignored_folders: ignored_folders.map_err(|e| format!("Database error: {}", e))?,
+            scan_paths,
+            ignored_folders,
         })
     })
     .await
@@ -161,8 +174,79 @@ pub async fn search_files(
     query: String,
     top_k: Option<usize>,
     filters: Option<SearchFilters>,
-) -> Result<Vec<SearchResult>, String> {
-    ann_search::perform_file_search(query, top_k, filters).await
+    cursor: Option<String>,
+) -> Result<SearchResponse, String> {
+    match cursor {
+        Some(cursor) => ann_search::fetch_search_page(&cursor, top_k),
+        None => {
+            let response =
+                ann_search::perform_file_search(query.clone(), top_k, filters.clone()).await?;
+            record_search_history(query, filters, response.total_candidates);
+            Ok(response)
+        }
+    }
+}
+
+/// Runs as the user types. Only a `committed` search (submitted, not a keystroke) goes to
+/// history; opening one of its results also records it.
+#[tauri::command]
+pub async fn search_files_streaming(
+    app: AppHandle,
+    request_id: String,
+    query: String,
+    top_k: Option<usize>,
+    filters: Option<SearchFilters>,
+    committed: Option<bool>,
+) -> Result<SearchResponse, String> {
+    let response =
+        ann_search::stream_file_search(app, request_id, query.clone(), top_k, filters.clone())
+            .await?;
+    if committed.unwrap_or(false) {
+        record_search_history(query, filters, response.total_candidates);
+    }
+    Ok(response)
+}
+
+/// Re-runs a search from history with the filters it was executed with.
+#[tauri::command]
+pub async fn replay_search_history_entry(
+    id: i64,
+    top_k: Option<usize>,
+) -> Result<SearchResponse, String> {
+    let entry = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        history::get_history_entry_sync(&db, id)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))??
+    .ok_or_else(|| format!("Search history entry {} not found", id))?;
+
+    search_files(entry.query, top_k, entry.filters, None).await
+}
+
+/// Runs a saved search with its pinned filters.
+#[tauri::command]
+pub async fn run_saved_search(id: i64, top_k: Option<usize>) -> Result<SearchResponse, String> {
+    let saved = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        history::get_saved_search_sync(&db, id)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))??
+    .ok_or_else(|| format!("Saved search {} not found", id))?;
+
+    search_files(saved.query, top_k, saved.filters, None).await
+}
+
+/// Best-effort and fire-and-forget: the search returns without waiting for the writer, and a
+/// failed history insert is only logged.
+fn record_search_history(query: String, filters: Option<SearchFilters>, result_count: usize) {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        if let Err(e) = history::record_search(&db, &query, filters.as_ref(), result_count) {
+            eprintln!("Failed to record search history: {}", e);
+        }
+    });
 }
 
 #[tauri::command]
@@ -170,22 +254,82 @@ pub async fn search_indexed_files(
     query: String,
     limit: Option<usize>,
 ) -> Result<Vec<SearchResult>, String> {
-    ann_search::perform_file_search(query, limit, None).await
+    ann_search::perform_file_search(query, limit, None)
+        .await
+        .map(|response| response.results)
 }
 
+// `query` is the search the file was picked from, if any; it feeds usage-aware ranking.
 #[tauri::command]
-pub fn open_file(file_path: String) -> Result<(), String> {
-    open_file_impl(file_path)
+pub async fn open_file(file_path: String, query: Option<String>) -> Result<(), String> {
+    ensure_available(&file_path).await?;
+    open_file_impl(file_path.clone())?;
+    record_interaction(file_path, InteractionKind::Open, query);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn open_file_with(file_path: String, application: String) -> Result<(), String> {
-    open_file_with_impl(file_path, application)
+pub async fn open_file_with(
+    file_path: String,
+    application: String,
+    query: Option<String>,
+) -> Result<(), String> {
+    ensure_available(&file_path).await?;
+    open_file_with_impl(file_path.clone(), application)?;
+    record_interaction(file_path, InteractionKind::OpenWith, query);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn show_file_in_explorer(file_path: String) -> Result<(), String> {
-    show_file_in_explorer_impl(file_path)
+pub async fn show_file_in_explorer(file_path: String, query: Option<String>) -> Result<(), String> {
+    ensure_available(&file_path).await?;
+    show_file_in_explorer_impl(file_path.clone())?;
+    record_interaction(file_path, InteractionKind::Reveal, query);
+    Ok(())
+}
+
+/// Explains a missing file on a disconnected volume instead of letting the launch fail.
+async fn ensure_available(file_path: &str) -> Result<(), String> {
+    if std::path::Path::new(file_path).exists() {
+        return Ok(());
+    }
+    let path = file_path.to_string();
+    let offline = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection().ok()?;
+        let tracked = volume_registry::load_tracked_volumes(&db).ok()?;
+        volume_registry::owning_volume(&tracked, &path)
+            .filter(|v| !v.is_online)
+            .cloned()
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?;
+    match offline {
+        Some(volume) => Err(format!(
+            "{} is on volume \"{}\", which is not connected",
+            file_path,
+            volume.label.as_deref().unwrap_or(&volume.mount_point)
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Best-effort and fire-and-forget: the file is already open, so the command doesn't wait
+/// for the writer and a failed insert is only logged.
+fn record_interaction(file_path: String, kind: InteractionKind, query: Option<String>) {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        // With history disabled the pick is still counted, but not tied to the query
+        let query = query.filter(|_| history::is_history_enabled(&db));
+        let result = interactions::record_interaction(&db, &file_path, kind, query.as_deref())
+            .map_err(|e| e.to_string())
+            .and_then(|_| match query {
+                Some(query) => history::record_chosen_result(&db, &query, &file_path),
+                None => Ok(()),
+            });
+        if let Err(e) = result {
+            eprintln!("Failed to record file interaction: {}", e);
+        }
+    });
 }
 
 #[tauri::command]
@@ -195,17 +339,79 @@ pub async fn select_folder() -> Result<String, String> {
     Err("Folder selection not implemented yet".to_string())
 }
 
+/// Replaces the exact included and excluded path rules with the given lists.
 #[tauri::command]
 pub async fn save_scan_settings(settings: ScanSettings) -> Result<(), String> {
-    // For now, just log the settings. You can implement actual storage later
-    println!("Saving scan settings: {:?}", settings);
-    Ok(())
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        replace_exact_path_rules_sync(&db, RuleType::Include, &settings.scan_paths)
+            .and_then(|_| replace_exact_path_rules_sync(&db, RuleType::Exclude, &settings.ignored_folders))
+            .map_err(|e| format!("Database error: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
 }
 
 #[tauri::command]
-pub async fn get_excluded_folder() -> Result<HashSet<String>, String> {
+pub async fn get_scoring_profile() -> Result<ScoringProfile, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        Ok(scoring::load_scoring_profile(&db))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Saves the profile. Existing scores keep their old values until `recompute_scores` runs.
+#[tauri::command]
+pub async fn update_scoring_profile(profile: ScoringProfile) -> Result<ScoringProfile, String> {
     tokio::task::spawn_blocking(move || {
         let db = crate::database::get_connection();
+        scoring::save_scoring_profile(&db, profile)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn reset_scoring_profile() -> Result<ScoringProfile, String> {
+    update_scoring_profile(ScoringProfile::default()).await
+}
+
+#[tauri::command]
+pub async fn import_scoring_profile(file_path: String) -> Result<ScoringProfile, String> {
+    let json = tokio::fs::read_to_string(&file_path)
+        .await
+        .map_err(|e| format!("Failed to read scoring profile: {}", e))?;
+    let profile: ScoringProfile =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid scoring profile: {}", e))?;
+    update_scoring_profile(profile).await
+}
+
+#[tauri::command]
+pub async fn export_scoring_profile(file_path: String) -> Result<(), String> {
+    let profile = get_scoring_profile().await?;
+    let json = serde_json::to_string_pretty(&profile).map_err(|e| e.to_string())?;
+    tokio::fs::write(&file_path, json)
+        .await
+        .map_err(|e| format!("Failed to write scoring profile: {}", e))
+}
+
+/// Rescores every indexed file and folder with the current profile, without rescanning.
+#[tauri::command]
+pub async fn recompute_scores() -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        scoring::recompute_all_scores(&db)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_excluded_folder() -> Result<HashSet<String>, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_excluded_folder_sync(&db).map_err(|e| e.to_string())
     }).await.map_err(|e| format!("Task spawn error: {}", e))?
 }
@@ -213,7 +419,7 @@ pub async fn get_excluded_folder() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_included_folders() -> Result<HashSet<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_included_folders_sync(&db).map_err(|e| e.to_string())
     }).await.map_err(|e| format!("Task spawn error: {}", e))?
 }
@@ -221,7 +427,7 @@ pub async fn get_included_folders() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_excluded_paths() -> Result<HashSet<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_excluded_paths_sync(&db).map_err(|e| e.to_string())
     }).await.map_err(|e| format!("Task spawn error: {}", e))?
 }
@@ -229,7 +435,7 @@ pub async fn get_excluded_paths() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_included_extensions() -> Result<HashSet<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_included_extensions_sync(&db).map_err(|e| e.to_string())
     }).await.map_err(|e| format!("Task spawn error: {}", e))?
 }
@@ -237,7 +443,7 @@ pub async fn get_included_extensions() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_excluded_extensions() -> Result<HashSet<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_excluded_extensions_sync(&db).map_err(|e| e.to_string())
     }).await.map_err(|e| format!("Task spawn error: {}", e))?
 }
@@ -245,7 +451,7 @@ pub async fn get_excluded_extensions() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_excluded_filenames() -> Result<HashSet<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_excluded_filenames_sync(&db).map_err(|e| e.to_string())
     }).await.map_err(|e| format!("Task spawn error: {}", e))?
 }
@@ -253,7 +459,7 @@ pub async fn get_excluded_filenames() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_included_paths() -> Result<HashSet<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_included_paths_sync(&db).map_err(|e| e.to_string())
     })
     .await
@@ -263,7 +469,7 @@ pub async fn get_included_paths() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_excluded_folders() -> Result<HashSet<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         crate::database::rules::get_rules_sync(&db, crate::database::rules::RuleCategory::Folder, crate::database::rules::RuleType::Exclude)
             .map_err(|e| e.to_string())
     })
@@ -274,7 +480,7 @@ pub async fn get_excluded_folders() -> Result<HashSet<String>, String> {
 #[tauri::command]
 pub async fn get_matching_file_count() -> Result<usize, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
+        let db = crate::database::get_read_connection()?;
         // Get a count of files that would match the current rules
         // For now, return count of all indexed files - you can enhance this later
         let mut stmt = db.prepare("SELECT COUNT(*) FROM files").map_err(|e| e.to_string())?;
@@ -288,10 +494,9 @@ pub async fn get_matching_file_count() -> Result<usize, String> {
 #[tauri::command]
 pub async fn run_full_scan_and_index(app: AppHandle) -> Result<usize, String> {
     tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
         // The "" for path is because the function uses the stored include paths from the DB
         // when running in phase 1 mode, not the path argument.
-        crate::file_scanner::scan_and_store_files(&db, "", None, None, app)
+        crate::file_scanner::scan_and_store_files("", None, None, app)
     })
     .await
     .map_err(|e| format!("Task spawn error: {}", e))?
@@ -299,27 +504,104 @@ pub async fn run_full_scan_and_index(app: AppHandle) -> Result<usize, String> {
 
 #[tauri::command]
 pub async fn scan_drives_metadata(app: AppHandle) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::file_scanner::scan_drives_metadata_only(&app)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Lists mounted volumes with their flags and whether Phase 2 will scan them.
+#[tauri::command]
+pub async fn discover_system_drives() -> Result<Vec<Volume>, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        Ok(volumes::discover_volumes_with_selection(&db))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Chooses which volumes Phase 2 indexes, by mount point.
+#[tauri::command]
+pub async fn set_selected_volumes(mount_points: Vec<String>) -> Result<Vec<Volume>, String> {
     tokio::task::spawn_blocking(move || {
         let db = crate::database::get_connection();
-        crate::file_scanner::scan_drives_metadata_only(&db, &app)
+        volumes::save_selected_mount_points(&db, &mount_points)?;
+        Ok(volumes::discover_volumes_with_selection(&db))
     })
     .await
     .map_err(|e| format!("Task spawn error: {}", e))?
 }
 
 #[tauri::command]
-pub async fn discover_system_drives() -> Result<Vec<String>, String> {
+pub async fn get_respect_ignore_files() -> Result<bool, String> {
     tokio::task::spawn_blocking(move || {
-        Ok(crate::file_scanner::discovery::discover_drives())
+        let db = crate::database::get_read_connection()?;
+        Ok(walker::respect_ignore_files(&db))
     })
     .await
     .map_err(|e| format!("Task spawn error: {}", e))?
 }
 
+/// Runs a path through every scan check and reports why it is or isn't indexed.
 #[tauri::command]
-pub async fn get_phase2_stats() -> Result<serde_json::Value, String> {
+pub async fn explain_path(path: String) -> Result<PathExplanation, String> {
+    let mut explanation = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        explain::explain_path_sync(&db, &path)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))??;
+    explain::add_lancedb_state(&mut explanation).await;
+    Ok(explanation)
+}
+
+/// Whether scans honour .gitignore, .ignore and .fileaiignore files.
+#[tauri::command]
+pub async fn set_respect_ignore_files(enabled: bool, app: AppHandle) -> Result<(), String> {
     tokio::task::spawn_blocking(move || {
         let db = crate::database::get_connection();
+        crate::database::rules::set_setting_sync(
+            &db,
+            walker::RESPECT_IGNORE_FILES_SETTING,
+            if enabled { "true" } else { "false" },
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+        settings::notify_settings_changed(&app, &db);
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Every volume seen so far, connected or not, with its indexing policy.
+#[tauri::command]
+pub async fn list_tracked_volumes() -> Result<Vec<TrackedVolume>, String> {
+    tokio::task::spawn_blocking(move || {
+        volume_registry::refresh_volumes()?;
+        let db = crate::database::get_read_connection()?;
+        volume_registry::load_tracked_volumes(&db).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Sets whether a volume is never indexed, indexed for metadata only, or fully indexed.
+#[tauri::command]
+pub async fn set_volume_policy(volume_key: String, policy: VolumePolicy) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        volume_registry::set_volume_policy(&db, &volume_key, policy)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_phase2_stats() -> Result<serde_json::Value, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
         
         // Get total files count
         let mut stmt = db.prepare("SELECT COUNT(*) FROM files").map_err(|e| e.to_string())?;
@@ -1,200 +1,535 @@
 use crate::database;
 use crate::embed_and_store;
-use arrow_array::{Int32Array, RecordBatch, StringArray};
+use crate::database::facets::{count_facets, filter_candidates, SearchFacets};
+use crate::file_scanner::lancedb::get_lancedb_folder_table;
+use crate::file_scanner::volume_registry::mark_availability;
+use arrow_array::{Int32Array, RecordBatch};
 use database::search::{
-    advanced_search, classify_intent, combine_and_rank_results, parse_query, search_files_fts,
-    search_folders_by_name, SearchFilters, SearchMatchType, SearchResult,
+    advanced_search, classify_intent, combine_and_rank_results, merge_filters, parse_query,
+    search_files_fts, search_folders_by_name, SearchFilters, SearchIntent, SearchMatchType,
+    SearchResponse, SearchResult,
 };
-use rusqlite::{Connection, Result};
+use database::interactions;
+use database::query_compiler::{self, CompiledQuery};
+use database::search_session;
+use database::vector_index::{LanceVectorIndex, VectorIndex};
+use rusqlite::{Connection, OptionalExtension, Result};
 use lancedb::{Table, DistanceType};
-use anyhow::{ anyhow};
 use lancedb::query::QueryBase;
 use lancedb::query::ExecutableQuery;
 use futures::TryStreamExt;
+use tauri::{AppHandle, Emitter};
 
-// This function gets a connection to the LanceDB database and opens the 'files' table.
-pub async fn get_lancedb_files_table() -> anyhow::Result<Table> {
-    let database_path = crate::database::lancedb_ops::get_app_data_dir()
-        .ok_or_else(|| anyhow!("Could not get app data directory"))?
-        .join("my-lancedb");
+// --- MAIN SEARCH ORCHESTRATION ---
 
-    let db = lancedb::connect(database_path.to_str().unwrap())
-        .execute()
-        .await?;
-    let files_table = db.open_table("files").execute().await?;
-    Ok(files_table)
+/// Number of candidates each prong contributes to the ranked pool that pages are cut from.
+const CANDIDATE_POOL_SIZE: usize = 200;
+
+/// A date filter matching at most this many files is applied inside the file vector search;
+/// broader ones are left to the post-ranking filter, whose pool they are unlikely to empty.
+const DATE_PREFILTER_MAX_FILES: usize = 10_000;
+
+/// Event emitted by `stream_file_search` for each ranking stage.
+pub const SEARCH_RESULTS_EVENT: &str = "search_results";
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SearchStreamUpdate {
+    pub request_id: String,
+    pub stage: String, // "partial" (keyword prongs only) or "final" (re-ranked with vectors)
+    pub response: SearchResponse,
+    pub error: Option<String>,
 }
 
-// --- MAIN SEARCH ORCHESTRATION ---
+/// Results of the prongs that only need SQLite and finish in milliseconds.
+struct KeywordProngResults {
+    fts_results: Vec<SearchResult>,
+    folder_results: Vec<SearchResult>,
+    metadata_results: Vec<SearchResult>,
+}
 
 pub async fn perform_file_search(
     query: String,
     top_k: Option<usize>,
     filters: Option<SearchFilters>,
-) -> Result<Vec<SearchResult>, String> {
+) -> Result<SearchResponse, String> {
     println!("DEBUG: perform_file_search called with query: '{}'", query);
 
     let limit = top_k.unwrap_or(10);
     let (search_term, parsed_filters) = parse_query(&query);
-    let final_filters = filters.unwrap_or(parsed_filters);
+    let final_filters = merge_filters(filters, parsed_filters);
+    let compiled = query_compiler::compile(&search_term);
 
-    println!("DEBUG: Getting embedding for query");
-    let search_term_clone = search_term.clone();
-    let query_embedding = tokio::task::spawn_blocking(move || {
-        embed_and_store::get_embedding(&search_term_clone).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| format!("Task join error: {}", e))?
-    .map_err(|e| format!("Embedding error: {}", e))?;
-    let normalized_embedding = embed_and_store::normalize(query_embedding);
+    let normalized_embedding = embed_query(&compiled.semantic_text_or(&search_term)).await?;
 
-    println!("DEBUG: Getting lancedb table");
-    let lancedb_files_table = get_lancedb_files_table().await.map_err(|e| e.to_string())?;
+    log::debug!("Opening vector index");
+    let vector_index = LanceVectorIndex::open().await?;
+    let lancedb_folder_table = open_folder_table().await;
 
     println!("DEBUG: Starting hybrid search");
     let results = perform_hybrid_search(
-        &lancedb_files_table,
+        &vector_index,
+        lancedb_folder_table.as_ref(),
         &normalized_embedding,
+        &query,
         &search_term,
         final_filters,
         limit,
     )
     .await?;
 
-    println!("DEBUG: Search completed with {} results", results.len());
+    log::debug!("Search completed with {} results", results.results.len());
     Ok(results)
 }
 
+/// Returns the next page of a previous search without re-running any prong.
+pub fn fetch_search_page(cursor: &str, limit: Option<usize>) -> Result<SearchResponse, String> {
+    search_session::fetch_page(cursor, limit.unwrap_or(10))
+}
+
+/// Streaming search: emits the keyword-only ranking as soon as the SQLite prongs finish,
+/// then a re-ranked update once the embedding and vector prongs complete.
+pub async fn stream_file_search(
+    app: AppHandle,
+    request_id: String,
+    query: String,
+    top_k: Option<usize>,
+    filters: Option<SearchFilters>,
+) -> Result<SearchResponse, String> {
+    let limit = top_k.unwrap_or(10);
+    let (search_term, parsed_filters) = parse_query(&query);
+    let final_filters = merge_filters(filters, parsed_filters);
+    let intent = classify_intent(&search_term);
+    let compiled = query_compiler::compile(&search_term);
+
+    // Start the slow embedding request while the fast prongs run
+    let embedding_task = tokio::spawn(embed_query_owned(compiled.semantic_text_or(&search_term)));
+
+    let keyword_results =
+        run_keyword_prongs(&search_term, &final_filters, CANDIDATE_POOL_SIZE).await?;
+    let partial = rank_and_paginate(
+        &query,
+        &search_term,
+        intent,
+        Vec::new(),
+        keyword_results.fts_results.clone(),
+        keyword_results.folder_results.clone(),
+        Vec::new(),
+        keyword_results.metadata_results.clone(),
+        &final_filters,
+        limit,
+    )
+    .await?;
+    emit_search_update(&app, &request_id, "partial", &partial, None);
+
+    let vector_stage = async {
+        let normalized_embedding = embedding_task
+            .await
+            .map_err(|e| format!("Task join error: {}", e))??;
+        let vector_index = LanceVectorIndex::open().await?;
+        let folder_table = open_folder_table().await;
+        Ok::<_, String>(
+            run_vector_prongs(
+                &vector_index,
+                folder_table.as_ref(),
+                &normalized_embedding,
+                &compiled,
+                &final_filters,
+                CANDIDATE_POOL_SIZE,
+            )
+            .await?,
+        )
+    };
+
+    match vector_stage.await {
+        Ok((vector_results, folder_vector_results)) => {
+            let final_response = rank_and_paginate(
+                &query,
+                &search_term,
+                intent,
+                vector_results,
+                keyword_results.fts_results,
+                keyword_results.folder_results,
+                folder_vector_results,
+                keyword_results.metadata_results,
+                &final_filters,
+                limit,
+            )
+            .await?;
+            emit_search_update(&app, &request_id, "final", &final_response, None);
+            Ok(final_response)
+        }
+        Err(e) => {
+            // Keep the keyword results on screen; the vector prong is best-effort here
+            eprintln!("Vector stage failed for streamed search: {}", e);
+            emit_search_update(&app, &request_id, "final", &partial, Some(e));
+            Ok(partial)
+        }
+    }
+}
+
+fn emit_search_update(
+    app: &AppHandle,
+    request_id: &str,
+    stage: &str,
+    response: &SearchResponse,
+    error: Option<String>,
+) {
+    let update = SearchStreamUpdate {
+        request_id: request_id.to_string(),
+        stage: stage.to_string(),
+        response: response.clone(),
+        error,
+    };
+    app.emit(SEARCH_RESULTS_EVENT, &update).ok();
+}
+
+async fn embed_query(search_term: &str) -> Result<Vec<f32>, String> {
+    embed_query_owned(search_term.to_string()).await
+}
+
+async fn embed_query_owned(search_term: String) -> Result<Vec<f32>, String> {
+    log::debug!("Getting embedding for query");
+    let query_embedding = tokio::task::spawn_blocking(move || {
+        embed_and_store::get_embedding(&search_term).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+    .map_err(|e| format!("Embedding error: {}", e))?;
+    Ok(embed_and_store::normalize(query_embedding))
+}
+
+async fn open_folder_table() -> Option<Table> {
+    match get_lancedb_folder_table().await {
+        Ok(table) => Some(table),
+        Err(e) => {
+            eprintln!("Semantic folder search unavailable: {}", e);
+            None
+        }
+    }
+}
+
 pub async fn perform_hybrid_search(
-    lancedb_files_table: &Table,
+    vector_index: &dyn VectorIndex,
+    lancedb_folder_table: Option<&Table>,
     normalized_embedding: &[f32],
+    raw_query: &str,
     query: &str,
     filters: SearchFilters,
     limit: usize,
-) -> Result<Vec<SearchResult>, String> {
+) -> Result<SearchResponse, String> {
     let intent = classify_intent(query);
     println!("DEBUG: Classified intent: {:?}", intent);
+    let compiled = query_compiler::compile(query);
 
     // --- Stage 2: Execute Search Prongs ---
-    let vector_results_fut =
-        search_similar_files_lancedb(lancedb_files_table, normalized_embedding, limit * 2);
+    let (vector_prongs, keyword_prongs) = tokio::join!(
+        run_vector_prongs(
+            vector_index,
+            lancedb_folder_table,
+            normalized_embedding,
+            &compiled,
+            &filters,
+            CANDIDATE_POOL_SIZE
+        ),
+        run_keyword_prongs(query, &filters, CANDIDATE_POOL_SIZE)
+    );
+    let (vector_results, folder_vector_results) = vector_prongs?;
+    let keyword_results = keyword_prongs?;
 
+    // --- Stage 3, 4 & 5: Combine, rank, facet and paginate ---
+    rank_and_paginate(
+        raw_query,
+        query,
+        intent,
+        vector_results,
+        keyword_results.fts_results,
+        keyword_results.folder_results,
+        folder_vector_results,
+        keyword_results.metadata_results,
+        &filters,
+        limit,
+    )
+    .await
+}
+
+async fn run_keyword_prongs(
+    query: &str,
+    filters: &SearchFilters,
+    pool_size: usize,
+) -> Result<KeywordProngResults, String> {
     let query_clone = query.to_string();
     let filters_clone = filters.clone();
-    let sync_search_task = tokio::task::spawn_blocking(move || {
-        let db = crate::database::get_connection();
-        let fts_results = search_files_fts(&db, &query_clone, limit * 2);
-        let folder_results = search_folders_by_name(&db, &query_clone, limit);
-        let metadata_results = advanced_search(&db, Some(query_clone.clone()), filters_clone, limit);
-        (fts_results, folder_results, metadata_results)
-    });
+    let (fts_results, folder_results, metadata_results) = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        let fts_results = search_files_fts(&db, &query_clone, &filters_clone, pool_size);
+        let folder_results =
+            search_folders_by_name(&db, &query_clone, &filters_clone, pool_size / 2);
+        let metadata_results =
+            advanced_search(&db, Some(query_clone.clone()), filters_clone, pool_size / 2);
+        Ok::<_, String>((fts_results, folder_results, metadata_results))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))??;
+
+    Ok(KeywordProngResults {
+        fts_results: fts_results?,
+        folder_results: folder_results?,
+        metadata_results: metadata_results?,
+    })
+}
 
-    let vector_results = vector_results_fut.await?;
-    let (fts_results_res, folder_results_res, metadata_results_res) = sync_search_task.await.map_err(|e| format!("Task join error: {}", e))?;
+async fn run_vector_prongs(
+    vector_index: &dyn VectorIndex,
+    lancedb_folder_table: Option<&Table>,
+    normalized_embedding: &[f32],
+    compiled: &CompiledQuery,
+    filters: &SearchFilters,
+    pool_size: usize,
+) -> Result<(Vec<SearchResult>, Vec<SearchResult>), String> {
+    // Exclusions and scoped terms constrain the vector prongs too
+    let file_filter = compiled.to_lancedb_filter("name", Some("content"));
+    let folder_filter = compiled.to_lancedb_filter("folder_name", None);
 
-    // --- Stage 3 & 4: Combine, Rank, and Finalize ---
-    let mut combined_results = combine_and_rank_results(
+    let vector_results = match date_prefilter(filters).await? {
+        // Nothing is in the date range
+        Some(ids) if ids.is_empty() => Vec::new(),
+        date_ids => {
+            let id_filter = date_ids.map(|ids| {
+                let id_list = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+                format!("id IN ({})", id_list)
+            });
+            let file_filter = match (file_filter, id_filter) {
+                (Some(a), Some(b)) => Some(format!("({}) AND {}", a, b)),
+                (a, b) => a.or(b),
+            };
+            search_similar_files(
+                vector_index,
+                normalized_embedding,
+                file_filter.as_deref(),
+                pool_size,
+            )
+            .await?
+        }
+    };
+    let folder_vector_results = match lancedb_folder_table {
+        Some(table) => search_folders_semantic(
+            table,
+            normalized_embedding,
+            folder_filter.as_deref(),
+            pool_size / 2,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Semantic folder search failed: {}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    Ok((vector_results, folder_vector_results))
+}
+
+/// Ids of the files in the date range, or None when there is no date filter or the range
+/// holds too many files to list in a LanceDB predicate.
+async fn date_prefilter(filters: &SearchFilters) -> Result<Option<Vec<i64>>, String> {
+    let Some((date_clause, date_params)) = filters.sqlite_date_filter("") else {
+        return Ok(None);
+    };
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        let sql = format!("SELECT id FROM files WHERE {} LIMIT ?", date_clause);
+        let mut params_values = date_params;
+        params_values.push((DATE_PREFILTER_MAX_FILES as i64 + 1).into());
+        let mut stmt = db.prepare(&sql).map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map(rusqlite::params_from_iter(params_values), |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        Ok((ids.len() <= DATE_PREFILTER_MAX_FILES).then_some(ids))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Merges the prong results, applies facet filters, sorts deterministically and
+/// stores the ranked list in a search session so later pages come from the same ranking.
+/// `raw_query` is the query as typed; usage affinity is keyed on it like `open_file` is.
+/// `query` is the search term the prongs ran, which facets are counted over.
+#[allow(clippy::too_many_arguments)]
+async fn rank_and_paginate(
+    raw_query: &str,
+    query: &str,
+    intent: SearchIntent,
+    vector_results: Vec<SearchResult>,
+    fts_results: Vec<SearchResult>,
+    folder_results: Vec<SearchResult>,
+    folder_vector_results: Vec<SearchResult>,
+    metadata_results: Vec<SearchResult>,
+    filters: &SearchFilters,
+    limit: usize,
+) -> Result<SearchResponse, String> {
+    // Facets count every keyword match, but a full vector prong means semantic matches went
+    // past the pool uncounted
+    let pool_capped = vector_results.len() >= CANDIDATE_POOL_SIZE
+        || folder_vector_results.len() >= CANDIDATE_POOL_SIZE / 2;
+    let combined_results = combine_and_rank_results(
         intent,
         vector_results,
-        fts_results_res?,
-        folder_results_res?,
-        metadata_results_res?,
+        fts_results,
+        folder_results,
+        folder_vector_results,
+        metadata_results,
     );
 
-    combined_results.sort_by(|a, b| {
+    let facet_filters = filters.clone();
+    let query_clone = raw_query.to_string();
+    let search_term = query.to_string();
+    let (mut ranked_results, mut facets) = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        // Facets only guide refinement; a failed count must not lose the results
+        let facets = count_facets(&db, &search_term, &facet_filters, &combined_results)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to count facets: {}", e);
+                SearchFacets::default()
+            });
+        let mut kept = filter_candidates(&db, combined_results, &facet_filters)?;
+        // Usage history only reorders; a failure here must not lose the results
+        if let Err(e) = interactions::apply_usage_boost(&db, &mut kept, &query_clone) {
+            eprintln!("Failed to apply usage boost: {}", e);
+        }
+        if let Err(e) = mark_availability(&db, &mut kept) {
+            eprintln!("Failed to check volume availability: {}", e);
+        }
+        Ok::<_, String>((kept, facets))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))??;
+    facets.pool_capped = pool_capped;
+
+    // Ties are broken by id so the order (and therefore every page) is reproducible
+    ranked_results.sort_by(|a, b| {
         b.relevance_score
             .partial_cmp(&a.relevance_score)
             .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.id.cmp(&b.id))
     });
-    combined_results.truncate(limit);
 
-    println!(
-        "DEBUG: Final hybrid search returned {} results",
-        combined_results.len()
-    );
-    Ok(combined_results)
+    log::debug!("Hybrid search ranked {} candidates", ranked_results.len());
+    Ok(search_session::start_session(ranked_results, facets, limit))
 }
 
-// --- CORRECTED LANCEDB SEARCH LOGIC FOR v0.22.0 ---
+/// File vector prong: nearest files in the vector index.
+pub async fn search_similar_files(
+    vector_index: &dyn VectorIndex,
+    normalized_query: &[f32],
+    filter: Option<&str>,
+    limit: usize,
+) -> Result<Vec<SearchResult>, String> {
+    log::debug!("Starting vector search");
+    let hits = vector_index.search(normalized_query, filter, limit).await?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| SearchResult {
+            id: format!("file-{}", hit.file_id),
+            result_type: "file".to_string(),
+            title: hit.name,
+            path: hit.path,
+            relevance_score: hit.relevance,
+            match_type: SearchMatchType::Vector(hit.relevance),
+            snippet: None,
+            available: true,
+        })
+        .collect())
+}
 
-pub async fn search_similar_files_lancedb(
-    lancedb_table: &Table,
+/// Semantic folder prong: nearest folders by their name/hierarchy/children embedding.
+/// LanceDB only stores the SQLite folder id, so hits are enriched with name and path from SQLite.
+pub async fn search_folders_semantic(
+    folder_table: &Table,
     normalized_query: &[f32],
+    filter: Option<&str>,
     limit: usize,
 ) -> Result<Vec<SearchResult>, String> {
-    println!("DEBUG: Starting LanceDB vector search");
+    log::debug!("Starting LanceDB folder vector search");
 
-    // Execute vector search with explicit IVF_PQ parameters
-    let mut search_result = lancedb_table
+    let mut query = folder_table
         .vector_search(normalized_query.to_vec())
-        .map_err(|e| format!("Failed to create vector query: {}", e))?
-        .distance_type(DistanceType::Cosine) // Match index training distance type
-        .limit((limit as u64).try_into().unwrap()) // Ensure limit is u64
-        .nprobes(20) // Search 5-15% of partitions for good recall/latency balance
-        .refine_factor(10) // Add refine step for better accuracy
+        .map_err(|e| format!("Failed to create folder vector query: {}", e))?
+        .column("folder_metadata_embed")
+        .distance_type(DistanceType::Cosine)
+        .limit(limit);
+    if let Some(filter) = filter {
+        query = query.only_if(filter);
+    }
+    let mut search_result = query
         .execute()
         .await
-        .map_err(|e| format!("LanceDB search error: {}", e))?;
-
-    let mut search_results = Vec::new();
+        .map_err(|e| format!("LanceDB folder search error: {}", e))?;
 
-    // Process results as a stream
+    let mut hits: Vec<(i64, f32)> = Vec::new();
     while let Some(batch) = search_result
         .try_next()
         .await
         .map_err(|e| format!("Error reading batch: {}", e))?
     {
         let batch: RecordBatch = batch;
-
         if batch.num_rows() == 0 {
             continue;
         }
 
         let id_array = batch
             .column_by_name("id")
-            .ok_or("ID column not found in LanceDB result".to_string())?
+            .ok_or("ID column not found in LanceDB folder result".to_string())?
             .as_any()
             .downcast_ref::<Int32Array>()
             .ok_or("Failed to downcast 'id' column".to_string())?;
-
-        let name_array = batch
-            .column_by_name("name")
-            .ok_or("Name column not found in LanceDB result".to_string())?
-            .as_any()
-            .downcast_ref::<StringArray>()
-            .ok_or("Failed to downcast 'name' column".to_string())?;
-
-        let path_array = batch
-            .column_by_name("path")
-            .ok_or("Path column not found in LanceDB result".to_string())?
+        let distance_array = batch
+            .column_by_name("_distance")
+            .ok_or("Distance column not found".to_string())?
             .as_any()
-            .downcast_ref::<StringArray>()
-            .ok_or("Failed to downcast 'path' column".to_string())?;
+            .downcast_ref::<arrow_array::Float32Array>()
+            .ok_or("Failed to downcast '_distance' column".to_string())?;
 
         for i in 0..batch.num_rows() {
-            let id = id_array.value(i);
-            let name = name_array.value(i).to_string();
-            let path = path_array.value(i).to_string();
-            let distance = batch
-                .column_by_name("_distance")
-                .ok_or("Distance column not found".to_string())?
-                .as_any()
-                .downcast_ref::<arrow_array::Float32Array>()
-                .ok_or("Failed to downcast '_distance' column".to_string())?
-                .value(i);
-            let relevance = 1.0 - distance;
-
-            search_results.push(SearchResult {
-                id: format!("file-{}", id),
-                result_type: "file".to_string(),
-                title: name,
-                path,
-                relevance_score: relevance,
-                match_type: SearchMatchType::Vector(relevance),
-                snippet: None,
-            });
+            let relevance = 1.0 - distance_array.value(i);
+            // Zero vectors produce NaN distances
+            if relevance.is_finite() {
+                hits.push((id_array.value(i) as i64, relevance));
+            }
         }
     }
 
-    Ok(search_results)
+    if hits.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        let mut stmt = db
+            .prepare("SELECT name, path FROM folders WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+
+        let mut results = Vec::with_capacity(hits.len());
+        for (id, relevance) in hits {
+            let row = stmt
+                .query_row([id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+                .optional()
+                .map_err(|e| e.to_string())?;
+            if let Some((name, path)) = row {
+                results.push(SearchResult {
+                    id: format!("folder-{}", id),
+                    result_type: "folder".to_string(),
+                    title: name,
+                    path,
+                    relevance_score: relevance,
+                    match_type: SearchMatchType::Vector(relevance),
+                    snippet: None,
+                    available: true,
+                });
+            }
+        }
+        Ok(results)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
 }
\ No newline at end of file
@@ -0,0 +1,362 @@
+// Index export and import. An archive is a tar.gz holding a manifest, a copy of the SQLite
+// database and the LanceDB directory. Rules and settings travel inside the database, so the
+// path remaps of an import apply to the rules too.
+use crate::database::lancedb_ops::{get_app_data_dir, lancedb_path};
+use crate::database::migrations::{self, LANCEDB_STORE, SQLITE_STORE};
+use crate::embed_and_store::{EMBEDDING_DIM, EMBEDDING_MODEL};
+use crate::file_scanner::claim_scan_slot;
+use crate::file_scanner::lancedb::remap_path_prefix_lancedb;
+use crate::file_scanner::volume_registry::{mount_prefix, remap_path_root};
+use arrow_schema::DataType;
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::backup::Progress;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tauri::async_runtime::block_on;
+
+/// Bumped whenever the archive layout changes.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const DATABASE_FILE: &str = "database.db";
+const LANCEDB_DIR: &str = "lancedb";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: String,
+    pub embedding_model: String,
+    pub embedding_dim: usize,
+    pub sqlite_schema_version: i64,
+    pub lancedb_schema_version: i64,
+    pub file_count: i64,
+    /// Home directory of the exporting machine, used to remap paths on import
+    pub home_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathRemap {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub manifest: IndexManifest,
+    pub remaps_applied: Vec<PathRemap>,
+    /// Copy of the database that was replaced
+    pub backup_path: String,
+}
+
+/// Working folder next to the live stores, so LanceDB can be moved into place with a rename.
+/// Removed when dropped.
+struct StagingDir(PathBuf);
+
+impl StagingDir {
+    fn create(kind: &str) -> Result<Self, String> {
+        let dir = get_app_data_dir()
+            .ok_or("Could not get app data directory")?
+            .join(format!("staging-{}-{}", kind, Utc::now().format("%Y%m%d%H%M%S")));
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        Ok(Self(dir))
+    }
+
+    fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.0) {
+            eprintln!("Failed to remove staging folder {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+fn copy_dir_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn build_manifest(db: &Connection) -> Result<IndexManifest, String> {
+    let file_count = db
+        .query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0))
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(IndexManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now().to_rfc3339(),
+        embedding_model: EMBEDDING_MODEL.to_string(),
+        embedding_dim: EMBEDDING_DIM,
+        sqlite_schema_version: migrations::current_version(db, SQLITE_STORE)
+            .map_err(|e| format!("Database error: {}", e))?,
+        lancedb_schema_version: migrations::current_version(db, LANCEDB_STORE)
+            .map_err(|e| format!("Database error: {}", e))?,
+        file_count,
+        home_dir: dirs::home_dir().map(|p| p.to_string_lossy().into_owned()),
+    })
+}
+
+fn export_index_sync(destination: &Path) -> Result<IndexManifest, String> {
+    let _scan_guard = claim_scan_slot().ok_or("Cannot export the index while a scan is running")?;
+    let staging = StagingDir::create("export")?;
+
+    // The writer lock keeps both stores still while they are copied
+    let manifest = {
+        let db = crate::database::get_connection();
+        db.backup(DatabaseName::Main, staging.join(DATABASE_FILE), None)
+            .map_err(|e| format!("Database backup failed: {}", e))?;
+        let lancedb_dir = lancedb_path().ok_or("Could not get app data directory")?;
+        copy_dir_all(&lancedb_dir, &staging.join(LANCEDB_DIR))
+            .map_err(|e| format!("Failed to copy LanceDB: {}", e))?;
+        build_manifest(&db)?
+    };
+    write_json(&staging.join(MANIFEST_FILE), &manifest)?;
+
+    // Written under a temporary name so a failed export never leaves a truncated archive
+    let mut partial_name = destination
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", destination.display()))?
+        .to_os_string();
+    partial_name.push(".partial");
+    let partial = destination.with_file_name(partial_name);
+    let write_archive = || -> std::io::Result<()> {
+        let encoder = GzEncoder::new(File::create(&partial)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_path_with_name(staging.join(MANIFEST_FILE), MANIFEST_FILE)?;
+        builder.append_path_with_name(staging.join(DATABASE_FILE), DATABASE_FILE)?;
+        builder.append_dir_all(LANCEDB_DIR, staging.join(LANCEDB_DIR))?;
+        builder.into_inner()?.finish()?;
+        fs::rename(&partial, destination)
+    };
+    if let Err(e) = write_archive() {
+        fs::remove_file(&partial).ok();
+        return Err(format!("Failed to write {}: {}", destination.display(), e));
+    }
+
+    println!(
+        "Exported {} files to {}",
+        manifest.file_count,
+        destination.display()
+    );
+    Ok(manifest)
+}
+
+/// Writes the whole index to a versioned archive at `destination`.
+#[tauri::command]
+pub async fn export_index(destination: String) -> Result<IndexManifest, String> {
+    tokio::task::spawn_blocking(move || export_index_sync(Path::new(&destination)))
+        .await
+        .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Rejects archives whose vectors this build can't search or whose schema it doesn't know.
+fn validate_manifest(manifest: &IndexManifest) -> Result<(), String> {
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(format!(
+            "Archive format {} is newer than this version supports ({})",
+            manifest.format_version, ARCHIVE_FORMAT_VERSION
+        ));
+    }
+    if manifest.embedding_model != EMBEDDING_MODEL || manifest.embedding_dim != EMBEDDING_DIM {
+        return Err(format!(
+            "Archive was built with {} ({} dimensions), but this version uses {} ({} dimensions)",
+            manifest.embedding_model, manifest.embedding_dim, EMBEDDING_MODEL, EMBEDDING_DIM
+        ));
+    }
+    if manifest.sqlite_schema_version > migrations::latest_version(SQLITE_STORE)
+        || manifest.lancedb_schema_version > migrations::latest_version(LANCEDB_STORE)
+    {
+        return Err("Archive was exported by a newer version of the app".to_string());
+    }
+    Ok(())
+}
+
+/// Checks that the staged vector columns really have the manifest's dimension.
+async fn validate_lancedb(dir: &Path) -> Result<(), String> {
+    let db = lancedb::connect(dir.to_str().ok_or("Failed to convert path to string")?)
+        .execute()
+        .await
+        .map_err(|e| format!("Archived LanceDB can't be opened: {}", e))?;
+    for (table_name, column) in [("files", "vector"), ("file_embeddings", "content_vec")] {
+        let table = db
+            .open_table(table_name)
+            .execute()
+            .await
+            .map_err(|e| format!("Archived LanceDB has no {} table: {}", table_name, e))?;
+        let schema = table.schema().await.map_err(|e| e.to_string())?;
+        let dim = match schema.field_with_name(column).map(|f| f.data_type().clone()) {
+            Ok(DataType::FixedSizeList(_, dim)) => dim as usize,
+            _ => return Err(format!("Archived {} table has no {} column", table_name, column)),
+        };
+        if dim != EMBEDDING_DIM {
+            return Err(format!(
+                "Archived {} vectors have {} dimensions, expected {}",
+                table_name, dim, EMBEDDING_DIM
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Without explicit remaps, paths under the exporting machine's home move to this one's.
+fn default_remaps(manifest: &IndexManifest) -> Vec<PathRemap> {
+    let current_home = dirs::home_dir().map(|p| p.to_string_lossy().into_owned());
+    match (&manifest.home_dir, current_home) {
+        (Some(from), Some(to)) if *from != to => vec![PathRemap {
+            from: from.clone(),
+            to,
+        }],
+        _ => Vec::new(),
+    }
+}
+
+fn prepare_staged_database(path: &Path, remaps: &[PathRemap]) -> Result<(), String> {
+    let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .map_err(|e| format!("Archived database can't be opened: {}", e))?;
+    let integrity: String = db
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Database error: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("Archived database is damaged: {}", integrity));
+    }
+    let tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
+    for remap in remaps {
+        remap_path_root(&tx, &remap.from, &remap.to).map_err(|e| format!("Database error: {}", e))?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+async fn remap_staged_lancedb(dir: &Path, remaps: &[PathRemap]) -> Result<(), String> {
+    if remaps.is_empty() {
+        return Ok(());
+    }
+    let db = lancedb::connect(dir.to_str().ok_or("Failed to convert path to string")?)
+        .execute()
+        .await
+        .map_err(|e| e.to_string())?;
+    let files_table = db.open_table("files").execute().await.map_err(|e| e.to_string())?;
+    for remap in remaps {
+        remap_path_prefix_lancedb(&files_table, &mount_prefix(&remap.from), &mount_prefix(&remap.to))
+            .await
+            .map_err(|e| format!("Failed to remap LanceDB paths: {}", e))?;
+    }
+    Ok(())
+}
+
+fn import_index_sync(archive: &Path, path_remaps: Vec<PathRemap>) -> Result<ImportSummary, String> {
+    let _scan_guard = claim_scan_slot().ok_or("Cannot import an index while a scan is running")?;
+    let staging = StagingDir::create("import")?;
+
+    let file = File::open(archive).map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+    // unpack refuses entries that would land outside the staging folder
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(&staging.0)
+        .map_err(|e| format!("Failed to unpack {}: {}", archive.display(), e))?;
+
+    let manifest: IndexManifest = fs::read(staging.join(MANIFEST_FILE))
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Archive has no readable manifest: {}", e))?;
+    validate_manifest(&manifest)?;
+
+    let staged_db = staging.join(DATABASE_FILE);
+    let staged_lancedb = staging.join(LANCEDB_DIR);
+    block_on(validate_lancedb(&staged_lancedb))?;
+
+    let remaps = if path_remaps.is_empty() {
+        default_remaps(&manifest)
+    } else {
+        path_remaps
+    };
+    prepare_staged_database(&staged_db, &remaps)?;
+    block_on(remap_staged_lancedb(&staged_lancedb, &remaps))?;
+
+    let backup_path = {
+        // Taken before the writer, so a reader that is waiting on the writer can finish first
+        let _readers = crate::database::drain_read_pool()?;
+        let mut db = crate::database::get_connection();
+        let current = migrations::current_version(&db, SQLITE_STORE).map_err(|e| e.to_string())?;
+        let backup = migrations::backup_database(&db, current)
+            .map_err(|e| format!("Failed to back up the current database: {}", e))?;
+
+        // The old LanceDB stays beside the new one until the SQLite restore succeeded
+        let live_lancedb = lancedb_path().ok_or("Could not get app data directory")?;
+        let previous_lancedb =
+            live_lancedb.with_extension(format!("bak-{}", Utc::now().format("%Y%m%d%H%M%S")));
+        fs::rename(&live_lancedb, &previous_lancedb)
+            .map_err(|e| format!("Failed to move the current LanceDB aside: {}", e))?;
+        let swapped = fs::rename(&staged_lancedb, &live_lancedb)
+            .map_err(|e| format!("Failed to move the imported LanceDB into place: {}", e))
+            .and_then(|_| {
+                db.restore(DatabaseName::Main, &staged_db, None::<fn(Progress)>)
+                    .map_err(|e| format!("Database restore failed: {}", e))
+            });
+        if let Err(e) = swapped {
+            fs::remove_dir_all(&live_lancedb).ok();
+            fs::rename(&previous_lancedb, &live_lancedb).ok();
+            return Err(e);
+        }
+        if let Err(e) = fs::remove_dir_all(&previous_lancedb) {
+            eprintln!("Failed to remove {}: {}", previous_lancedb.display(), e);
+        }
+
+        // The restore copies pages, not the connection's journal mode
+        db.execute_batch("PRAGMA journal_mode = WAL;").map_err(|e| e.to_string())?;
+        migrations::run_migrations(&db).map_err(|e| format!("Migration failed: {}", e))?;
+        backup
+    };
+
+    block_on(migrations::run_lancedb_migrations())?;
+    match block_on(crate::database::journal::recover_pending_writes()) {
+        Ok(report) => println!("Write journal recovery after import: {:?}", report),
+        Err(e) => eprintln!("Write journal recovery after import failed: {}", e),
+    }
+
+    println!(
+        "Imported {} files from {} ({} path remaps)",
+        manifest.file_count,
+        archive.display(),
+        remaps.len()
+    );
+    Ok(ImportSummary {
+        manifest,
+        remaps_applied: remaps,
+        backup_path: backup_path.to_string_lossy().into_owned(),
+    })
+}
+
+/// Replaces the current index with an exported archive. Paths are rewritten by `path_remaps`,
+/// or from the exporting machine's home folder to this one's when none are given.
+#[tauri::command]
+pub async fn import_index(
+    archive: String,
+    path_remaps: Option<Vec<PathRemap>>,
+) -> Result<ImportSummary, String> {
+    tokio::task::spawn_blocking(move || {
+        import_index_sync(Path::new(&archive), path_remaps.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
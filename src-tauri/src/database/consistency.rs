@@ -0,0 +1,378 @@
+// SQLite owns file and chunk ids. Every LanceDB row must point back at a SQLite row with the
+// same id, and every SQLite row should have its LanceDB counterpart.
+use crate::database::journal::{delete_lancedb_rows, forget_files};
+use crate::embed_and_store::EMBEDDING_DIM;
+use crate::file_scanner::claim_scan_slot;
+use crate::file_scanner::lancedb::{
+    delete_rows_with_ids_lancedb, find_vector_faults_lancedb, get_lancedb_tables,
+    load_chunk_ids_lancedb, load_file_ids_lancedb, VectorFault,
+};
+use rusqlite::Connection;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct IndexedFile {
+    pub id: i64,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ConsistencyReport {
+    /// SQLite files with no LanceDB `files` row
+    pub files_missing_in_lancedb: Vec<IndexedFile>,
+    /// LanceDB `files` rows with no SQLite file of the same id and path
+    pub orphaned_lancedb_files: Vec<IndexedFile>,
+    /// SQLite chunk ids with no LanceDB `file_embeddings` row
+    pub chunks_missing_in_lancedb: Vec<i64>,
+    /// LanceDB `file_embeddings` ids with no SQLite chunk of the same id and file
+    pub orphaned_lancedb_chunks: Vec<i64>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.files_missing_in_lancedb.is_empty()
+            && self.orphaned_lancedb_files.is_empty()
+            && self.chunks_missing_in_lancedb.is_empty()
+            && self.orphaned_lancedb_chunks.is_empty()
+    }
+}
+
+fn load_sqlite_files(db: &Connection) -> rusqlite::Result<HashMap<i64, String>> {
+    let mut stmt = db.prepare("SELECT id, path FROM files")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+fn load_sqlite_chunks(db: &Connection) -> rusqlite::Result<HashMap<i64, i64>> {
+    let mut stmt = db.prepare("SELECT id, file_id FROM file_chunks")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+fn compare(
+    sqlite_files: &HashMap<i64, String>,
+    sqlite_chunks: &HashMap<i64, i64>,
+    lance_files: &[(i32, String)],
+    lance_chunks: &[(i32, i32)],
+) -> ConsistencyReport {
+    let mut report = ConsistencyReport::default();
+
+    let mut matched_files = HashSet::new();
+    for (id, path) in lance_files {
+        let id = *id as i64;
+        if sqlite_files.get(&id) == Some(path) {
+            matched_files.insert(id);
+        } else {
+            report.orphaned_lancedb_files.push(IndexedFile {
+                id,
+                path: path.clone(),
+            });
+        }
+    }
+    report.files_missing_in_lancedb = sqlite_files
+        .iter()
+        .filter(|(id, _)| !matched_files.contains(*id))
+        .map(|(id, path)| IndexedFile {
+            id: *id,
+            path: path.clone(),
+        })
+        .collect();
+
+    let mut matched_chunks = HashSet::new();
+    for (id, file_id) in lance_chunks {
+        let id = *id as i64;
+        if sqlite_chunks.get(&id) == Some(&(*file_id as i64)) {
+            matched_chunks.insert(id);
+        } else {
+            report.orphaned_lancedb_chunks.push(id);
+        }
+    }
+    report.chunks_missing_in_lancedb = sqlite_chunks
+        .keys()
+        .filter(|id| !matched_chunks.contains(*id))
+        .copied()
+        .collect();
+
+    report.files_missing_in_lancedb.sort_by_key(|f| f.id);
+    report.orphaned_lancedb_files.sort_by_key(|f| f.id);
+    report.chunks_missing_in_lancedb.sort_unstable();
+    report.orphaned_lancedb_chunks.sort_unstable();
+    report
+}
+
+/// SQLite ids and paths, read together so they describe the same moment.
+struct SqliteSnapshot {
+    files: HashMap<i64, String>,
+    chunks: HashMap<i64, i64>,
+}
+
+async fn load_sqlite_snapshot() -> Result<SqliteSnapshot, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        let files = load_sqlite_files(&db).map_err(|e| format!("Database error: {}", e))?;
+        let chunks = load_sqlite_chunks(&db).map_err(|e| format!("Database error: {}", e))?;
+        Ok::<_, String>(SqliteSnapshot { files, chunks })
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+async fn compare_stores(sqlite: &SqliteSnapshot) -> Result<ConsistencyReport, String> {
+    let (files_table, file_emb_table) = get_lancedb_tables()
+        .await
+        .map_err(|e| format!("Failed to open LanceDB tables: {}", e))?;
+    let lance_files = load_file_ids_lancedb(&files_table)
+        .await
+        .map_err(|e| format!("Failed to read LanceDB files: {}", e))?;
+    let lance_chunks = load_chunk_ids_lancedb(&file_emb_table)
+        .await
+        .map_err(|e| format!("Failed to read LanceDB embeddings: {}", e))?;
+    Ok(compare(&sqlite.files, &sqlite.chunks, &lance_files, &lance_chunks))
+}
+
+/// Lists rows that exist in only one of the two stores, or whose ids no longer agree.
+#[tauri::command]
+pub async fn check_index_consistency() -> Result<ConsistencyReport, String> {
+    let sqlite = load_sqlite_snapshot().await?;
+    let report = compare_stores(&sqlite).await?;
+    println!(
+        "Index consistency: {} files missing in LanceDB, {} orphaned LanceDB files, {} chunks missing, {} orphaned chunks",
+        report.files_missing_in_lancedb.len(),
+        report.orphaned_lancedb_files.len(),
+        report.chunks_missing_in_lancedb.len(),
+        report.orphaned_lancedb_chunks.len()
+    );
+    Ok(report)
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VectorIssue {
+    /// LanceDB table: `files` or `file_embeddings`
+    pub table: &'static str,
+    pub id: i64,
+    pub fault: VectorFault,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct IndexReport {
+    pub cross_store: ConsistencyReport,
+    /// Files flagged as embedded that have no chunk rows
+    pub files_missing_embeddings: Vec<IndexedFile>,
+    /// LanceDB vectors that are missing, all zeros or of the wrong dimension
+    pub vector_issues: Vec<VectorIssue>,
+    /// Full-text rows whose file is gone
+    pub stale_fts_rows: Vec<i64>,
+    /// Files with no full-text row
+    pub files_missing_in_fts: Vec<i64>,
+}
+
+impl IndexReport {
+    pub fn is_clean(&self) -> bool {
+        self.cross_store.is_consistent()
+            && self.files_missing_embeddings.is_empty()
+            && self.vector_issues.is_empty()
+            && self.stale_fts_rows.is_empty()
+            && self.files_missing_in_fts.is_empty()
+    }
+}
+
+/// The checks that only need SQLite.
+struct SqliteFindings {
+    files_missing_embeddings: Vec<IndexedFile>,
+    stale_fts_rows: Vec<i64>,
+    files_missing_in_fts: Vec<i64>,
+}
+
+fn check_sqlite(db: &Connection) -> rusqlite::Result<SqliteFindings> {
+    let ids = |sql: &str| -> rusqlite::Result<Vec<i64>> {
+        let mut stmt = db.prepare(sql)?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    };
+    let mut stmt = db.prepare(
+        "SELECT id, path FROM files
+         WHERE vector_indexed = 1 AND id NOT IN (SELECT file_id FROM file_chunks)
+         ORDER BY id",
+    )?;
+    let files_missing_embeddings = stmt
+        .query_map([], |row| Ok(IndexedFile { id: row.get(0)?, path: row.get(1)? }))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // files_fts reads its columns from `files`, so only the docsize shadow table shows
+    // which rows the full-text index itself holds
+    Ok(SqliteFindings {
+        files_missing_embeddings,
+        stale_fts_rows: ids(
+            "SELECT id FROM files_fts_docsize WHERE id NOT IN (SELECT id FROM files) ORDER BY id",
+        )?,
+        files_missing_in_fts: ids(
+            "SELECT id FROM files WHERE id NOT IN (SELECT id FROM files_fts_docsize) ORDER BY id",
+        )?,
+    })
+}
+
+async fn find_vector_issues(sqlite: &SqliteSnapshot) -> Result<Vec<VectorIssue>, String> {
+    let (files_table, file_emb_table) = get_lancedb_tables()
+        .await
+        .map_err(|e| format!("Failed to open LanceDB tables: {}", e))?;
+    let file_faults = find_vector_faults_lancedb(&files_table, "vector", EMBEDDING_DIM)
+        .await
+        .map_err(|e| format!("Failed to read LanceDB file vectors: {}", e))?;
+    let chunk_faults = find_vector_faults_lancedb(&file_emb_table, "content_vec", EMBEDDING_DIM)
+        .await
+        .map_err(|e| format!("Failed to read LanceDB chunk vectors: {}", e))?;
+
+    // A file without chunks never had a vector to store, so it gets a zero one
+    let files_with_chunks: HashSet<i64> = sqlite.chunks.values().copied().collect();
+    let mut issues: Vec<VectorIssue> = file_faults
+        .into_iter()
+        .filter(|(id, fault)| {
+            *fault == VectorFault::WrongDimension || files_with_chunks.contains(&(*id as i64))
+        })
+        .map(|(id, fault)| VectorIssue { table: "files", id: id as i64, fault })
+        .collect();
+    issues.extend(chunk_faults.into_iter().map(|(id, fault)| VectorIssue {
+        table: "file_embeddings",
+        id: id as i64,
+        fault,
+    }));
+    Ok(issues)
+}
+
+async fn collect_index_report() -> Result<(IndexReport, SqliteSnapshot), String> {
+    let sqlite = load_sqlite_snapshot().await?;
+    let findings = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        check_sqlite(&db).map_err(|e| format!("Database error: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))??;
+
+    let report = IndexReport {
+        cross_store: compare_stores(&sqlite).await?,
+        files_missing_embeddings: findings.files_missing_embeddings,
+        vector_issues: find_vector_issues(&sqlite).await?,
+        stale_fts_rows: findings.stale_fts_rows,
+        files_missing_in_fts: findings.files_missing_in_fts,
+    };
+    Ok((report, sqlite))
+}
+
+/// Reports every cross-store inconsistency: orphans, missing embeddings, unusable vectors
+/// and stale full-text rows.
+#[tauri::command]
+pub async fn check_index() -> Result<IndexReport, String> {
+    let (report, _) = collect_index_report().await?;
+    log::debug!(
+        "Index check: clean = {}, {} vector issues, {} stale FTS rows, {} files missing embeddings",
+        report.is_clean(),
+        report.vector_issues.len(),
+        report.stale_fts_rows.len(),
+        report.files_missing_embeddings.len()
+    );
+    Ok(report)
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct RepairReport {
+    pub orphaned_lancedb_rows_removed: usize,
+    /// Files dropped from both stores so the next scan indexes them again
+    pub files_requeued: Vec<IndexedFile>,
+    pub fts_rebuilt: bool,
+    /// What the check still finds afterwards
+    pub remaining: IndexReport,
+}
+
+/// Files whose vectors are incomplete or unusable. Vectors can't be rebuilt here, so the
+/// repair forgets these files and leaves them to the next scan.
+fn files_to_requeue(report: &IndexReport, sqlite: &SqliteSnapshot) -> BTreeSet<i64> {
+    let mut file_ids: BTreeSet<i64> = report
+        .cross_store
+        .files_missing_in_lancedb
+        .iter()
+        .chain(&report.files_missing_embeddings)
+        .map(|f| f.id)
+        .collect();
+    let chunk_owners = report
+        .cross_store
+        .chunks_missing_in_lancedb
+        .iter()
+        .filter_map(|chunk_id| sqlite.chunks.get(chunk_id).copied());
+    file_ids.extend(chunk_owners);
+    for issue in &report.vector_issues {
+        let owner = match issue.table {
+            "files" => Some(issue.id),
+            _ => sqlite.chunks.get(&issue.id).copied(),
+        };
+        file_ids.extend(owner.filter(|id| sqlite.files.contains_key(id)));
+    }
+    file_ids
+}
+
+/// Fixes what `check_index` finds where that is possible without re-embedding: removes
+/// orphans, rebuilds the full-text index and requeues files with missing or unusable vectors.
+#[tauri::command]
+pub async fn repair_index() -> Result<RepairReport, String> {
+    let _scan_guard = claim_scan_slot().ok_or("Cannot repair the index while a scan is running")?;
+    let (report, sqlite) = collect_index_report().await?;
+    let mut repair = RepairReport::default();
+
+    // Rows only LanceDB has are never returned by searches that join back to SQLite
+    let (files_table, file_emb_table) = get_lancedb_tables()
+        .await
+        .map_err(|e| format!("Failed to open LanceDB tables: {}", e))?;
+    let orphaned_files: Vec<i64> =
+        report.cross_store.orphaned_lancedb_files.iter().map(|f| f.id).collect();
+    let orphaned_chunks = &report.cross_store.orphaned_lancedb_chunks;
+    delete_rows_with_ids_lancedb(&file_emb_table, orphaned_chunks)
+        .await
+        .map_err(|e| format!("Failed to remove orphaned LanceDB chunks: {}", e))?;
+    delete_rows_with_ids_lancedb(&files_table, &orphaned_files)
+        .await
+        .map_err(|e| format!("Failed to remove orphaned LanceDB files: {}", e))?;
+    repair.orphaned_lancedb_rows_removed = orphaned_files.len() + orphaned_chunks.len();
+
+    // LanceDB first: if SQLite fails afterwards, the next check finds these files again
+    let requeued: Vec<i64> = files_to_requeue(&report, &sqlite).into_iter().collect();
+    let requeued_chunks: Vec<i64> = sqlite
+        .chunks
+        .iter()
+        .filter(|(_, file_id)| requeued.binary_search(file_id).is_ok())
+        .map(|(chunk_id, _)| *chunk_id)
+        .collect();
+    delete_lancedb_rows(&requeued, &requeued_chunks).await?;
+
+    let needs_fts_rebuild =
+        !report.stale_fts_rows.is_empty() || !report.files_missing_in_fts.is_empty();
+    let requeued_ids = requeued.clone();
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        forget_files(&db, &requeued_ids)?;
+        if needs_fts_rebuild {
+            // Re-reads every row from `files`, dropping entries for deleted rows
+            db.execute("INSERT INTO files_fts (files_fts) VALUES ('rebuild')", [])
+                .map_err(|e| format!("Failed to rebuild full-text index: {}", e))?;
+        }
+        Ok::<_, String>(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))??;
+
+    repair.files_requeued = requeued
+        .iter()
+        .filter_map(|id| {
+            let path = sqlite.files.get(id)?.clone();
+            Some(IndexedFile { id: *id, path })
+        })
+        .collect();
+    repair.fts_rebuilt = needs_fts_rebuild;
+    repair.remaining = collect_index_report().await?.0;
+    println!(
+        "Index repair: removed {} orphaned LanceDB rows, requeued {} files, FTS rebuilt = {}, clean = {}",
+        repair.orphaned_lancedb_rows_removed,
+        repair.files_requeued.len(),
+        repair.fts_rebuilt,
+        repair.remaining.is_clean()
+    );
+    Ok(repair)
+}
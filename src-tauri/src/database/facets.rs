@@ -0,0 +1,952 @@
+// Facet counting over the keyword match set and facet filtering of the hybrid candidate pool
+use crate::database::query_compiler::{self, escape_like, CompiledQuery};
+use crate::database::search::{SearchFilters, SearchResult};
+use crate::database::temporal::DateField;
+use crate::file_scanner::types::FileCategory;
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+use std::collections::HashMap;
+use std::path::Path;
+
+const MAX_FACET_VALUES: usize = 20;
+
+// Paths bound per metadata lookup, well under SQLite's variable limit
+const METADATA_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: usize,
+}
+
+/// Counts cover every keyword match in the index plus the ranked candidate pool.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SearchFacets {
+    pub category: Vec<FacetCount>,
+    pub extension: Vec<FacetCount>,
+    pub folder: Vec<FacetCount>,
+    pub modified: Vec<FacetCount>,
+    pub size: Vec<FacetCount>,
+    /// How many files and folders the counts cover
+    pub counted: usize,
+    /// True when a vector prong filled its share of the pool, so more semantic matches exist
+    /// than were counted
+    pub pool_capped: bool,
+}
+
+/// The metadata of a single candidate that facets and filters are computed from.
+#[derive(Debug, Clone)]
+struct CandidateMetadata {
+    category: String,
+    extension: String,
+    created_at: Option<DateTime<Utc>>,
+    modified_at: Option<DateTime<Utc>>,
+    accessed_at: Option<DateTime<Utc>>,
+    size: Option<u64>,
+}
+
+impl CandidateMetadata {
+    fn timestamp(&self, field: DateField) -> Option<DateTime<Utc>> {
+        match field {
+            DateField::Created => self.created_at,
+            DateField::Modified => self.modified_at,
+            DateField::Accessed => self.accessed_at,
+        }
+    }
+}
+
+fn included_roots(db: &Connection) -> Result<Vec<String>, String> {
+    Ok(crate::database::rules::get_included_paths_sync(db)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect())
+}
+
+/// Drops the candidates the facet filters exclude.
+/// Must run before the candidate list is truncated to the requested page size.
+pub fn filter_candidates(
+    db: &Connection,
+    candidates: Vec<SearchResult>,
+    filters: &SearchFilters,
+) -> Result<Vec<SearchResult>, String> {
+    let now = Utc::now();
+    let metadata = load_candidate_metadata(db, &candidates)?;
+    Ok(candidates
+        .into_iter()
+        .zip(metadata)
+        .filter(|(candidate, meta)| matches_filters(candidate, meta, filters, now))
+        .map(|(candidate, _)| candidate)
+        .collect())
+}
+
+/// Counts facets over every file and folder the keyword prongs match for `query`, plus the
+/// `pool` candidates (so vector hits count too), with `filters` applied. The counts are
+/// grouped in SQLite, so they don't stop where the ranked pool does.
+pub fn count_facets(
+    db: &Connection,
+    query: &str,
+    filters: &SearchFilters,
+    pool: &[SearchResult],
+) -> Result<SearchFacets, String> {
+    let roots = included_roots(db)?;
+    count_facets_at(db, &query_compiler::compile(query), filters, pool, &roots, Utc::now())
+}
+
+/// One group of the facet count: rows sharing every facet value.
+struct FacetGroup {
+    category: Option<String>,
+    extension: String,
+    modified: String,
+    size: String,
+    folder: String,
+    count: usize,
+}
+
+fn count_facets_at(
+    db: &Connection,
+    compiled: &CompiledQuery,
+    filters: &SearchFilters,
+    pool: &[SearchResult],
+    roots: &[String],
+    now: DateTime<Utc>,
+) -> Result<SearchFacets, String> {
+    let pool_ids = |prefix: &str| -> Vec<i64> {
+        pool.iter()
+            .filter_map(|c| c.id.strip_prefix(prefix)?.parse().ok())
+            .collect()
+    };
+    let mut groups = count_file_groups(db, compiled, filters, &pool_ids("file-"), roots, now)?;
+    groups.extend(count_folder_groups(db, compiled, filters, &pool_ids("folder-"), roots, now)?);
+
+    let mut counts: [HashMap<String, usize>; 5] = Default::default();
+    let mut counted = 0;
+    for group in groups {
+        let category = group
+            .category
+            .unwrap_or_else(|| format!("{:?}", FileCategory::from_extension(&group.extension)));
+        // The remaining facet filters apply to the group values, exactly as to a candidate
+        let selected = |selection: &Option<Vec<String>>, value: &str| match non_empty(selection) {
+            Some(values) => values.iter().any(|v| v.eq_ignore_ascii_case(value)),
+            None => true,
+        };
+        if !selected(&filters.categories, &category)
+            || !selected(&filters.modified_buckets, &group.modified)
+            || !selected(&filters.size_buckets, &group.size)
+        {
+            continue;
+        }
+
+        counted += group.count;
+        for (facet, value) in counts.iter_mut().zip([
+            category,
+            group.extension,
+            group.folder,
+            group.modified,
+            group.size,
+        ]) {
+            if !value.is_empty() {
+                *facet.entry(value).or_insert(0) += group.count;
+            }
+        }
+    }
+
+    let [category, extension, folder, modified, size] = counts;
+    Ok(SearchFacets {
+        category: sorted_counts(category),
+        extension: sorted_counts(extension),
+        folder: sorted_counts(folder),
+        modified: sorted_counts(modified),
+        size: sorted_counts(size),
+        counted,
+        pool_capped: false,
+    })
+}
+
+/// Facet groups of the matching files. The FTS expression falls back like the FTS prong does,
+/// and is dropped last, so a bad expression still leaves the name matches counted.
+fn count_file_groups(
+    db: &Connection,
+    compiled: &CompiledQuery,
+    filters: &SearchFilters,
+    pool_ids: &[i64],
+    roots: &[String],
+    now: DateTime<Utc>,
+) -> Result<Vec<FacetGroup>, String> {
+    let mut expressions = vec![compiled.to_fts5(), compiled.to_fts5_fallback(), None];
+    expressions.dedup();
+    let mut last_error = None;
+    for expression in expressions {
+        let mut matches = Vec::new();
+        let mut params_values: Vec<Value> = Vec::new();
+        if let Some(expression) = expression.as_ref() {
+            matches.push("id IN (SELECT rowid FROM files_fts WHERE files_fts MATCH ?)".to_string());
+            params_values.push(expression.clone().into());
+        }
+        if let Some((name_filter, name_params)) = compiled.to_sqlite_name_filter("name") {
+            matches.push(format!("({})", name_filter));
+            params_values.extend(name_params.into_iter().map(Into::into));
+        }
+        if !pool_ids.is_empty() {
+            matches.push(format!("id IN ({})", id_list(pool_ids)));
+        }
+        if matches.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut clauses = vec![format!("({})", matches.join(" OR "))];
+        if let Some(extensions) = non_empty(&filters.extensions) {
+            clauses.push(format!(
+                "lower(extension) IN ({})",
+                vec!["?"; extensions.len()].join(", ")
+            ));
+            params_values.extend(
+                extensions
+                    .iter()
+                    .map(|e| Value::Text(e.trim_start_matches('.').to_lowercase())),
+            );
+        }
+        push_shared_filters(&mut clauses, &mut params_values, filters, "file_size");
+
+        let sql = format!(
+            "SELECT category, lower(extension), {}, {}, {}, COUNT(*) FROM files
+             WHERE {} GROUP BY 1, 2, 3, 4, 5",
+            modified_bucket_sql("updated_at", now),
+            size_bucket_sql("file_size"),
+            folder_key_sql(roots),
+            clauses.join(" AND ")
+        );
+        match query_groups(db, &sql, params_values) {
+            Ok(groups) => return Ok(groups),
+            Err(e) => {
+                eprintln!("Facet count failed: {}", e);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_default())
+}
+
+/// Facet groups of the matching folders, which have no extension and count as "Folder".
+fn count_folder_groups(
+    db: &Connection,
+    compiled: &CompiledQuery,
+    filters: &SearchFilters,
+    pool_ids: &[i64],
+    roots: &[String],
+    now: DateTime<Utc>,
+) -> Result<Vec<FacetGroup>, String> {
+    if non_empty(&filters.extensions).is_some() {
+        return Ok(Vec::new());
+    }
+    let mut matches = Vec::new();
+    let mut params_values: Vec<Value> = Vec::new();
+    // Matched on the path, like the folder name prong
+    if let Some((name_filter, name_params)) = compiled.to_sqlite_name_filter("path") {
+        matches.push(format!("({})", name_filter));
+        params_values.extend(name_params.into_iter().map(Into::into));
+    }
+    if !pool_ids.is_empty() {
+        matches.push(format!("id IN ({})", id_list(pool_ids)));
+    }
+    if matches.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut clauses = vec![format!("({})", matches.join(" OR "))];
+    push_shared_filters(&mut clauses, &mut params_values, filters, "folder_size");
+    let sql = format!(
+        "SELECT 'Folder', '', {}, {}, {}, COUNT(*) FROM folders
+         WHERE {} GROUP BY 3, 4, 5",
+        modified_bucket_sql("updated_at", now),
+        size_bucket_sql("folder_size"),
+        folder_key_sql(roots),
+        clauses.join(" AND ")
+    );
+    query_groups(db, &sql, params_values)
+}
+
+/// The date, size and folder filters, which `files` and `folders` rows share.
+fn push_shared_filters(
+    clauses: &mut Vec<String>,
+    params_values: &mut Vec<Value>,
+    filters: &SearchFilters,
+    size_column: &str,
+) {
+    if let Some((date_clause, date_params)) = filters.sqlite_date_filter("") {
+        clauses.push(date_clause);
+        params_values.extend(date_params);
+    }
+    if let Some(min_size) = filters.min_size {
+        clauses.push(format!("{} >= ?", size_column));
+        params_values.push((min_size as i64).into());
+    }
+    if let Some(max_size) = filters.max_size {
+        clauses.push(format!("{} <= ?", size_column));
+        params_values.push((max_size as i64).into());
+    }
+    if let Some(folders) = non_empty(&filters.folders) {
+        // Path::starts_with compares whole components, so "/a/b" doesn't contain "/a/bc"
+        let mut alternatives = Vec::with_capacity(folders.len());
+        for folder in folders {
+            let trimmed = folder.trim_end_matches(['/', '\\']);
+            let prefix = format!("{}{}", trimmed, std::path::MAIN_SEPARATOR);
+            alternatives.push("path = ? OR path = ? OR path LIKE ? ESCAPE '\\'");
+            params_values.push(Value::Text(folder.clone()));
+            params_values.push(Value::Text(trimmed.to_string()));
+            params_values.push(Value::Text(format!("{}%", escape_like(&prefix))));
+        }
+        clauses.push(format!("({})", alternatives.join(" OR ")));
+    }
+}
+
+fn query_groups(
+    db: &Connection,
+    sql: &str,
+    params_values: Vec<Value>,
+) -> Result<Vec<FacetGroup>, String> {
+    let mut stmt = db.prepare(sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params_from_iter(params_values), |row| {
+            Ok(FacetGroup {
+                category: row.get(0)?,
+                extension: row.get(1)?,
+                modified: row.get(2)?,
+                size: row.get(3)?,
+                folder: row.get(4)?,
+                count: row.get::<_, i64>(5)? as usize,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| e.to_string())
+}
+
+fn id_list(ids: &[i64]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn sql_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// `modified_bucket` over a timestamp column. Comparing against the cutoffs, rather than
+/// subtracting julian days, keeps a timestamp exactly on a boundary in the same bucket.
+fn modified_bucket_sql(column: &str, now: DateTime<Utc>) -> String {
+    let mut sql = format!("CASE WHEN julianday({}) IS NULL THEN 'unknown'", column);
+    for (days, bucket) in [(1, "today"), (7, "past_week"), (30, "past_month"), (365, "past_year")] {
+        let cutoff = (now - chrono::Duration::days(days)).to_rfc3339();
+        sql.push_str(&format!(
+            " WHEN julianday({}) > julianday({}) THEN '{}'",
+            column,
+            sql_literal(&cutoff),
+            bucket
+        ));
+    }
+    sql.push_str(" ELSE 'older' END");
+    sql
+}
+
+/// `size_bucket` over a size column.
+fn size_bucket_sql(column: &str) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    let mut sql = format!("CASE WHEN {} IS NULL THEN 'unknown'", column);
+    let limits = [(100 * KB, "tiny"), (MB, "small"), (10 * MB, "medium"), (100 * MB, "large")];
+    for (limit, bucket) in limits {
+        sql.push_str(&format!(" WHEN {} < {} THEN '{}'", column, limit, bucket));
+    }
+    sql.push_str(" ELSE 'huge' END");
+    sql
+}
+
+/// The folder a `path` is grouped under: the first folder below the included root it lives in,
+/// or the first folder below the filesystem root when it is outside every included path.
+fn folder_key_sql(roots: &[String]) -> String {
+    let sep = sql_literal(std::path::MAIN_SEPARATOR_STR);
+    // `base` followed by the first component of `rest`, or `single` when `rest` is one component
+    let below = |base: &str, rest: &str, single: &str| {
+        format!(
+            "CASE WHEN instr({rest}, {sep}) > 0 \
+             THEN {base} || substr({rest}, 1, instr({rest}, {sep}) - 1) ELSE {single} END",
+            rest = rest,
+            sep = sep,
+            base = base,
+            single = single,
+        )
+    };
+
+    let mut roots: Vec<&String> = roots.iter().filter(|r| !r.trim().is_empty()).collect();
+    // Longest first, so the innermost root wins
+    roots.sort_by_key(|r| std::cmp::Reverse(r.len()));
+    let mut whens = String::new();
+    for root in roots {
+        let trimmed = root.trim_end_matches(std::path::MAIN_SEPARATOR);
+        let prefix = format!("{}{}", trimmed, std::path::MAIN_SEPARATOR);
+        let len = prefix.chars().count();
+        whens.push_str(&format!(
+            " WHEN path = {} OR substr(path, 1, {}) = {} THEN {}",
+            sql_literal(trimmed),
+            len,
+            sql_literal(&prefix),
+            below(&sql_literal(&prefix), &format!("substr(path, {})", len + 1), &sql_literal(root))
+        ));
+    }
+    // Outside every root: below the filesystem root, which ends at the first separator
+    let base = format!("substr(path, 1, instr(path, {}))", sep);
+    let rest = format!("substr(path, instr(path, {}) + 1)", sep);
+    let outside = below(&base, &rest, &base);
+    if whens.is_empty() {
+        outside
+    } else {
+        format!("CASE{} ELSE {} END", whens, outside)
+    }
+}
+
+fn sorted_counts(counts: HashMap<String, usize>) -> Vec<FacetCount> {
+    let mut values: Vec<FacetCount> = counts
+        .into_iter()
+        .map(|(value, count)| FacetCount { value, count })
+        .collect();
+    values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    values.truncate(MAX_FACET_VALUES);
+    values
+}
+
+type FileRow = (Option<String>, String, Option<i64>, Option<String>, Option<String>, Option<String>);
+type FolderRow = (Option<i64>, Option<String>, Option<String>, Option<String>);
+
+/// Runs `sql` (which must end in `IN ({})`) over `paths` in batches and keys the rows by path.
+fn load_rows_by_path<T>(
+    db: &Connection,
+    sql: &str,
+    paths: &[&str],
+    map_row: impl Fn(&rusqlite::Row) -> rusqlite::Result<T>,
+) -> Result<HashMap<String, T>, String> {
+    let mut rows = HashMap::with_capacity(paths.len());
+    for batch in paths.chunks(METADATA_BATCH_SIZE) {
+        let placeholders = vec!["?"; batch.len()].join(", ");
+        let mut stmt = db
+            .prepare(&sql.replace("{}", &placeholders))
+            .map_err(|e| e.to_string())?;
+        let mapped = stmt
+            .query_map(params_from_iter(batch.iter()), |row| {
+                Ok((row.get::<_, String>(0)?, map_row(row)?))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in mapped {
+            let (path, value) = row.map_err(|e| e.to_string())?;
+            rows.insert(path, value);
+        }
+    }
+    Ok(rows)
+}
+
+/// Metadata for every candidate, in candidate order, from one batched lookup per table.
+fn load_candidate_metadata(
+    db: &Connection,
+    candidates: &[SearchResult],
+) -> Result<Vec<CandidateMetadata>, String> {
+    let paths_of = |folders: bool| -> Vec<&str> {
+        candidates
+            .iter()
+            .filter(|c| (c.result_type == "folder") == folders)
+            .map(|c| c.path.as_str())
+            .collect()
+    };
+    let (folder_paths, file_paths) = (paths_of(true), paths_of(false));
+
+    let folders: HashMap<String, FolderRow> = load_rows_by_path(
+        db,
+        "SELECT path, folder_size, created_at, updated_at, last_accessed FROM folders WHERE path IN ({})",
+        &folder_paths,
+        |row| Ok((row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+    )?;
+    let files: HashMap<String, FileRow> = load_rows_by_path(
+        db,
+        "SELECT path, category, extension, file_size, created_at, updated_at, last_accessed
+         FROM files WHERE path IN ({})",
+        &file_paths,
+        |row| {
+            Ok((
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+            ))
+        },
+    )?;
+
+    Ok(candidates
+        .iter()
+        .map(|candidate| {
+            if candidate.result_type == "folder" {
+                folder_metadata(folders.get(&candidate.path).cloned().unwrap_or_default())
+            } else {
+                file_metadata(&candidate.path, files.get(&candidate.path).cloned())
+            }
+        })
+        .collect())
+}
+
+fn folder_metadata(row: FolderRow) -> CandidateMetadata {
+    let (size, created_at, updated_at, last_accessed) = row;
+    CandidateMetadata {
+        category: "Folder".to_string(),
+        extension: String::new(),
+        created_at: created_at.as_deref().and_then(parse_timestamp),
+        modified_at: updated_at.as_deref().and_then(parse_timestamp),
+        accessed_at: last_accessed.as_deref().and_then(parse_timestamp),
+        size: size.map(|s| s.max(0) as u64),
+    }
+}
+
+fn file_metadata(path: &str, row: Option<FileRow>) -> CandidateMetadata {
+    match row {
+        Some((category, extension, size, created_at, updated_at, last_accessed)) => CandidateMetadata {
+            category: category
+                .unwrap_or_else(|| format!("{:?}", FileCategory::from_extension(&extension))),
+            extension: extension.to_lowercase(),
+            created_at: created_at.as_deref().and_then(parse_timestamp),
+            modified_at: updated_at.as_deref().and_then(parse_timestamp),
+            accessed_at: last_accessed.as_deref().and_then(parse_timestamp),
+            size: size.map(|s| s.max(0) as u64),
+        },
+        None => {
+            // Not in SQLite (e.g. a vector-only hit): fall back to the path and filesystem.
+            let path = Path::new(path);
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("")
+                .to_lowercase();
+            let fs_meta = std::fs::metadata(path).ok();
+            CandidateMetadata {
+                category: format!("{:?}", FileCategory::from_extension(&extension)),
+                extension,
+                created_at: fs_meta
+                    .as_ref()
+                    .and_then(|m| m.created().ok())
+                    .map(DateTime::<Utc>::from),
+                modified_at: fs_meta
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .map(DateTime::<Utc>::from),
+                accessed_at: fs_meta
+                    .as_ref()
+                    .and_then(|m| m.accessed().ok())
+                    .map(DateTime::<Utc>::from),
+                size: fs_meta.map(|m| m.len()),
+            }
+        }
+    }
+}
+
+fn matches_filters(
+    candidate: &SearchResult,
+    meta: &CandidateMetadata,
+    filters: &SearchFilters,
+    now: DateTime<Utc>,
+) -> bool {
+    if let Some(extensions) = non_empty(&filters.extensions) {
+        if !extensions
+            .iter()
+            .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(&meta.extension))
+        {
+            return false;
+        }
+    }
+
+    if let Some(categories) = non_empty(&filters.categories) {
+        if !categories.iter().any(|c| c.eq_ignore_ascii_case(&meta.category)) {
+            return false;
+        }
+    }
+
+    if let Some(folders) = non_empty(&filters.folders) {
+        if !folders
+            .iter()
+            .any(|f| Path::new(&candidate.path).starts_with(f))
+        {
+            return false;
+        }
+    }
+
+    if let Some(buckets) = non_empty(&filters.modified_buckets) {
+        let bucket = modified_bucket(meta.modified_at, now);
+        if !buckets.iter().any(|b| b == bucket) {
+            return false;
+        }
+    }
+
+    if let Some(buckets) = non_empty(&filters.size_buckets) {
+        let bucket = size_bucket(meta.size);
+        if !buckets.iter().any(|b| b == bucket) {
+            return false;
+        }
+    }
+
+    if filters.date_from.is_some() || filters.date_to.is_some() {
+        let field = filters.date_field.unwrap_or(DateField::Modified);
+        let Some(timestamp) = meta.timestamp(field) else {
+            return false;
+        };
+        if let Some(from) = filters.date_from.as_deref().and_then(|d| parse_filter_date(d, false)) {
+            if timestamp < from {
+                return false;
+            }
+        }
+        if let Some(to) = filters.date_to.as_deref().and_then(|d| parse_filter_date(d, true)) {
+            if timestamp > to {
+                return false;
+            }
+        }
+    }
+
+    if filters.min_size.is_some() || filters.max_size.is_some() {
+        let Some(size) = meta.size else {
+            return false;
+        };
+        if filters.min_size.map_or(false, |min| size < min as u64) {
+            return false;
+        }
+        if filters.max_size.map_or(false, |max| size > max as u64) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn non_empty(values: &Option<Vec<String>>) -> Option<&Vec<String>> {
+    values.as_ref().filter(|v| !v.is_empty())
+}
+
+pub fn modified_bucket(modified_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> &'static str {
+    let Some(modified_at) = modified_at else {
+        return "unknown";
+    };
+    let age = now.signed_duration_since(modified_at);
+    if age < chrono::Duration::days(1) {
+        "today"
+    } else if age < chrono::Duration::days(7) {
+        "past_week"
+    } else if age < chrono::Duration::days(30) {
+        "past_month"
+    } else if age < chrono::Duration::days(365) {
+        "past_year"
+    } else {
+        "older"
+    }
+}
+
+pub fn size_bucket(size: Option<u64>) -> &'static str {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    match size {
+        None => "unknown",
+        Some(s) if s < 100 * KB => "tiny",
+        Some(s) if s < MB => "small",
+        Some(s) if s < 10 * MB => "medium",
+        Some(s) if s < 100 * MB => "large",
+        Some(_) => "huge",
+    }
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Filter dates may be full RFC 3339 timestamps or plain `YYYY-MM-DD` dates.
+/// A plain `date_to` covers the whole day, so it resolves to the day's last second.
+pub fn parse_filter_date(value: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    parse_timestamp(value).or_else(|| {
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+        let time = if end_of_day {
+            date.and_hms_opt(23, 59, 59)
+        } else {
+            date.and_hms_opt(0, 0, 0)
+        };
+        time.map(|dt| dt.and_utc())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::path::PathBuf;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 15, 12, 0, 0).unwrap()
+    }
+
+    fn open_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(&crate::database::schema::create_all_sql()).unwrap();
+        db
+    }
+
+    fn ago(seconds: i64) -> String {
+        (now() - chrono::Duration::seconds(seconds)).to_rfc3339()
+    }
+
+    fn add_file(db: &Connection, id: i64, path: &str, size: Option<i64>, updated_at: &str) {
+        let name = Path::new(path).file_name().unwrap().to_str().unwrap();
+        let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("");
+        // Markdown files carry a stored category; the rest fall back to their extension
+        let category = extension.eq_ignore_ascii_case("md").then_some("Document");
+        db.execute(
+            "INSERT INTO files (id, name, extension, path, content, file_size, category,
+                                created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, '', ?5, ?6, ?7, ?7)",
+            rusqlite::params![id, name, extension, path, size, category, updated_at],
+        )
+        .unwrap();
+        db.execute(
+            "INSERT INTO files_fts (rowid, name, content) VALUES (?1, ?2, '')",
+            rusqlite::params![id, name],
+        )
+        .unwrap();
+    }
+
+    fn add_folder(db: &Connection, id: i64, path: &str, size: i64, updated_at: &str) {
+        let name = Path::new(path).file_name().unwrap().to_str().unwrap();
+        db.execute(
+            "INSERT INTO folders (id, name, path, folder_size, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            rusqlite::params![id, name, path, size, updated_at],
+        )
+        .unwrap();
+    }
+
+    /// What `folder_key_sql` computes, per path.
+    fn top_level_folder(path: &Path, roots: &[String]) -> String {
+        let root = roots
+            .iter()
+            .filter(|r| !r.trim().is_empty() && path.starts_with(r.as_str()))
+            .max_by_key(|r| r.len())
+            .map(PathBuf::from);
+
+        let (base, rest) = match root {
+            Some(root) => {
+                let rest = path.strip_prefix(&root).map(Path::to_path_buf).unwrap_or_default();
+                (root, rest)
+            }
+            None => {
+                let mut components = path.components();
+                let mut base = PathBuf::new();
+                // Keep the drive prefix and root separator together (e.g. "C:\" or "/")
+                for comp in components.by_ref() {
+                    base.push(comp.as_os_str());
+                    use std::path::Component;
+                    if matches!(comp, Component::RootDir | Component::Normal(_)) {
+                        break;
+                    }
+                }
+                (base, components.as_path().to_path_buf())
+            }
+        };
+
+        // A file directly inside the base is grouped under the base itself
+        let mut rest_components = rest.components();
+        match (rest_components.next(), rest_components.next()) {
+            (Some(first), Some(_)) => base.join(first.as_os_str()).to_string_lossy().into_owned(),
+            _ => base.to_string_lossy().into_owned(),
+        }
+    }
+
+    fn candidate(id: &str, path: &str) -> SearchResult {
+        SearchResult {
+            id: id.to_string(),
+            result_type: if id.starts_with("folder-") { "folder" } else { "file" }.to_string(),
+            title: String::new(),
+            path: path.to_string(),
+            relevance_score: 0.0,
+            match_type: crate::database::search::SearchMatchType::Text(0.0),
+            snippet: None,
+            available: true,
+        }
+    }
+
+    fn count(
+        db: &Connection,
+        query: &str,
+        filters: &SearchFilters,
+        roots: &[String],
+    ) -> SearchFacets {
+        let compiled = query_compiler::compile(query);
+        count_facets_at(db, &compiled, filters, &[], roots, now()).unwrap()
+    }
+
+    fn values(counts: &[FacetCount]) -> Vec<(&str, usize)> {
+        let mut values: Vec<(&str, usize)> =
+            counts.iter().map(|c| (c.value.as_str(), c.count)).collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn buckets_split_at_their_boundaries() {
+        const KB: u64 = 1024;
+        const MB: u64 = 1024 * KB;
+        const DAY: i64 = 86_400;
+        assert_eq!(size_bucket(Some(100 * KB - 1)), "tiny");
+        assert_eq!(size_bucket(Some(100 * KB)), "small");
+        assert_eq!(size_bucket(Some(MB)), "medium");
+        assert_eq!(size_bucket(Some(100 * MB)), "huge");
+        let at = |age: i64| Some(now() - chrono::Duration::seconds(age));
+        assert_eq!(modified_bucket(at(DAY - 1), now()), "today");
+        assert_eq!(modified_bucket(at(DAY), now()), "past_week");
+        assert_eq!(modified_bucket(None, now()), "unknown");
+
+        // SQLite must put every row in the bucket the Rust functions pick
+        let db = open_db();
+        let rows: [(Option<u64>, Option<i64>); 9] = [
+            (None, Some(0)),
+            (Some(0), Some(DAY - 1)),
+            (Some(100 * KB - 1), Some(DAY)),
+            (Some(100 * KB), Some(7 * DAY - 1)),
+            (Some(MB - 1), Some(7 * DAY)),
+            (Some(MB), Some(30 * DAY)),
+            (Some(10 * MB), Some(365 * DAY - 1)),
+            (Some(100 * MB - 1), Some(365 * DAY)),
+            (Some(100 * MB), None),
+        ];
+        let mut expected: [HashMap<String, usize>; 2] = Default::default();
+        for (i, (size, age)) in rows.iter().enumerate() {
+            let updated_at = age.map(ago).unwrap_or_default();
+            let path = format!("/d/e/report{}.txt", i);
+            add_file(&db, i as i64 + 1, &path, size.map(|s| s as i64), &updated_at);
+            let modified = modified_bucket(age.and_then(at), now());
+            *expected[0].entry(modified.to_string()).or_insert(0) += 1;
+            *expected[1].entry(size_bucket(*size).to_string()).or_insert(0) += 1;
+        }
+
+        let facets = count(&db, "report*", &SearchFilters::default(), &[]);
+        assert_eq!(facets.counted, rows.len());
+        let [modified, size] = expected;
+        assert_eq!(values(&facets.modified), values(&sorted_counts(modified)));
+        assert_eq!(values(&facets.size), values(&sorted_counts(size)));
+        assert_eq!(
+            values(&facets.size),
+            [("huge", 1), ("large", 2), ("medium", 1), ("small", 2), ("tiny", 2), ("unknown", 1)]
+        );
+        assert_eq!(
+            values(&facets.modified),
+            [
+                ("older", 1),
+                ("past_month", 1),
+                ("past_week", 2),
+                ("past_year", 2),
+                ("today", 2),
+                ("unknown", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_cover_matches_beyond_the_pool() {
+        let db = open_db();
+        for id in 1..=250 {
+            add_file(&db, id, &format!("/d/e/report {}.txt", id), Some(10), &ago(60));
+        }
+        add_file(&db, 251, "/d/e/other.txt", Some(10), &ago(60));
+
+        let compiled = query_compiler::compile("report");
+        let facets = count_facets_at(&db, &compiled, &SearchFilters::default(), &[], &[], now())
+            .unwrap();
+        assert_eq!(facets.counted, 250);
+        assert_eq!(values(&facets.extension), [("txt", 250)]);
+
+        // Pool candidates the keywords don't match (e.g. vector hits) are counted too
+        let pool = [
+            candidate("file-251", "/d/e/other.txt"),
+            candidate("file-1", "/d/e/report 1.txt"),
+        ];
+        let facets = count_facets_at(&db, &compiled, &SearchFilters::default(), &pool, &[], now())
+            .unwrap();
+        assert_eq!(facets.counted, 251);
+    }
+
+    #[test]
+    fn facet_values_round_trip_as_filters() {
+        let db = open_db();
+        let roots = vec!["/home/u/docs".to_string(), "/home/u/music/".to_string()];
+        let files = [
+            "/home/u/docs/a/report.txt",
+            "/home/u/docs/a/b/Report.MD",
+            "/home/u/docs/c/report.rs",
+            "/home/u/music/live/report.mp3",
+            "/srv/data/report.csv",
+            "/srv/report_2024.pdf",
+        ];
+        let sizes = [Some(10), Some(200 * 1024), None, Some(20 << 20), Some(0), Some(5 << 20)];
+        let ages = [60, 3 * 86_400, 40 * 86_400, 86_400, 800 * 86_400, 10 * 86_400];
+        for (i, path) in files.iter().enumerate() {
+            add_file(&db, i as i64 + 1, path, sizes[i], &ago(ages[i]));
+        }
+        add_file(&db, 10, "/home/u/docs/a/plan.txt", Some(10), &ago(60));
+        add_folder(&db, 1, "/home/u/docs/a/reports", 5 << 20, &ago(10 * 86_400));
+        add_folder(&db, 2, "/srv/reports", 0, &ago(60));
+
+        let mut pool: Vec<SearchResult> = files
+            .iter()
+            .enumerate()
+            .map(|(i, path)| candidate(&format!("file-{}", i + 1), path))
+            .collect();
+        pool.push(candidate("folder-1", "/home/u/docs/a/reports"));
+        pool.push(candidate("folder-2", "/srv/reports"));
+        let metadata = load_candidate_metadata(&db, &pool).unwrap();
+
+        let facets = count(&db, "report", &SearchFilters::default(), &roots);
+        assert_eq!(facets.counted, pool.len());
+        // Grouping in SQLite agrees with the per-candidate metadata
+        let rust_values = |pick: &dyn Fn(&SearchResult, &CandidateMetadata) -> String| {
+            let mut counts = HashMap::new();
+            for (candidate, meta) in pool.iter().zip(&metadata) {
+                *counts.entry(pick(candidate, meta)).or_insert(0) += 1;
+            }
+            counts.remove("");
+            sorted_counts(counts)
+        };
+        let category = rust_values(&|_, m| m.category.clone());
+        let extension = rust_values(&|_, m| m.extension.clone());
+        let folder = rust_values(&|c, _| top_level_folder(Path::new(&c.path), &roots));
+        assert_eq!(values(&facets.category), values(&category));
+        assert_eq!(values(&facets.extension), values(&extension));
+        assert_eq!(values(&facets.folder), values(&folder));
+        assert_eq!(
+            values(&facets.folder),
+            [("/home/u/docs/a", 3), ("/home/u/docs/c", 1), ("/home/u/music/live", 1), ("/srv", 3)]
+        );
+
+        type Select = fn(&mut SearchFilters, Vec<String>);
+        let selections: [(&[FacetCount], Select); 5] = [
+            (&facets.category, |f, v| f.categories = Some(v)),
+            (&facets.extension, |f, v| f.extensions = Some(v)),
+            (&facets.folder, |f, v| f.folders = Some(v)),
+            (&facets.modified, |f, v| f.modified_buckets = Some(v)),
+            (&facets.size, |f, v| f.size_buckets = Some(v)),
+        ];
+        for (counts, select) in selections {
+            for facet in counts {
+                let mut filters = SearchFilters::default();
+                select(&mut filters, vec![facet.value.clone()]);
+                let filtered = count(&db, "report", &filters, &roots);
+                assert_eq!(filtered.counted, facet.count, "{}", facet.value);
+
+                // The pool filter keeps the same candidates the count covers
+                let kept = pool
+                    .iter()
+                    .zip(&metadata)
+                    .filter(|(c, m)| matches_filters(c, m, &filters, now()))
+                    .count();
+                assert_eq!(kept, facet.count, "{}", facet.value);
+            }
+        }
+    }
+}
@@ -0,0 +1,381 @@
+// Bottom-up folder aggregation over parent_folder_id: recursive file count, size,
+// average/max score and latest modification time
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default)]
+struct FolderAggregate {
+    file_count: i64,
+    total_size: i64,
+    score_sum: f64,
+    max_score: f64,
+    latest_modified: Option<String>,
+}
+
+impl FolderAggregate {
+    fn merge(&mut self, other: &FolderAggregate) {
+        self.file_count += other.file_count;
+        self.total_size += other.total_size;
+        self.score_sum += other.score_sum;
+        self.max_score = self.max_score.max(other.max_score);
+        // RFC 3339 UTC timestamps compare correctly as strings
+        if other.latest_modified > self.latest_modified {
+            self.latest_modified = other.latest_modified.clone();
+        }
+    }
+
+    fn average_score(&self) -> f64 {
+        if self.file_count == 0 {
+            return 0.0;
+        }
+        round_score(self.score_sum / self.file_count as f64)
+    }
+}
+
+fn round_score(score: f64) -> f64 {
+    let clamped = score.clamp(0.0, 10.0);
+    (clamped * 10.0).round() / 10.0
+}
+
+struct FolderNode {
+    path: String,
+    parent_id: Option<i64>,
+}
+
+fn load_folders(conn: &Connection) -> Result<HashMap<i64, FolderNode>> {
+    let mut stmt = conn.prepare("SELECT id, path, parent_folder_id FROM folders")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            FolderNode {
+                path: row.get(1)?,
+                parent_id: row.get(2)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Nearest indexed folder strictly above `path`.
+fn nearest_folder(path: &str, ids_by_path: &HashMap<&str, i64>) -> Option<i64> {
+    Path::new(path)
+        .ancestors()
+        .skip(1)
+        .find_map(|ancestor| ids_by_path.get(ancestor.to_str()?).copied())
+}
+
+/// Fills in `files.folder_id` and `folders.parent_folder_id` links that are missing,
+/// e.g. rows indexed before their parent folder was recorded.
+fn link_orphans(conn: &Connection, folders: &mut HashMap<i64, FolderNode>) -> Result<()> {
+    let ids_by_path: HashMap<&str, i64> = folders
+        .iter()
+        .map(|(id, f)| (f.path.as_str(), *id))
+        .collect();
+
+    let missing_parents: Vec<(i64, i64)> = folders
+        .iter()
+        .filter(|(_, f)| f.parent_id.is_none())
+        .filter_map(|(id, f)| nearest_folder(&f.path, &ids_by_path).map(|parent| (*id, parent)))
+        .collect();
+
+    let mut stmt = conn.prepare("SELECT id, path FROM files WHERE folder_id IS NULL")?;
+    let orphan_files: Vec<(i64, i64)> = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .filter_map(|row| row.ok())
+        .filter_map(|(id, path)| nearest_folder(&path, &ids_by_path).map(|folder| (id, folder)))
+        .collect();
+
+    let tx = conn.unchecked_transaction()?;
+    for (id, parent) in &missing_parents {
+        tx.execute(
+            "UPDATE folders SET parent_folder_id = ?1 WHERE id = ?2",
+            params![parent, id],
+        )?;
+    }
+    for (id, folder) in &orphan_files {
+        tx.execute(
+            "UPDATE files SET folder_id = ?1 WHERE id = ?2",
+            params![folder, id],
+        )?;
+    }
+    tx.commit()?;
+
+    for (id, parent) in missing_parents {
+        if let Some(folder) = folders.get_mut(&id) {
+            folder.parent_id = Some(parent);
+        }
+    }
+    Ok(())
+}
+
+fn folder_id_for_path(conn: &Connection, path: &str) -> Result<Option<i64>> {
+    let mut stmt = conn.prepare_cached("SELECT id FROM folders WHERE path = ?1")?;
+    stmt.query_row(params![path], |row| row.get(0)).optional()
+}
+
+/// Nearest indexed folder strictly above `path`, looked up one ancestor at a time.
+fn nearest_folder_in_db(conn: &Connection, path: &str) -> Result<Option<i64>> {
+    for ancestor in Path::new(path).ancestors().skip(1) {
+        let Some(ancestor) = ancestor.to_str() else {
+            continue;
+        };
+        if let Some(id) = folder_id_for_path(conn, ancestor)? {
+            return Ok(Some(id));
+        }
+    }
+    Ok(None)
+}
+
+/// Links files that have no folder yet and returns the folders they went into. The
+/// folder_id index finds them without scanning `files`.
+fn link_orphan_files(conn: &Connection) -> Result<HashSet<i64>> {
+    let orphans: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare_cached("SELECT id, path FROM files WHERE folder_id IS NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_>>()?
+    };
+    let mut linked = HashSet::new();
+    if orphans.is_empty() {
+        return Ok(linked);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for (id, path) in orphans {
+        if let Some(folder) = nearest_folder_in_db(&tx, &path)? {
+            tx.execute(
+                "UPDATE files SET folder_id = ?1 WHERE id = ?2",
+                params![folder, id],
+            )?;
+            linked.insert(folder);
+        }
+    }
+    tx.commit()?;
+    Ok(linked)
+}
+
+/// The given folders and every ancestor up to the root, linking parents that are missing
+/// on the way.
+fn load_ancestor_chains(
+    conn: &Connection,
+    start: &HashSet<i64>,
+) -> Result<HashMap<i64, FolderNode>> {
+    let mut folders: HashMap<i64, FolderNode> = HashMap::new();
+    let mut new_links = Vec::new();
+    {
+        let mut stmt =
+            conn.prepare_cached("SELECT path, parent_folder_id FROM folders WHERE id = ?1")?;
+        for id in start {
+            let mut current = Some(*id);
+            while let Some(folder_id) = current.filter(|id| !folders.contains_key(id)) {
+                let Some(mut node) = stmt
+                    .query_row(params![folder_id], |row| {
+                        Ok(FolderNode {
+                            path: row.get(0)?,
+                            parent_id: row.get(1)?,
+                        })
+                    })
+                    .optional()?
+                else {
+                    break;
+                };
+                if node.parent_id.is_none() {
+                    node.parent_id = nearest_folder_in_db(conn, &node.path)?;
+                    if let Some(parent) = node.parent_id {
+                        new_links.push((folder_id, parent));
+                    }
+                }
+                current = node.parent_id;
+                folders.insert(folder_id, node);
+            }
+        }
+    }
+
+    if !new_links.is_empty() {
+        let tx = conn.unchecked_transaction()?;
+        for (id, parent) in &new_links {
+            tx.execute(
+                "UPDATE folders SET parent_folder_id = ?1 WHERE id = ?2",
+                params![parent, id],
+            )?;
+        }
+        tx.commit()?;
+    }
+    Ok(folders)
+}
+
+const DIRECT_AGGREGATE_SQL: &str =
+    "SELECT folder_id, COUNT(*), SUM(COALESCE(file_size, 0)), SUM(score), MAX(score), MAX(updated_at)
+     FROM files";
+
+fn row_to_direct_aggregate(row: &rusqlite::Row) -> Result<(i64, FolderAggregate)> {
+    Ok((
+        row.get(0)?,
+        FolderAggregate {
+            file_count: row.get(1)?,
+            total_size: row.get(2)?,
+            score_sum: row.get(3)?,
+            max_score: row.get(4)?,
+            latest_modified: row.get(5)?,
+        },
+    ))
+}
+
+/// Aggregates of the files directly inside every folder, in a single grouped query.
+fn direct_aggregates(conn: &Connection) -> Result<HashMap<i64, FolderAggregate>> {
+    let sql = format!(
+        "{} WHERE folder_id IS NOT NULL GROUP BY folder_id",
+        DIRECT_AGGREGATE_SQL
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], row_to_direct_aggregate)?;
+    rows.collect()
+}
+
+/// Aggregates of the files directly inside one folder.
+fn direct_aggregate(conn: &Connection, folder_id: i64) -> Result<FolderAggregate> {
+    let sql = format!(
+        "{} WHERE folder_id = ?1 GROUP BY folder_id",
+        DIRECT_AGGREGATE_SQL
+    );
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let mut rows = stmt.query_map(params![folder_id], row_to_direct_aggregate)?;
+    match rows.next() {
+        Some(row) => Ok(row?.1),
+        None => Ok(FolderAggregate::default()),
+    }
+}
+
+fn depth(path: &str) -> usize {
+    Path::new(path).components().count()
+}
+
+fn write_aggregates(conn: &Connection, aggregates: &HashMap<i64, FolderAggregate>) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE folders
+             SET file_count = ?1, folder_size = ?2, score = ?3, max_score = ?4, latest_modified = ?5
+             WHERE id = ?6",
+        )?;
+        for (id, aggregate) in aggregates {
+            stmt.execute(params![
+                aggregate.file_count,
+                aggregate.total_size,
+                aggregate.average_score(),
+                round_score(aggregate.max_score),
+                aggregate.latest_modified,
+                id
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Recomputes every folder in one pass: one grouped query for the files, then a deepest-first
+/// walk that adds each folder's totals into its parent.
+pub fn rollup_all_folders(conn: &Connection) -> Result<usize> {
+    let mut folders = load_folders(conn)?;
+    link_orphans(conn, &mut folders)?;
+
+    let mut aggregates = direct_aggregates(conn)?;
+    let mut order: Vec<i64> = folders.keys().copied().collect();
+    order.sort_by_key(|id| std::cmp::Reverse(depth(&folders[id].path)));
+
+    for id in &order {
+        let own = aggregates.entry(*id).or_default().clone();
+        if let Some(parent) = folders[id].parent_id.filter(|p| folders.contains_key(p)) {
+            aggregates.entry(parent).or_default().merge(&own);
+        }
+    }
+
+    write_aggregates(conn, &aggregates)?;
+    Ok(aggregates.len())
+}
+
+/// Recomputes only the given folders and their ancestors. Untouched child folders contribute
+/// their stored totals, and only those ancestor chains are loaded, so this stays proportional
+/// to the size of the change.
+pub fn rollup_folders(conn: &Connection, touched: &HashSet<i64>) -> Result<usize> {
+    if touched.is_empty() {
+        return Ok(0);
+    }
+    let mut start = touched.clone();
+    start.extend(link_orphan_files(conn)?);
+
+    // Touched folders plus every ancestor up to the root
+    let folders = load_ancestor_chains(conn, &start)?;
+    let affected: HashSet<i64> = folders.keys().copied().collect();
+
+    let mut aggregates: HashMap<i64, FolderAggregate> = HashMap::new();
+    for id in &affected {
+        aggregates.insert(*id, direct_aggregate(conn, *id)?);
+        // Children outside the affected set keep their stored recursive totals
+        for child in load_child_aggregates(conn, *id, &affected)? {
+            aggregates.get_mut(id).unwrap().merge(&child);
+        }
+    }
+
+    // Affected children are recomputed, so they are merged deepest first
+    let mut order: Vec<i64> = affected.iter().copied().collect();
+    order.sort_by_key(|id| std::cmp::Reverse(depth(&folders[id].path)));
+    for id in &order {
+        if let Some(parent) = folders[id].parent_id.filter(|p| affected.contains(p)) {
+            let own = aggregates[id].clone();
+            aggregates.get_mut(&parent).unwrap().merge(&own);
+        }
+    }
+
+    write_aggregates(conn, &aggregates)?;
+    Ok(aggregates.len())
+}
+
+fn load_child_aggregates(
+    conn: &Connection,
+    parent_id: i64,
+    exclude: &HashSet<i64>,
+) -> Result<Vec<FolderAggregate>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, file_count, folder_size, score, max_score, latest_modified
+         FROM folders WHERE parent_folder_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![parent_id], |row| {
+        let file_count: i64 = row.get::<_, Option<i64>>(1)?.unwrap_or(0);
+        let average: f64 = row.get(3)?;
+        Ok((
+            row.get::<_, i64>(0)?,
+            FolderAggregate {
+                file_count,
+                total_size: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                score_sum: average * file_count as f64,
+                max_score: row.get::<_, Option<f64>>(4)?.unwrap_or(0.0),
+                latest_modified: row.get(5)?,
+            },
+        ))
+    })?;
+
+    let mut children = Vec::new();
+    for row in rows {
+        let (id, aggregate) = row?;
+        if !exclude.contains(&id) {
+            children.push(aggregate);
+        }
+    }
+    Ok(children)
+}
+
+/// Resolves file paths to the nearest indexed folder ids, for `rollup_folders`.
+pub fn folder_ids_for_paths<'a>(
+    conn: &Connection,
+    paths: impl IntoIterator<Item = &'a str>,
+) -> Result<HashSet<i64>> {
+    let mut ids = HashSet::new();
+    for path in paths {
+        if let Some(id) = nearest_folder_in_db(conn, path)? {
+            ids.insert(id);
+        }
+    }
+    Ok(ids)
+}
@@ -0,0 +1,374 @@
+// Search history, saved searches and query suggestions
+use crate::database::query_compiler::escape_like;
+use crate::database::rules::{get_setting_sync, set_setting_sync};
+use crate::database::search::SearchFilters;
+use crate::database::settings::notify_settings_changed;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::AppHandle;
+
+pub const HISTORY_ENABLED_SETTING: &str = "search_history_enabled";
+
+// Oldest entries are dropped past this size
+const MAX_HISTORY_ENTRIES: i64 = 1000;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SearchHistoryEntry {
+    pub id: i64,
+    pub query: String,
+    pub filters: Option<SearchFilters>,
+    pub result_count: i64,
+    pub chosen_path: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+    pub filters: Option<SearchFilters>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct QuerySuggestion {
+    pub text: String,
+    pub source: String, // "saved" or "history"
+    pub saved_search_id: Option<i64>,
+}
+
+/// History is on unless the user turned it off.
+pub fn is_history_enabled(db: &Connection) -> bool {
+    let value = get_setting_sync(db, HISTORY_ENABLED_SETTING).ok().flatten();
+    value.as_deref() != Some("false")
+}
+
+fn filters_to_json(filters: Option<&SearchFilters>) -> Result<Option<String>, String> {
+    filters
+        .map(|f| serde_json::to_string(f).map_err(|e| e.to_string()))
+        .transpose()
+}
+
+fn filters_from_json(value: Option<String>) -> Option<SearchFilters> {
+    value.and_then(|json| serde_json::from_str(&json).ok())
+}
+
+/// Records an executed query. Does nothing when history is disabled or the query is blank.
+pub fn record_search(
+    db: &Connection,
+    query: &str,
+    filters: Option<&SearchFilters>,
+    result_count: usize,
+) -> Result<(), String> {
+    let query = query.trim();
+    if query.is_empty() || !is_history_enabled(db) {
+        return Ok(());
+    }
+
+    db.execute(
+        "INSERT INTO search_history (query, filters, result_count, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![query, filters_to_json(filters)?, result_count as i64, Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+    db.execute(
+        "DELETE FROM search_history WHERE id NOT IN
+         (SELECT id FROM search_history ORDER BY id DESC LIMIT ?1)",
+        params![MAX_HISTORY_ENTRIES],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Marks the result the user picked on the latest history entry for `query`. Opening a
+/// result commits a streamed search that was never submitted, so it gets an entry then.
+pub fn record_chosen_result(db: &Connection, query: &str, path: &str) -> Result<(), String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(());
+    }
+    let updated = db
+        .execute(
+            "UPDATE search_history SET chosen_path = ?1
+             WHERE id = (SELECT id FROM search_history WHERE query = ?2 ORDER BY id DESC LIMIT 1)",
+            params![path, query],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        db.execute(
+            "INSERT INTO search_history (query, chosen_path, created_at) VALUES (?1, ?2, ?3)",
+            params![query, path, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn row_to_history_entry(row: &rusqlite::Row) -> rusqlite::Result<SearchHistoryEntry> {
+    Ok(SearchHistoryEntry {
+        id: row.get(0)?,
+        query: row.get(1)?,
+        filters: filters_from_json(row.get(2)?),
+        result_count: row.get(3)?,
+        chosen_path: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn row_to_saved_search(row: &rusqlite::Row) -> rusqlite::Result<SavedSearch> {
+    Ok(SavedSearch {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        query: row.get(2)?,
+        filters: filters_from_json(row.get(3)?),
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+pub fn get_history_entry_sync(db: &Connection, id: i64) -> Result<Option<SearchHistoryEntry>, String> {
+    db.query_row(
+        "SELECT id, query, filters, result_count, chosen_path, created_at
+         FROM search_history WHERE id = ?1",
+        params![id],
+        row_to_history_entry,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+pub fn get_saved_search_sync(db: &Connection, id: i64) -> Result<Option<SavedSearch>, String> {
+    db.query_row(
+        "SELECT id, name, query, filters, created_at, updated_at FROM saved_searches WHERE id = ?1",
+        params![id],
+        row_to_saved_search,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Query suggestions for `prefix`: saved searches first, then past queries by frequency and recency.
+pub fn suggest_queries_sync(
+    db: &Connection,
+    prefix: &str,
+    limit: usize,
+) -> Result<Vec<QuerySuggestion>, String> {
+    let prefix = prefix.trim();
+    let pattern = format!("{}%", escape_like(prefix));
+    let mut suggestions: Vec<QuerySuggestion> = Vec::new();
+
+    let mut stmt = db
+        .prepare(
+            "SELECT id, name, query FROM saved_searches
+             WHERE name LIKE ?1 ESCAPE '\\' OR query LIKE ?1 ESCAPE '\\'
+             ORDER BY updated_at DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let saved = stmt
+        .query_map(params![pattern, limit as i64], |row| {
+            Ok(QuerySuggestion {
+                saved_search_id: Some(row.get(0)?),
+                text: row.get::<_, String>(1)?,
+                source: "saved".to_string(),
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    for suggestion in saved {
+        suggestions.push(suggestion.map_err(|e| e.to_string())?);
+    }
+
+    let mut stmt = db
+        .prepare(
+            "SELECT query FROM search_history
+             WHERE query LIKE ?1 ESCAPE '\\' AND query != ?2
+             GROUP BY query
+             ORDER BY COUNT(*) DESC, MAX(id) DESC
+             LIMIT ?3",
+        )
+        .map_err(|e| e.to_string())?;
+    let history = stmt
+        .query_map(params![pattern, prefix, limit as i64], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    for query in history {
+        let text = query.map_err(|e| e.to_string())?;
+        if !suggestions.iter().any(|s| s.text.eq_ignore_ascii_case(&text)) {
+            suggestions.push(QuerySuggestion {
+                text,
+                source: "history".to_string(),
+                saved_search_id: None,
+            });
+        }
+    }
+
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+// --- Tauri commands ---
+
+#[tauri::command]
+pub async fn get_search_history(limit: Option<usize>) -> Result<Vec<SearchHistoryEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        let mut stmt = db
+            .prepare(
+                "SELECT id, query, filters, result_count, chosen_path, created_at
+                 FROM search_history ORDER BY id DESC LIMIT ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![limit.unwrap_or(50) as i64], row_to_history_entry)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn delete_search_history_entry(id: i64) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        db.execute("DELETE FROM search_history WHERE id = ?1", params![id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Removes every history entry. Saved searches are kept, and so are the opens and reveals
+/// that usage ranking learns from unless `include_interactions` is set.
+#[tauri::command]
+pub async fn purge_search_history(include_interactions: Option<bool>) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        db.execute("DELETE FROM search_history", [])
+            .map_err(|e| format!("Database error: {}", e))?;
+        if include_interactions.unwrap_or(false) {
+            db.execute("DELETE FROM file_interactions", [])
+                .map_err(|e| format!("Database error: {}", e))?;
+        }
+        println!("Search history purged");
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_search_history_enabled() -> Result<bool, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        Ok(is_history_enabled(&db))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn set_search_history_enabled(enabled: bool, app: AppHandle) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        set_setting_sync(&db, HISTORY_ENABLED_SETTING, if enabled { "true" } else { "false" })
+            .map_err(|e| format!("Database error: {}", e))?;
+        notify_settings_changed(&app, &db);
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_query_suggestions(
+    prefix: String,
+    limit: Option<usize>,
+) -> Result<Vec<QuerySuggestion>, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        suggest_queries_sync(&db, &prefix, limit.unwrap_or(8))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn list_saved_searches() -> Result<Vec<SavedSearch>, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        let mut stmt = db
+            .prepare(
+                "SELECT id, name, query, filters, created_at, updated_at
+                 FROM saved_searches ORDER BY name COLLATE NOCASE",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], row_to_saved_search)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn save_search(
+    name: String,
+    query: String,
+    filters: Option<SearchFilters>,
+) -> Result<SavedSearch, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Saved search name cannot be empty".to_string());
+    }
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        let now = Utc::now().to_rfc3339();
+        db.execute(
+            "INSERT INTO saved_searches (name, query, filters, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![name, query.trim(), filters_to_json(filters.as_ref())?, now],
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+        get_saved_search_sync(&db, db.last_insert_rowid())?
+            .ok_or_else(|| "Saved search not found after insert".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn rename_saved_search(id: i64, name: String) -> Result<(), String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Saved search name cannot be empty".to_string());
+    }
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        let updated = db
+            .execute(
+                "UPDATE saved_searches SET name = ?1, updated_at = ?2 WHERE id = ?3",
+                params![name, Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| format!("Database error: {}", e))?;
+        if updated == 0 {
+            return Err(format!("Saved search {} not found", id));
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn delete_saved_search(id: i64) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        db.execute("DELETE FROM saved_searches WHERE id = ?1", params![id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
@@ -0,0 +1,143 @@
+// Opens and reveals of search results, and the usage boost they feed into ranking
+use crate::database::search::{parse_query, SearchResult};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, params_from_iter, Connection};
+use std::collections::HashMap;
+
+// Interactions older than this no longer influence ranking
+const USAGE_WINDOW_DAYS: i64 = 180;
+
+const FREQUENCY_WEIGHT: f32 = 0.08;
+const RECENCY_WEIGHT: f32 = 0.15;
+const RECENCY_HALF_LIFE_DAYS: f32 = 14.0;
+const AFFINITY_WEIGHT: f32 = 0.3;
+const MAX_USAGE_BOOST: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionKind {
+    Open,
+    OpenWith,
+    Reveal,
+}
+
+impl InteractionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InteractionKind::Open => "open",
+            InteractionKind::OpenWith => "open_with",
+            InteractionKind::Reveal => "reveal",
+        }
+    }
+}
+
+/// Queries are compared case- and whitespace-insensitively for click affinity, and without
+/// their date expression, which search strips before ranking too.
+pub fn normalize_query(query: &str) -> String {
+    let (search_term, _) = parse_query(query);
+    search_term
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Records that the user picked `path`, optionally from the results of `query`.
+pub fn record_interaction(
+    db: &Connection,
+    path: &str,
+    kind: InteractionKind,
+    query: Option<&str>,
+) -> rusqlite::Result<()> {
+    let now = Utc::now().to_rfc3339();
+    let query_key = query.map(normalize_query).filter(|q| !q.is_empty());
+
+    db.execute(
+        "INSERT INTO file_interactions (path, action, query, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![path, kind.as_str(), query_key, now],
+    )?;
+    // The scan-time atime is stale as soon as the user opens the file through the app
+    if kind != InteractionKind::Reveal {
+        db.execute(
+            "UPDATE files SET last_accessed = ?1 WHERE path = ?2",
+            params![now, path],
+        )?;
+    }
+    Ok(())
+}
+
+struct UsageStats {
+    total: u32,
+    last_used: Option<DateTime<Utc>>,
+    query_matches: u32,
+}
+
+/// Adds a usage boost to each result: how often it was picked, how recently,
+/// and how often it was picked for this same query. `query` is the query as typed.
+pub fn apply_usage_boost(
+    db: &Connection,
+    results: &mut [SearchResult],
+    query: &str,
+) -> rusqlite::Result<()> {
+    if results.is_empty() {
+        return Ok(());
+    }
+
+    let stats = load_usage_stats(db, results, &normalize_query(query))?;
+    if stats.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    for result in results.iter_mut() {
+        if let Some(usage) = stats.get(&result.path) {
+            result.relevance_score += usage_boost(usage, now);
+        }
+    }
+    Ok(())
+}
+
+fn load_usage_stats(
+    db: &Connection,
+    results: &[SearchResult],
+    query_key: &str,
+) -> rusqlite::Result<HashMap<String, UsageStats>> {
+    let since = (Utc::now() - chrono::Duration::days(USAGE_WINDOW_DAYS)).to_rfc3339();
+    let placeholders = vec!["?"; results.len()].join(", ");
+    let sql = format!(
+        "SELECT path, COUNT(*), MAX(created_at), SUM(CASE WHEN query = ? THEN 1 ELSE 0 END)
+         FROM file_interactions
+         WHERE created_at >= ? AND path IN ({})
+         GROUP BY path",
+        placeholders
+    );
+
+    let mut values: Vec<&str> = vec![query_key, &since];
+    values.extend(results.iter().map(|r| r.path.as_str()));
+
+    let mut stmt = db.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values), |row| {
+        let last_used: Option<String> = row.get(2)?;
+        Ok((
+            row.get::<_, String>(0)?,
+            UsageStats {
+                total: row.get(1)?,
+                last_used: last_used
+                    .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                    .map(|t| t.with_timezone(&Utc)),
+                query_matches: row.get(3)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn usage_boost(usage: &UsageStats, now: DateTime<Utc>) -> f32 {
+    let frequency = FREQUENCY_WEIGHT * (1.0 + usage.total as f32).ln();
+    let recency = usage.last_used.map_or(0.0, |last| {
+        let days = now.signed_duration_since(last).num_hours().max(0) as f32 / 24.0;
+        RECENCY_WEIGHT * 0.5f32.powf(days / RECENCY_HALF_LIFE_DAYS)
+    });
+    // Saturates after a handful of picks so one query can't pin a file forever
+    let affinity = AFFINITY_WEIGHT * (usage.query_matches.min(5) as f32 / 5.0);
+    (frequency + recency + affinity).min(MAX_USAGE_BOOST)
+}
@@ -0,0 +1,534 @@
+// Intent journal for writes that span SQLite and LanceDB. SQLite commits first, together with
+// a 'pending' entry naming the LanceDB work still to do; the entry is completed once LanceDB
+// has it too. Entries still pending at startup are replayed or rolled back.
+use crate::file_scanner::lancedb::{
+    clear_content_lancedb, count_rows_with_ids_lancedb, delete_rows_with_ids_lancedb,
+    get_lancedb_tables, remap_path_prefix_lancedb,
+};
+use crate::database::vector_index::{LanceVectorIndex, VectorIndex};
+use chrono::Utc;
+use lancedb::Table;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalOp {
+    /// Rows committed to SQLite `files`/`file_chunks` that LanceDB must receive with the same ids
+    InsertFiles { file_ids: Vec<i64>, chunk_ids: Vec<i64> },
+    /// A path prefix rewrite already applied to SQLite
+    RemapPaths { old_prefix: String, new_prefix: String },
+    /// Files SQLite already made metadata-only; LanceDB must drop their content and the
+    /// content chunks SQLite deleted
+    DegradeFiles { file_ids: Vec<i64>, chunk_ids: Vec<i64> },
+}
+
+impl JournalOp {
+    fn name(&self) -> &'static str {
+        match self {
+            JournalOp::InsertFiles { .. } => "insert_files",
+            JournalOp::RemapPaths { .. } => "remap_paths",
+            JournalOp::DegradeFiles { .. } => "degrade_files",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RecoveryReport {
+    pub completed: usize,
+    pub replayed: usize,
+    pub rolled_back: usize,
+    pub failed: usize,
+}
+
+/// Records the LanceDB half of a write. Call it inside the SQLite transaction of the other half.
+pub fn begin(conn: &Connection, op: &JournalOp) -> Result<i64> {
+    let payload = serde_json::to_string(op)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO write_journal (operation, payload, status, created_at) VALUES (?1, ?2, 'pending', ?3)",
+        params![op.name(), payload, Utc::now().to_rfc3339()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn finish(conn: &Connection, id: i64, status: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE write_journal SET status = ?1, finished_at = ?2 WHERE id = ?3",
+        params![status, Utc::now().to_rfc3339(), id],
+    )?;
+    Ok(())
+}
+
+/// Both stores have the write.
+pub fn complete(conn: &Connection, id: i64) -> Result<()> {
+    finish(conn, id, "complete")
+}
+
+fn pending_entries(conn: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut stmt =
+        conn.prepare("SELECT id, payload FROM write_journal WHERE status = 'pending' ORDER BY id")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Removes the SQLite half of an insert: the files, their chunks and their full-text rows.
+fn delete_sqlite_files(conn: &Connection, file_ids: &[i64]) -> Result<Vec<String>> {
+    let tx = conn.unchecked_transaction()?;
+    let mut paths = Vec::with_capacity(file_ids.len());
+    for file_id in file_ids {
+        // Read as optional, so a NULL content can't fail the whole delete
+        let row: Option<(String, String, Option<String>)> = tx
+            .query_row(
+                "SELECT path, name, content FROM files WHERE id = ?1",
+                params![file_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((path, name, content)) = row else {
+            continue;
+        };
+        // files_fts is an external-content table, so deletes must repeat the indexed values
+        tx.execute(
+            "INSERT INTO files_fts (files_fts, rowid, name, content) VALUES ('delete', ?1, ?2, ?3)",
+            params![file_id, name, content],
+        )?;
+        tx.execute("DELETE FROM file_chunks WHERE file_id = ?1", params![file_id])?;
+        tx.execute("DELETE FROM files WHERE id = ?1", params![file_id])?;
+        paths.push(path);
+    }
+    tx.commit()?;
+    Ok(paths)
+}
+
+/// Both halves of a journaled write: the LanceDB tables and the SQLite writer.
+struct Stores<'a> {
+    files: Table,
+    file_embeddings: Table,
+    writer: &'a Mutex<Connection>,
+}
+
+impl Stores<'static> {
+    async fn open() -> Result<Self, String> {
+        let (files, file_embeddings) = get_lancedb_tables()
+            .await
+            .map_err(|e| format!("Failed to open LanceDB tables: {}", e))?;
+        Ok(Stores { files, file_embeddings, writer: &super::DB_CONNECTION })
+    }
+}
+
+impl Stores<'_> {
+    /// Take it only once the LanceDB half is done, so other writes don't wait on LanceDB.
+    fn writer(&self) -> MutexGuard<'_, Connection> {
+        self.writer.lock().expect("Failed to lock DB")
+    }
+
+    async fn roll_back_insert(
+        &self,
+        id: i64,
+        file_ids: &[i64],
+        chunk_ids: &[i64],
+    ) -> Result<(), String> {
+        self.delete_lancedb_rows(file_ids, chunk_ids).await?;
+        delete_sqlite_rows(&self.writer(), id, file_ids)
+    }
+
+    async fn delete_lancedb_rows(&self, file_ids: &[i64], chunk_ids: &[i64]) -> Result<(), String> {
+        delete_rows_with_ids_lancedb(&self.file_embeddings, chunk_ids)
+            .await
+            .map_err(|e| format!("Failed to remove LanceDB chunks: {}", e))?;
+        LanceVectorIndex::from_table(self.files.clone()).delete(file_ids).await
+    }
+
+    async fn degrade_lancedb_rows(
+        &self,
+        file_ids: &[i64],
+        chunk_ids: &[i64],
+    ) -> Result<(), String> {
+        delete_rows_with_ids_lancedb(&self.file_embeddings, chunk_ids)
+            .await
+            .map_err(|e| format!("Failed to remove LanceDB chunks: {}", e))?;
+        clear_content_lancedb(&self.files, file_ids)
+            .await
+            .map_err(|e| format!("Failed to clear LanceDB content: {}", e))
+    }
+}
+
+/// Undoes both halves of an insert. The files are no longer known, so the next scan picks
+/// them up again. The writer is only taken once LanceDB is done.
+pub async fn roll_back_insert(id: i64, file_ids: &[i64], chunk_ids: &[i64]) -> Result<(), String> {
+    Stores::open().await?.roll_back_insert(id, file_ids, chunk_ids).await
+}
+
+/// Deletes the LanceDB rows of files and chunks; ids without a row are ignored.
+pub async fn delete_lancedb_rows(file_ids: &[i64], chunk_ids: &[i64]) -> Result<(), String> {
+    Stores::open().await?.delete_lancedb_rows(file_ids, chunk_ids).await
+}
+
+/// Drops the content of degraded files from LanceDB. Safe to repeat.
+pub async fn degrade_lancedb_rows(file_ids: &[i64], chunk_ids: &[i64]) -> Result<(), String> {
+    Stores::open().await?.degrade_lancedb_rows(file_ids, chunk_ids).await
+}
+
+/// Removes files from SQLite and refreshes their folders, so the next scan indexes them again.
+pub fn forget_files(conn: &Connection, file_ids: &[i64]) -> Result<(), String> {
+    let paths = delete_sqlite_files(conn, file_ids).map_err(|e| format!("Database error: {}", e))?;
+    crate::database::update_folder_scores_for_paths(conn, paths.iter().map(String::as_str))
+        .map_err(|e| format!("Database error: {}", e))
+}
+
+fn delete_sqlite_rows(conn: &Connection, id: i64, file_ids: &[i64]) -> Result<(), String> {
+    forget_files(conn, file_ids)?;
+    finish(conn, id, "rolled_back").map_err(|e| format!("Database error: {}", e))
+}
+
+enum Recovery {
+    Completed,
+    Replayed,
+    RolledBack,
+}
+
+async fn recover_entry(stores: &Stores<'_>, id: i64, op: JournalOp) -> Result<Recovery, String> {
+    match op {
+        JournalOp::InsertFiles { file_ids, chunk_ids } => {
+            // The vectors were never journaled, so an insert can only finish if LanceDB has every row
+            let files = count_rows_with_ids_lancedb(&stores.files, &file_ids)
+                .await
+                .map_err(|e| e.to_string())?;
+            let chunks = count_rows_with_ids_lancedb(&stores.file_embeddings, &chunk_ids)
+                .await
+                .map_err(|e| e.to_string())?;
+            if files == file_ids.len() && chunks == chunk_ids.len() {
+                complete(&stores.writer(), id).map_err(|e| format!("Database error: {}", e))?;
+                Ok(Recovery::Completed)
+            } else {
+                stores.roll_back_insert(id, &file_ids, &chunk_ids).await?;
+                Ok(Recovery::RolledBack)
+            }
+        }
+        JournalOp::RemapPaths { old_prefix, new_prefix } => {
+            // Rewriting the prefix again is a no-op for rows that already moved; remaps between
+            // nested mount points, where that wouldn't hold, are never journaled
+            remap_path_prefix_lancedb(&stores.files, &old_prefix, &new_prefix)
+                .await
+                .map_err(|e| e.to_string())?;
+            complete(&stores.writer(), id).map_err(|e| format!("Database error: {}", e))?;
+            Ok(Recovery::Replayed)
+        }
+        JournalOp::DegradeFiles { file_ids, chunk_ids } => {
+            stores.degrade_lancedb_rows(&file_ids, &chunk_ids).await?;
+            complete(&stores.writer(), id).map_err(|e| format!("Database error: {}", e))?;
+            Ok(Recovery::Replayed)
+        }
+    }
+}
+
+/// Finishes or undoes every write that was interrupted before both stores had it.
+/// Run at startup, before any scan or search.
+pub async fn recover_pending_writes() -> Result<RecoveryReport, String> {
+    recover_writes(&Stores::open().await?).await
+}
+
+async fn recover_writes(stores: &Stores<'_>) -> Result<RecoveryReport, String> {
+    let entries =
+        pending_entries(&stores.writer()).map_err(|e| format!("Database error: {}", e))?;
+    let mut report = RecoveryReport::default();
+    for (id, payload) in entries {
+        let op = match serde_json::from_str::<JournalOp>(&payload) {
+            Ok(op) => op,
+            Err(e) => {
+                eprintln!("Unreadable journal entry {}: {}", id, e);
+                report.failed += 1;
+                continue;
+            }
+        };
+        match recover_entry(stores, id, op).await {
+            Ok(Recovery::Completed) => report.completed += 1,
+            Ok(Recovery::Replayed) => report.replayed += 1,
+            Ok(Recovery::RolledBack) => report.rolled_back += 1,
+            Err(e) => {
+                // Left pending, so the next start tries again
+                eprintln!("Failed to recover journal entry {}: {}", id, e);
+                report.failed += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::lancedb_ops::{file_embeddings_schema, files_schema};
+    use crate::database::vector_index::IndexedVector;
+    use crate::embed_and_store::EMBEDDING_DIM;
+    use crate::file_scanner::lancedb::{insert_file_embedding_batch, load_file_ids_lancedb};
+
+    /// Temporary copies of both stores.
+    struct Fixture {
+        dir: std::path::PathBuf,
+        writer: Mutex<Connection>,
+        files: Table,
+        file_embeddings: Table,
+    }
+
+    impl Fixture {
+        async fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("journal-{}-{}", name, std::process::id()));
+            std::fs::remove_dir_all(&dir).ok();
+            let db = lancedb::connect(dir.to_str().unwrap()).execute().await.unwrap();
+            let files = db
+                .create_empty_table("files", files_schema())
+                .execute()
+                .await
+                .unwrap();
+            let file_embeddings = db
+                .create_empty_table("file_embeddings", file_embeddings_schema())
+                .execute()
+                .await
+                .unwrap();
+            let conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch(&crate::database::schema::create_all_sql())
+                .unwrap();
+            Fixture { dir, writer: Mutex::new(conn), files, file_embeddings }
+        }
+
+        fn stores(&self) -> Stores<'_> {
+            Stores {
+                files: self.files.clone(),
+                file_embeddings: self.file_embeddings.clone(),
+                writer: &self.writer,
+            }
+        }
+
+        /// A file with one chunk (`id + 100`) in SQLite.
+        fn add_sqlite_file(&self, id: i64, path: &str) {
+            let conn = self.writer.lock().unwrap();
+            let name = path.rsplit('/').next().unwrap();
+            conn.execute(
+                "INSERT INTO files (id, name, extension, path, content, created_at, updated_at)
+                 VALUES (?1, ?2, 'txt', ?3, 'some content', '', '')",
+                params![id, name, path],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO files_fts (rowid, name, content) VALUES (?1, ?2, 'some content')",
+                params![id, name],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO file_chunks (id, file_id, chunk_index, created_at)
+                 VALUES (?1, ?2, 0, '')",
+                params![id + 100, id],
+            )
+            .unwrap();
+        }
+
+        async fn add_lance_file(&self, id: i64, path: &str) {
+            let record = IndexedVector {
+                file_id: id,
+                path: path.to_string(),
+                content: "some content".to_string(),
+                content_hash: Some("hash".to_string()),
+                vector: Some(vec![1.0; EMBEDDING_DIM]),
+            };
+            LanceVectorIndex::from_table(self.files.clone())
+                .insert(&[record])
+                .await
+                .unwrap();
+        }
+
+        async fn add_lance_chunk(&self, file_id: i64) {
+            let vector = vec![1.0; EMBEDDING_DIM];
+            let chunk = (file_id as i32 + 100, file_id as i32, 0, String::new(), vector);
+            insert_file_embedding_batch(&self.file_embeddings, &[chunk])
+                .await
+                .unwrap();
+        }
+
+        fn begin(&self, op: &JournalOp) -> i64 {
+            begin(&self.writer.lock().unwrap(), op).unwrap()
+        }
+
+        fn status(&self, id: i64) -> String {
+            self.writer
+                .lock()
+                .unwrap()
+                .query_row("SELECT status FROM write_journal WHERE id = ?1", params![id], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        }
+
+        fn sqlite_rows(&self) -> (i64, i64) {
+            let conn = self.writer.lock().unwrap();
+            let count = |table: &str| {
+                conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+                    .unwrap()
+            };
+            (count("files"), count("file_chunks"))
+        }
+
+        async fn lance_rows(&self) -> (usize, usize) {
+            (
+                self.files.count_rows(None).await.unwrap(),
+                self.file_embeddings.count_rows(None).await.unwrap(),
+            )
+        }
+
+        async fn lance_paths(&self) -> Vec<String> {
+            let mut paths: Vec<String> = load_file_ids_lancedb(&self.files)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(_, path)| path)
+                .collect();
+            paths.sort();
+            paths
+        }
+
+        async fn cleared_content(&self) -> usize {
+            self.files
+                .count_rows(Some("content = '' AND content_hash IS NULL".to_string()))
+                .await
+                .unwrap()
+        }
+
+        /// Recovers twice; the second pass must find nothing left to do.
+        async fn recover(&self) -> RecoveryReport {
+            let report = recover_writes(&self.stores()).await.unwrap();
+            let again = recover_writes(&self.stores()).await.unwrap();
+            assert_eq!(
+                (again.completed, again.replayed, again.rolled_back, again.failed),
+                (0, 0, 0, 0)
+            );
+            report
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+
+    fn insert_op() -> JournalOp {
+        JournalOp::InsertFiles { file_ids: vec![1, 2], chunk_ids: vec![101, 102] }
+    }
+
+    #[tokio::test]
+    async fn insert_that_reached_lancedb_completes() {
+        let fixture = Fixture::new("insert-complete").await;
+        for id in [1, 2] {
+            fixture.add_sqlite_file(id, &format!("/docs/{}.txt", id));
+            fixture.add_lance_file(id, &format!("/docs/{}.txt", id)).await;
+            fixture.add_lance_chunk(id).await;
+        }
+        let id = fixture.begin(&insert_op());
+
+        let report = fixture.recover().await;
+        assert_eq!(report.completed, 1);
+        assert_eq!(fixture.status(id), "complete");
+        assert_eq!(fixture.sqlite_rows(), (2, 2));
+        assert_eq!(fixture.lance_rows().await, (2, 2));
+    }
+
+    #[tokio::test]
+    async fn partial_insert_rolls_back_both_stores() {
+        let fixture = Fixture::new("insert-rollback").await;
+        for id in [1, 2] {
+            fixture.add_sqlite_file(id, &format!("/docs/{}.txt", id));
+        }
+        // LanceDB got one file and no chunks before the crash
+        fixture.add_lance_file(1, "/docs/1.txt").await;
+        let id = fixture.begin(&insert_op());
+
+        let report = fixture.recover().await;
+        assert_eq!(report.rolled_back, 1);
+        assert_eq!(fixture.status(id), "rolled_back");
+        assert_eq!(fixture.sqlite_rows(), (0, 0));
+        assert_eq!(fixture.lance_rows().await, (0, 0));
+    }
+
+    #[tokio::test]
+    async fn roll_back_insert_tolerates_missing_rows() {
+        let fixture = Fixture::new("insert-undo").await;
+        fixture.add_sqlite_file(1, "/docs/1.txt");
+        fixture.add_lance_file(1, "/docs/1.txt").await;
+        let id = fixture.begin(&insert_op());
+
+        for _ in 0..2 {
+            fixture.stores().roll_back_insert(id, &[1, 2], &[101, 102]).await.unwrap();
+            assert_eq!(fixture.status(id), "rolled_back");
+            assert_eq!(fixture.sqlite_rows(), (0, 0));
+            assert_eq!(fixture.lance_rows().await, (0, 0));
+        }
+    }
+
+    #[tokio::test]
+    async fn remaps_are_replayed_whether_or_not_lancedb_has_them() {
+        let fixture = Fixture::new("remap").await;
+        // SQLite already moved both; LanceDB moved one before the crash
+        fixture.add_lance_file(1, "/mnt/new/a.txt").await;
+        fixture.add_lance_file(2, "/mnt/old/b.txt").await;
+        fixture.add_lance_file(3, "/mnt/other/c.txt").await;
+        let id = fixture.begin(&JournalOp::RemapPaths {
+            old_prefix: "/mnt/old/".to_string(),
+            new_prefix: "/mnt/new/".to_string(),
+        });
+
+        let report = fixture.recover().await;
+        assert_eq!(report.replayed, 1);
+        assert_eq!(fixture.status(id), "complete");
+        assert_eq!(
+            fixture.lance_paths().await,
+            ["/mnt/new/a.txt", "/mnt/new/b.txt", "/mnt/other/c.txt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn degrades_are_replayed_whether_or_not_lancedb_has_them() {
+        let fixture = Fixture::new("degrade").await;
+        for id in [1, 2, 3] {
+            fixture.add_lance_file(id, &format!("/docs/{}.txt", id)).await;
+            fixture.add_lance_chunk(id).await;
+        }
+        // File 1 was already degraded in LanceDB before the crash
+        fixture.stores().degrade_lancedb_rows(&[1], &[101]).await.unwrap();
+        assert_eq!(fixture.cleared_content().await, 1);
+        let id = fixture.begin(&JournalOp::DegradeFiles {
+            file_ids: vec![1, 2],
+            chunk_ids: vec![101, 102],
+        });
+
+        let report = fixture.recover().await;
+        assert_eq!(report.replayed, 1);
+        assert_eq!(fixture.status(id), "complete");
+        assert_eq!(fixture.cleared_content().await, 2);
+        // Degraded files keep their row and vector; only their chunks go
+        assert_eq!(fixture.lance_rows().await, (3, 1));
+    }
+
+    #[tokio::test]
+    async fn unreadable_entries_stay_pending() {
+        let fixture = Fixture::new("unreadable").await;
+        fixture
+            .writer
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO write_journal (operation, payload, status, created_at)
+                 VALUES ('insert_files', 'not json', 'pending', '')",
+                [],
+            )
+            .unwrap();
+
+        for _ in 0..2 {
+            let report = recover_writes(&fixture.stores()).await.unwrap();
+            assert_eq!(report.failed, 1);
+        }
+        assert_eq!(fixture.status(1), "pending");
+    }
+}
@@ -0,0 +1,135 @@
+// Every scan appends small fragments to LanceDB and every write leaves a table version behind.
+// Maintenance compacts the fragments, folds new rows into the indexes, retrains indexes that
+// have outgrown their training data and deletes old versions.
+use crate::database::lancedb_ops::{connect_lancedb, ensure_lancedb_indexes, lancedb_path};
+use crate::file_scanner::claim_scan_slot;
+use chrono::Utc;
+use lancedb::table::{CompactionOptions, OptimizeAction, OptimizeOptions};
+use std::path::Path;
+
+pub const MAINTAINED_TABLES: &[&str] = &["files", "file_embeddings", "folder"];
+
+// Recent versions stay so queries that started on them can finish
+const VERSION_RETENTION_DAYS: i64 = 7;
+
+/// A scan that stores at least this many files runs maintenance when it finishes.
+pub const LARGE_SCAN_FILES: usize = 5000;
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct TableMaintenance {
+    pub table: String,
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    pub versions_pruned: u64,
+    pub bytes_pruned: u64,
+    /// On-disk size after maintenance
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct MaintenanceReport {
+    pub tables: Vec<TableMaintenance>,
+    pub size_before_bytes: u64,
+    pub size_after_bytes: u64,
+    pub finished_at: String,
+}
+
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+async fn maintain_table(
+    lance: &lancedb::Connection,
+    name: &str,
+    lancedb_dir: &Path,
+) -> Result<TableMaintenance, String> {
+    let table = lance
+        .open_table(name)
+        .execute()
+        .await
+        .map_err(|e| format!("Failed to open {} table: {}", name, e))?;
+    let mut result = TableMaintenance {
+        table: name.to_string(),
+        ..TableMaintenance::default()
+    };
+
+    let compacted = table
+        .optimize(OptimizeAction::Compact {
+            options: CompactionOptions::default(),
+            remap_options: None,
+        })
+        .await
+        .map_err(|e| format!("Failed to compact {}: {}", name, e))?;
+    if let Some(metrics) = compacted.compaction {
+        result.fragments_removed = metrics.fragments_removed;
+        result.fragments_added = metrics.fragments_added;
+    }
+
+    // Rows added since an index was trained are searched by brute force until folded in
+    table
+        .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+        .await
+        .map_err(|e| format!("Failed to update indexes on {}: {}", name, e))?;
+
+    let pruned = table
+        .optimize(OptimizeAction::Prune {
+            older_than: Some(chrono::Duration::days(VERSION_RETENTION_DAYS)),
+            delete_unverified: Some(false),
+            error_if_tagged_old_versions: Some(false),
+        })
+        .await
+        .map_err(|e| format!("Failed to prune old versions of {}: {}", name, e))?;
+    if let Some(stats) = pruned.prune {
+        result.versions_pruned = stats.old_versions;
+        result.bytes_pruned = stats.bytes_removed;
+    }
+
+    result.size_bytes = dir_size(&lancedb_dir.join(format!("{}.lance", name)));
+    Ok(result)
+}
+
+/// Compacts, reindexes and prunes every LanceDB table. Callers make sure no scan is writing.
+pub async fn run_lancedb_maintenance() -> Result<MaintenanceReport, String> {
+    let lancedb_dir = lancedb_path().ok_or("Could not get app data directory")?;
+    let mut report = MaintenanceReport {
+        size_before_bytes: dir_size(&lancedb_dir),
+        ..MaintenanceReport::default()
+    };
+
+    // Retrain first, so the incremental index update only has the remainder to add
+    ensure_lancedb_indexes().await?;
+
+    let lance = connect_lancedb().await?;
+    for name in MAINTAINED_TABLES {
+        match maintain_table(&lance, name, &lancedb_dir).await {
+            Ok(table) => report.tables.push(table),
+            // One failing table shouldn't keep the others from being maintained
+            Err(e) => eprintln!("LanceDB maintenance: {}", e),
+        }
+    }
+
+    report.size_after_bytes = dir_size(&lancedb_dir);
+    report.finished_at = Utc::now().to_rfc3339();
+    println!(
+        "LanceDB maintenance: {} -> {} bytes on disk",
+        report.size_before_bytes, report.size_after_bytes
+    );
+    Ok(report)
+}
+
+/// Runs LanceDB maintenance now and reports what it did and the on-disk size.
+#[tauri::command]
+pub async fn optimize_lancedb() -> Result<MaintenanceReport, String> {
+    let _scan_guard = claim_scan_slot().ok_or("Cannot optimize the index while a scan is running")?;
+    run_lancedb_maintenance().await
+}
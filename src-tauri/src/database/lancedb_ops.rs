@@ -1,12 +1,9 @@
-use arrow_array::{
-    types::Float32Type, FixedSizeListArray, Int32Array, RecordBatch, RecordBatchIterator,
-    StringArray,
-};
 use arrow_schema::{DataType, Field, Schema};
 use lancedb::{
     connect,                                 // Simplified connect import
     index::{scalar::FtsIndexBuilder, Index}, // Correct FTS builder
 };
+use rusqlite::{params, Connection, OptionalExtension};
 use std::sync::Arc;
 use tauri::command;
 
@@ -20,204 +17,235 @@ pub fn get_app_data_dir() -> Option<std::path::PathBuf> {
     }
 }
 
-#[command]
-pub async fn create_local_lancedb() -> Result<(), String> {
-    let database_path = get_app_data_dir()
-        .ok_or("Could not get app data directory")?
-        .join("my-lancedb");
+pub fn lancedb_path() -> Option<std::path::PathBuf> {
+    get_app_data_dir().map(|dir| dir.join("my-lancedb"))
+}
 
-    let db = connect(
+pub async fn connect_lancedb() -> Result<lancedb::Connection, String> {
+    let database_path = lancedb_path().ok_or("Could not get app data directory")?;
+
+    connect(
         database_path
             .to_str()
             .ok_or("Failed to convert path to string")?,
     )
     .execute()
     .await
-    .map_err(|e| format!("LanceDB connection error: {}", e))?;
+    .map_err(|e| format!("LanceDB connection error: {}", e))
+}
 
-    const VECTOR_DIM: i32 = 768;
+#[command]
+pub async fn create_local_lancedb() -> Result<(), String> {
+    let db = connect_lancedb().await?;
 
-    // Helper closure: only create table if it doesn't already exist
-    async fn ensure_table<F>(
+    // Tables start empty; indexes are built by `ensure_lancedb_indexes` once real rows exist
+    async fn ensure_table(
         db: &lancedb::Connection,
         table_name: &str,
         schema: Arc<Schema>,
-        batch: RecordBatch,
-        build_indexes: F,
-    ) -> Result<(), String>
-    where
-        F: FnOnce(&lancedb::Table) -> futures::future::BoxFuture<'_, Result<(), String>>,
-    {
+        placeholder_filter: &str,
+    ) -> Result<(), String> {
         match db.open_table(table_name).execute().await {
-            Ok(table) => {
-                // Table exists → maybe ensure indexes
-                build_indexes(&table).await?;
-            }
+            Ok(table) => remove_placeholder_rows(&table, table_name, placeholder_filter).await,
             Err(_) => {
-                // Create table since it does not exist
-                let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
-                db.create_table(table_name, Box::new(batches))
+                db.create_empty_table(table_name, schema)
                     .execute()
                     .await
                     .map_err(|e| format!("Failed to create {} table: {}", table_name, e))?;
-
-                let table = db.open_table(table_name).execute().await.map_err(|e| {
-                    format!("Failed to open {} table after creation: {}", table_name, e)
-                })?;
-
-                build_indexes(&table).await?;
+                Ok(())
             }
         }
-        Ok(())
     }
 
-    // -------- files table --------
-    let files_schema = Arc::new(Schema::new(vec![
+    ensure_table(&db, "files", files_schema(), FILES_PLACEHOLDER_FILTER).await?;
+    ensure_table(
+        &db,
+        "file_embeddings",
+        file_embeddings_schema(),
+        FILE_EMBEDDINGS_PLACEHOLDER_FILTER,
+    )
+    .await?;
+    ensure_table(&db, "folder", folder_schema(), FOLDER_PLACEHOLDER_FILTER).await?;
+
+    Ok(())
+}
+
+const VECTOR_DIM: i32 = crate::embed_and_store::EMBEDDING_DIM as i32;
+
+// Older versions seeded every table with fake rows so it could be created and indexed.
+// These filters match exactly those rows.
+const FILES_PLACEHOLDER_FILTER: &str =
+    "path LIKE '/path/to/file_%' AND name LIKE 'file_%.txt' AND content = 'Sample content'";
+const FILE_EMBEDDINGS_PLACEHOLDER_FILTER: &str =
+    "chunk_text = 'Chunked content' AND id = file_id AND chunk_index = 0 AND id < 256";
+const FOLDER_PLACEHOLDER_FILTER: &str =
+    "folder_name = 'ExampleFolder' AND created_date = '2025-09-09'";
+
+fn vector_field(name: &str, nullable: bool) -> Field {
+    Field::new(
+        name,
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            VECTOR_DIM,
+        ),
+        nullable,
+    )
+}
+
+pub(crate) fn files_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
         Field::new("id", DataType::Int32, false),
         Field::new("name", DataType::Utf8, false),
         Field::new("extension", DataType::Utf8, false),
         Field::new("path", DataType::Utf8, false),
         Field::new("content", DataType::Utf8, true),
-        Field::new(
-            "vector",
-            DataType::FixedSizeList(
-                Arc::new(Field::new("item", DataType::Float32, true)),
-                VECTOR_DIM,
-            ),
-            true,
-        ),
-    ]));
-
-    let files_batch = RecordBatch::try_new(
-        files_schema.clone(),
-        vec![
-            Arc::new(Int32Array::from_iter_values(0..256)),
-            Arc::new(StringArray::from_iter_values(
-                (0..256).map(|i| format!("file_{}.txt", i)),
-            )),
-            Arc::new(StringArray::from_iter_values(
-                (0..256).map(|_| "txt".to_string()),
-            )),
-            Arc::new(StringArray::from_iter_values(
-                (0..256).map(|i| format!("/path/to/file_{}", i)),
-            )),
-            Arc::new(StringArray::from_iter(
-                (0..256).map(|_| Some("Sample content".to_string())),
-            )),
-            Arc::new(
-                FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
-                    (0..256).map(|_| Some(vec![Some(1.0); VECTOR_DIM as usize])),
-                    VECTOR_DIM,
-                ),
-            ),
-        ],
-    )
-    .map_err(|e| format!("Failed to create files batch: {}", e))?;
-
-    ensure_table(&db, "files", files_schema.clone(), files_batch, |table| {
-        Box::pin(async move {
-            // Indexes (idempotent attempt – will skip if already built)
-            table
-                .create_index(&["name"], Index::FTS(FtsIndexBuilder::default()))
-                .execute()
-                .await
-                .ok();
-            table
-                .create_index(&["content"], Index::FTS(FtsIndexBuilder::default()))
-                .execute()
-                .await
-                .ok();
-            table
-                .create_index(&["vector"], Index::Auto) // Auto will select the best index type
-                .execute()
-                .await
-                .ok();
-            Ok(())
-        })
-    })
-    .await?;
+        vector_field("vector", true),
+        Field::new("content_hash", DataType::Utf8, true),
+    ]))
+}
 
-    // -------- file_embeddings table --------
-    let file_emb_schema = Arc::new(Schema::new(vec![
+pub(crate) fn file_embeddings_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
         Field::new("id", DataType::Int32, false),
         Field::new("file_id", DataType::Int32, false),
         Field::new("chunk_index", DataType::Int32, false),
         Field::new("chunk_text", DataType::Utf8, true),
-        Field::new(
-            "content_vec",
-            DataType::FixedSizeList(
-                Arc::new(Field::new("item", DataType::Float32, true)),
-                VECTOR_DIM,
-            ),
-            false,
-        ),
-    ]));
-
-    let file_emb_batch = RecordBatch::try_new(
-        file_emb_schema.clone(),
-        vec![
-            Arc::new(Int32Array::from_iter_values(0..256)),
-            Arc::new(Int32Array::from_iter_values(0..256)),
-            Arc::new(Int32Array::from_iter_values((0..256).map(|_| 0))),
-            Arc::new(StringArray::from_iter(
-                (0..256).map(|_| Some("Chunked content".to_string())),
-            )),
-            Arc::new(
-                FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
-                    (0..256).map(|_| Some(vec![Some(0.5); VECTOR_DIM as usize])),
-                    VECTOR_DIM,
-                ),
-            ),
-        ],
-    )
-    .map_err(|e| format!("Failed to create file_embeddings batch: {}", e))?;
-
-    ensure_table(
-        &db,
-        "file_embeddings",
-        file_emb_schema,
-        file_emb_batch,
-        |_| {
-            Box::pin(async { Ok(()) }) // no indexes for now
-        },
-    )
-    .await?;
+        vector_field("content_vec", false),
+    ]))
+}
 
-    // -------- folder table --------
-    let folder_schema = Arc::new(Schema::new(vec![
+fn folder_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
         Field::new("id", DataType::Int32, false),
         Field::new("folder_name", DataType::Utf8, false),
         Field::new("created_date", DataType::Utf8, false),
-        Field::new(
-            "folder_metadata_embed",
-            DataType::FixedSizeList(
-                Arc::new(Field::new("item", DataType::Float32, true)),
-                VECTOR_DIM,
-            ),
-            false,
-        ),
-    ]));
-
-    let folder_batch = RecordBatch::try_new(
-        folder_schema.clone(),
-        vec![
-            Arc::new(Int32Array::from_iter_values(0..1)),
-            Arc::new(StringArray::from(vec!["ExampleFolder"])),
-            Arc::new(StringArray::from(vec!["2025-09-09"])),
-            Arc::new(
-                FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
-                    vec![Some(vec![Some(0.0); VECTOR_DIM as usize])],
-                    VECTOR_DIM,
-                ),
-            ),
-        ],
+        vector_field("folder_metadata_embed", false),
+    ]))
+}
+
+/// Deletes the seed rows older versions created. Counting first keeps startup from writing
+/// a new table version when there is nothing to delete.
+async fn remove_placeholder_rows(
+    table: &lancedb::Table,
+    table_name: &str,
+    filter: &str,
+) -> Result<(), String> {
+    let count = table
+        .count_rows(Some(filter.to_string()))
+        .await
+        .map_err(|e| format!("Failed to count placeholder rows in {}: {}", table_name, e))?;
+    if count > 0 {
+        table
+            .delete(filter)
+            .await
+            .map_err(|e| format!("Failed to remove placeholder rows from {}: {}", table_name, e))?;
+        println!("Removed {} placeholder rows from LanceDB table {}", count, table_name);
+    }
+    Ok(())
+}
+
+/// A LanceDB index, and how many rows its table needs before it is worth building.
+struct IndexSpec {
+    table: &'static str,
+    column: &'static str,
+    index_type: &'static str,
+    min_rows: usize,
+}
+
+// IVF-PQ training needs at least 256 vectors; FTS works from the first row
+const INDEX_SPECS: &[IndexSpec] = &[
+    IndexSpec { table: "files", column: "name", index_type: "fts", min_rows: 1 },
+    IndexSpec { table: "files", column: "content", index_type: "fts", min_rows: 1 },
+    IndexSpec { table: "files", column: "vector", index_type: "vector", min_rows: 256 },
+    IndexSpec { table: "file_embeddings", column: "content_vec", index_type: "vector", min_rows: 256 },
+];
+
+// Indexes are retrained once their table has doubled since the last build
+const REBUILD_GROWTH_FACTOR: usize = 2;
+
+/// Rows the index was last built over, or None if it has never been built.
+fn indexed_rows(db: &Connection, spec: &IndexSpec) -> rusqlite::Result<Option<usize>> {
+    db.query_row(
+        "SELECT indexed_rows FROM lancedb_indexes
+         WHERE table_name = ?1 AND column_name = ?2 AND status = 'built'",
+        params![spec.table, spec.column],
+        |row| row.get::<_, i64>(0),
     )
-    .map_err(|e| format!("Failed to create folder batch: {}", e))?;
+    .optional()
+    .map(|rows| rows.map(|r| r.max(0) as usize))
+}
 
-    ensure_table(&db, "folder", folder_schema, folder_batch, |_| {
-        Box::pin(async { Ok(()) })
-    })
-    .await?;
+fn record_index_state(
+    db: &Connection,
+    spec: &IndexSpec,
+    status: &str,
+    indexed_rows: Option<usize>,
+) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT INTO lancedb_indexes (table_name, column_name, index_type, status, indexed_rows, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(table_name, column_name) DO UPDATE SET
+             status = excluded.status,
+             indexed_rows = COALESCE(excluded.indexed_rows, lancedb_indexes.indexed_rows),
+             updated_at = excluded.updated_at",
+        params![
+            spec.table,
+            spec.column,
+            spec.index_type,
+            status,
+            indexed_rows.map(|r| r as i64),
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
 
+/// Builds each LanceDB index once its table holds enough real rows, and retrains it as the
+/// table grows. Indexes still waiting for data are marked 'needed'. The writer is only taken
+/// to update the tracking rows, never while an index builds.
+pub async fn ensure_lancedb_indexes() -> Result<(), String> {
+    let lance = connect_lancedb().await?;
+
+    for spec in INDEX_SPECS {
+        let table = lance
+            .open_table(spec.table)
+            .execute()
+            .await
+            .map_err(|e| format!("Failed to open {} table: {}", spec.table, e))?;
+        let rows = table
+            .count_rows(None)
+            .await
+            .map_err(|e| format!("Failed to count rows in {}: {}", spec.table, e))?;
+        let built_over = indexed_rows(&crate::database::get_read_connection()?, spec)
+            .map_err(|e| e.to_string())?;
+
+        let due = rows >= spec.min_rows
+            && built_over.is_none_or(|built| rows >= built.max(1) * REBUILD_GROWTH_FACTOR);
+        if !due {
+            if built_over.is_none() {
+                record_index_state(&crate::database::get_connection(), spec, "needed", None)
+                    .map_err(|e| e.to_string())?;
+            }
+            continue;
+        }
+
+        let index = match spec.index_type {
+            "fts" => Index::FTS(FtsIndexBuilder::default()),
+            _ => Index::Auto,
+        };
+        match table.create_index(&[spec.column], index).execute().await {
+            Ok(()) => {
+                println!("Built {} index on {}.{} over {} rows", spec.index_type, spec.table, spec.column, rows);
+                record_index_state(&crate::database::get_connection(), spec, "built", Some(rows))
+                    .map_err(|e| e.to_string())?;
+            }
+            Err(e) => {
+                // Searches still work without the index, just slower; retry after the next scan
+                eprintln!("Failed to build index on {}.{}: {}", spec.table, spec.column, e);
+                record_index_state(&crate::database::get_connection(), spec, "needed", None)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,457 @@
+// Versioned schema changes. New tables come from `create_all_sql` (CREATE ... IF NOT EXISTS);
+// anything that changes a table an older version already created goes through a migration.
+use crate::database::lancedb_ops::{connect_lancedb, get_app_data_dir};
+use crate::database::schema::{self, ensure_column};
+use crate::database::vector_index::{migrate_legacy_vectors, LanceVectorIndex};
+use crate::file_scanner::db::mark_vector_indexed;
+use crate::file_scanner::lancedb::{load_file_ids_lancedb, rekey_rows_lancedb};
+use arrow_schema::DataType;
+use chrono::Utc;
+use lancedb::table::{ColumnAlteration, NewColumnTransform, Table};
+use rusqlite::{params, Connection, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub const SQLITE_STORE: &str = "sqlite";
+pub const LANCEDB_STORE: &str = "lancedb";
+
+// Backups older than the newest few are deleted after each new one
+const BACKUPS_KEPT: usize = 5;
+
+pub const CREATE_SCHEMA_VERSION_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS schema_version (
+    store TEXT NOT NULL,             -- 'sqlite' or 'lancedb'
+    version INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    applied_at TEXT NOT NULL,
+    PRIMARY KEY (store, version)
+);
+";
+
+struct Migration {
+    version: i64,
+    name: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+// Append only: never renumber or edit a migration that has shipped.
+// Steps use `ensure_column` because databases from before versioning may already have the columns.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "folder_rollup",
+        up: |conn| {
+            // Each file points at its folder, folders keep recursive aggregates
+            ensure_column(conn, "files", "folder_id", "INTEGER REFERENCES folders(id)")?;
+            ensure_column(conn, "folders", "max_score", "REAL NOT NULL DEFAULT 0")?;
+            ensure_column(conn, "folders", "latest_modified", "TEXT")?;
+            conn.execute_batch(
+                "CREATE INDEX IF NOT EXISTS idx_files_folder_id ON files(folder_id);
+                 CREATE INDEX IF NOT EXISTS idx_folders_parent_id ON folders(parent_folder_id);",
+            )
+        },
+    },
+    Migration {
+        version: 2,
+        name: "rule_engine",
+        up: |conn| {
+            // Pattern kind, explicit action, priority and depth bound on every rule table
+            for table in ["path_rules", "folder_rules", "extension_rules", "filename_rules"] {
+                ensure_column(conn, table, "match_type", "TEXT NOT NULL DEFAULT 'exact'")?;
+                ensure_column(conn, table, "action", "TEXT")?;
+                ensure_column(conn, table, "priority", "INTEGER NOT NULL DEFAULT 0")?;
+                ensure_column(conn, table, "max_depth", "INTEGER")?;
+            }
+            Ok(())
+        },
+    },
+    Migration {
+        version: 3,
+        name: "file_content_hash",
+        up: |conn| {
+            ensure_column(conn, "files", "content_hash", "TEXT")?;
+            ensure_column(conn, "files", "vector_indexed", "BOOLEAN NOT NULL DEFAULT 0")?;
+            // Files stored before the flag existed have their vectors if they have chunks
+            conn.execute(
+                "UPDATE files SET vector_indexed = 1
+                 WHERE id IN (SELECT DISTINCT file_id FROM file_chunks)",
+                [],
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 4,
+        name: "retire_sqlite_vec",
+        up: |conn| {
+            // Vectors are copied to a plain table and moved into the vector index once LanceDB
+            // is open (`vector_index::migrate_legacy_vectors`)
+            let has_vec_table: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'file_vec')",
+                [],
+                |row| row.get(0),
+            )?;
+            if has_vec_table {
+                conn.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS legacy_file_vectors (
+                         file_id INTEGER PRIMARY KEY,
+                         vector BLOB NOT NULL
+                     );
+                     INSERT OR IGNORE INTO legacy_file_vectors (file_id, vector)
+                         SELECT m.file_id, v.content_vec
+                         FROM file_vec_map m JOIN file_vec v ON v.rowid = m.vec_rowid
+                         WHERE m.file_id IN (SELECT id FROM files);
+                     DROP TABLE file_vec;",
+                )?;
+            }
+            conn.execute_batch("DROP TABLE IF EXISTS file_vec_map;")
+        },
+    },
+];
+
+/// A change to one LanceDB table: new columns computed from SQL expressions, and column casts.
+struct LanceMigration {
+    version: i64,
+    name: &'static str,
+    table: &'static str,
+    add_columns: &'static [(&'static str, &'static str)],
+    cast_columns: &'static [(&'static str, DataType)],
+    /// Runs `rekey_legacy_rows` after the column changes
+    rekey_legacy_ids: bool,
+}
+
+const LANCEDB_MIGRATIONS: &[LanceMigration] = &[
+    LanceMigration {
+        version: 1,
+        name: "files_content_hash",
+        table: "files",
+        add_columns: &[("content_hash", "CAST(NULL AS STRING)")],
+        cast_columns: &[],
+        rekey_legacy_ids: false,
+    },
+    LanceMigration {
+        version: 2,
+        name: "rekey_legacy_ids",
+        table: "files",
+        add_columns: &[],
+        cast_columns: &[],
+        rekey_legacy_ids: true,
+    },
+];
+
+pub fn current_version(conn: &Connection, store: &str) -> Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version WHERE store = ?1",
+        params![store],
+        |row| row.get(0),
+    )
+}
+
+/// Newest version this build knows for `store`.
+pub fn latest_version(store: &str) -> i64 {
+    let versions: Vec<i64> = if store == LANCEDB_STORE {
+        LANCEDB_MIGRATIONS.iter().map(|m| m.version).collect()
+    } else {
+        MIGRATIONS.iter().map(|m| m.version).collect()
+    };
+    versions.into_iter().max().unwrap_or(0)
+}
+
+fn record_version(conn: &Connection, store: &str, version: i64, name: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO schema_version (store, version, name, applied_at) VALUES (?1, ?2, ?3, ?4)",
+        params![store, version, name, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+fn has_existing_data(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'files')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+pub fn backups_dir() -> Option<PathBuf> {
+    get_app_data_dir().map(|dir| dir.join("backups"))
+}
+
+/// Copies the whole database to the backups folder with `VACUUM INTO`, which also works
+/// while the connection is open.
+pub fn backup_database(conn: &Connection, from_version: i64) -> Result<PathBuf> {
+    let dir = backups_dir().ok_or_else(|| {
+        rusqlite::Error::InvalidPath(PathBuf::from("Could not get app data directory"))
+    })?;
+    // A missing folder surfaces as a VACUUM error below
+    std::fs::create_dir_all(&dir).ok();
+    let target = dir.join(format!(
+        "database-{}-v{}.db",
+        Utc::now().format("%Y%m%d%H%M%S"),
+        from_version
+    ));
+    conn.execute("VACUUM INTO ?1", params![target.to_string_lossy()])?;
+    prune_backups(&dir);
+    Ok(target)
+}
+
+fn prune_backups(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("database-") && n.ends_with(".db"))
+        })
+        .collect();
+    // Names start with the timestamp, so they sort oldest first
+    backups.sort();
+    let excess = backups.len().saturating_sub(BACKUPS_KEPT);
+    for old in &backups[..excess] {
+        if let Err(e) = std::fs::remove_file(old) {
+            eprintln!("Failed to remove old backup {}: {}", old.display(), e);
+        }
+    }
+}
+
+/// Creates missing tables and applies pending SQLite migrations in order, each in its own
+/// transaction. An existing database is backed up first.
+pub fn run_migrations(conn: &Connection) -> Result<()> {
+    conn.execute_batch(CREATE_SCHEMA_VERSION_TABLE)?;
+    let current = current_version(conn, SQLITE_STORE)?;
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+
+    if !pending.is_empty() && has_existing_data(conn)? {
+        let backup = backup_database(conn, current)?;
+        println!("Backed up the database to {} before migrating", backup.display());
+    }
+
+    conn.execute_batch(&schema::create_all_sql())?;
+
+    for migration in pending {
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx)?;
+        record_version(&tx, SQLITE_STORE, migration.version, migration.name)?;
+        tx.commit()?;
+        println!("Applied migration {} ({})", migration.version, migration.name);
+    }
+    Ok(())
+}
+
+/// Applies pending LanceDB migrations, then moves legacy sqlite-vec vectors into the index.
+/// Lance keeps every table version, so a failed step leaves the previous version intact; it
+/// is retried on the next start.
+pub async fn run_lancedb_migrations() -> std::result::Result<(), String> {
+    let current = {
+        let conn = crate::database::get_connection();
+        current_version(&conn, LANCEDB_STORE).map_err(|e| e.to_string())?
+    };
+    let pending: Vec<&LanceMigration> = LANCEDB_MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current)
+        .collect();
+
+    let db = connect_lancedb().await?;
+    for migration in pending {
+        let table = db
+            .open_table(migration.table)
+            .execute()
+            .await
+            .map_err(|e| format!("Failed to open {} table: {}", migration.table, e))?;
+        let schema = table.schema().await.map_err(|e| e.to_string())?;
+
+        // Tables created after the change already have the new columns
+        let additions: Vec<(String, String)> = migration
+            .add_columns
+            .iter()
+            .filter(|(name, _)| schema.field_with_name(name).is_err())
+            .map(|(name, expr)| (name.to_string(), expr.to_string()))
+            .collect();
+        if !additions.is_empty() {
+            table
+                .add_columns(NewColumnTransform::SqlExpressions(additions), None)
+                .await
+                .map_err(|e| format!("LanceDB migration {} failed: {}", migration.name, e))?;
+        }
+
+        let casts: Vec<ColumnAlteration> = migration
+            .cast_columns
+            .iter()
+            .filter(|(name, to)| schema.field_with_name(name).is_ok_and(|f| f.data_type() != to))
+            .map(|(name, to)| ColumnAlteration::new(name.to_string()).cast_to(to.clone()))
+            .collect();
+        if !casts.is_empty() {
+            table
+                .alter_columns(&casts)
+                .await
+                .map_err(|e| format!("LanceDB migration {} failed: {}", migration.name, e))?;
+        }
+
+        if migration.rekey_legacy_ids {
+            let changed = rekey_legacy_rows(&db)
+                .await
+                .map_err(|e| format!("LanceDB migration {} failed: {}", migration.name, e))?;
+            println!("Re-keyed {} legacy LanceDB rows", changed);
+        }
+
+        let conn = crate::database::get_connection();
+        record_version(&conn, LANCEDB_STORE, migration.version, migration.name)
+            .map_err(|e| e.to_string())?;
+        println!("Applied LanceDB migration {} ({})", migration.version, migration.name);
+    }
+
+    // Whatever SQLite migration 4 copied out of sqlite-vec; a no-op once it has all moved
+    let index = LanceVectorIndex::open().await?;
+    migrate_legacy_vectors(&index).await?;
+    Ok(())
+}
+
+async fn open_lance_table(
+    db: &lancedb::Connection,
+    name: &str,
+) -> std::result::Result<Table, String> {
+    db.open_table(name)
+        .execute()
+        .await
+        .map_err(|e| format!("Failed to open {} table: {}", name, e))
+}
+
+/// SQLite file ids by path; ids that don't fit a LanceDB id are left out.
+fn sqlite_file_ids(conn: &Connection) -> Result<HashMap<String, i32>> {
+    let mut stmt = conn.prepare("SELECT id, path FROM files")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+    let mut ids = HashMap::new();
+    for row in rows {
+        let (id, path) = row?;
+        if let Ok(id) = i32::try_from(id) {
+            ids.insert(path, id);
+        }
+    }
+    Ok(ids)
+}
+
+/// `(id, file_id)` of every stored chunk and the next id `file_chunks` would hand out.
+fn sqlite_chunk_ids(conn: &Connection) -> Result<(HashSet<(i32, i32)>, i64)> {
+    let mut stmt = conn.prepare("SELECT id, file_id FROM file_chunks")?;
+    let chunks = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+    // AUTOINCREMENT never reuses ids of deleted rows, so the sequence counts too
+    let sequence: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(seq), 0) FROM sqlite_sequence WHERE name = 'file_chunks'",
+        [],
+        |row| row.get(0),
+    )?;
+    let next = chunks.iter().map(|(id, _)| *id).max().unwrap_or(0).max(sequence) + 1;
+    let chunks = chunks
+        .into_iter()
+        .filter_map(|(id, file_id)| Some((i32::try_from(id).ok()?, i32::try_from(file_id).ok()?)))
+        .collect();
+    Ok((chunks, next))
+}
+
+/// Rows written before LanceDB ids came from SQLite carry random ids. Each such `files` row
+/// takes the SQLite id of its path, and its chunks get new `file_chunks` rows. A legacy row
+/// whose path already has a correctly keyed row is dropped with its chunks; rows whose path
+/// SQLite doesn't know stay as they are for the consistency check to report.
+/// Chunks are moved before files, so a retry after a failure still finds the legacy ids.
+async fn rekey_legacy_rows(db: &lancedb::Connection) -> std::result::Result<usize, String> {
+    let files_table = open_lance_table(db, "files").await?;
+    let emb_table = open_lance_table(db, "file_embeddings").await?;
+    let lance_files = load_file_ids_lancedb(&files_table).await.map_err(|e| e.to_string())?;
+
+    let (sqlite_ids, (known_chunks, mut next_chunk_id)) = {
+        let conn = crate::database::get_read_connection()?;
+        let files = sqlite_file_ids(&conn).map_err(|e| e.to_string())?;
+        (files, sqlite_chunk_ids(&conn).map_err(|e| e.to_string())?)
+    };
+
+    // Old id -> the SQLite id it moves to, or None when its row is a duplicate
+    let mut claimed: HashSet<&str> = lance_files
+        .iter()
+        .filter(|(id, path)| sqlite_ids.get(path) == Some(id))
+        .map(|(_, path)| path.as_str())
+        .collect();
+    let mut moved: BTreeMap<i32, Option<i32>> = BTreeMap::new();
+    for (id, path) in &lance_files {
+        let Some(&new_id) = sqlite_ids.get(path).filter(|new_id| *new_id != id) else {
+            continue;
+        };
+        let target = claimed.insert(path.as_str()).then_some(new_id);
+        moved.entry(*id).or_insert(target);
+    }
+    if moved.is_empty() {
+        return Ok(0);
+    }
+    let old_ids = moved.keys().map(i32::to_string).collect::<Vec<_>>().join(", ");
+
+    let has_chunks: HashSet<i32> = known_chunks.iter().map(|(_, file_id)| *file_id).collect();
+    let mut next_index: HashMap<i32, i32> = HashMap::new();
+    let mut new_chunks: Vec<(i32, i32, i32)> = Vec::new();
+    let chunks_changed = rekey_rows_lancedb(
+        &emb_table,
+        &format!("file_id IN ({})", old_ids),
+        &["id", "file_id", "chunk_index"],
+        None,
+        |row, _| {
+            let (id, file_id) = (row[0], row[1]);
+            // Chunks of a correctly keyed file whose id happens to equal a legacy one
+            let target = moved.get(&file_id).filter(|_| !known_chunks.contains(&(id, file_id)));
+            let Some(&target) = target else {
+                return Some(row.to_vec());
+            };
+            let new_file_id = target.filter(|new_id| !has_chunks.contains(new_id))?;
+            let chunk_id = i32::try_from(next_chunk_id).ok()?;
+            next_chunk_id += 1;
+            let index = next_index.entry(new_file_id).or_insert(0);
+            let chunk = (chunk_id, new_file_id, *index);
+            *index += 1;
+            new_chunks.push(chunk);
+            Some(vec![chunk.0, chunk.1, chunk.2])
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    {
+        let conn = crate::database::get_connection();
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        let now = Utc::now().to_rfc3339();
+        for (chunk_id, file_id, chunk_index) in &new_chunks {
+            tx.execute(
+                "INSERT INTO file_chunks (id, file_id, chunk_index, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![chunk_id, file_id, chunk_index, now],
+            )
+            .map_err(|e| e.to_string())?;
+            mark_vector_indexed(&tx, i64::from(*file_id)).map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+
+    let mut emitted: HashSet<i32> = HashSet::new();
+    let files_changed = rekey_rows_lancedb(
+        &files_table,
+        &format!("id IN ({})", old_ids),
+        &["id"],
+        Some("path"),
+        |row, path| {
+            let id = row[0];
+            match path.and_then(|path| sqlite_ids.get(path)) {
+                Some(&new_id) if new_id != id => {
+                    let target = moved.get(&id).copied().flatten();
+                    (target == Some(new_id) && emitted.insert(new_id)).then(|| vec![new_id])
+                }
+                // Correctly keyed, or a path SQLite doesn't know
+                _ => Some(row.to_vec()),
+            }
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(chunks_changed + files_changed)
+}
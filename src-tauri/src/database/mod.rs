@@ -2,12 +2,13 @@
 // No changes related to the error, just ensuring imports are correct.
 // Removed `use crate::embed_and_store::get_embedding;`
 use once_cell::sync::Lazy;
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::ffi::sqlite3_auto_extension;
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OpenFlags, Result};
 use std::path::PathBuf;
 use std::sync::Mutex;
 // use crate::test::{debug_print_available_functions, debug_print_file_vec_schema};
-use rusqlite::params;
 
 pub mod rules;
 pub mod schema;
@@ -15,34 +16,98 @@ pub mod search;
 pub mod seeder;
 pub mod lancedb_ops;
 pub mod ann_search;
+pub mod facets;
+pub mod search_session;
+pub mod temporal;
+pub mod query_compiler;
+pub mod interactions;
+pub mod history;
+pub mod folder_stats;
+pub mod settings;
+pub mod consistency;
+pub mod migrations;
+pub mod journal;
+pub mod archive;
+pub mod lancedb_maintenance;
+pub mod vector_index;
+pub mod storage;
 
 use crate::database::lancedb_ops::get_app_data_dir;
 
+// Readers beyond this wait for a free connection
+const READ_POOL_SIZE: u32 = 4;
+
+// How long a connection waits on another's write lock before failing with "database is locked"
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+fn database_path() -> PathBuf {
+    get_app_data_dir()
+        .expect("Could not get app data directory")
+        .join("database.db")
+}
+
+// The single writer. WAL mode lets the read pool keep reading while it writes.
 static DB_CONNECTION: Lazy<Mutex<Connection>> = Lazy::new(|| {
+    // sqlite-vec is only loaded so migration 4 can read and drop the old `file_vec` table
     unsafe {
         sqlite3_auto_extension(Some(std::mem::transmute(
             sqlite_vec::sqlite3_vec_init as *const (),
         )));
     }
 
-    let database_path = get_app_data_dir()
-        .expect("Could not get app data directory")
-        .join("database.db");
+    let database_path = database_path();
 
     std::fs::create_dir_all(database_path.parent().unwrap())
         .expect("Could not create app data directory");
 
     let conn = Connection::open(&database_path).expect("Failed to open DB");
+    conn.execute_batch(&format!(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         PRAGMA busy_timeout = {};",
+        BUSY_TIMEOUT_MS
+    ))
+    .expect("Failed to configure DB");
 
     println!("Connected to database at: {}", database_path.display());
 
     Mutex::new(conn)
 });
 
+static READ_POOL: Lazy<Pool<SqliteConnectionManager>> = Lazy::new(|| {
+    // The writer creates the file, switches it to WAL and registers sqlite-vec first
+    Lazy::force(&DB_CONNECTION);
+
+    let manager = SqliteConnectionManager::file(database_path())
+        .with_flags(OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .with_init(|conn| conn.execute_batch(&format!("PRAGMA busy_timeout = {};", BUSY_TIMEOUT_MS)));
+    Pool::builder()
+        .max_size(READ_POOL_SIZE)
+        .build(manager)
+        .expect("Failed to create DB read pool")
+});
+
+/// The writer connection. Hold it only for as long as the write takes: every other write
+/// waits on it. Reads should use `get_read_connection`.
 pub fn get_connection() -> std::sync::MutexGuard<'static, Connection> {
     DB_CONNECTION.lock().expect("Failed to lock DB")
 }
 
+/// A read-only connection from the pool. It sees the last committed state and never waits
+/// on the writer, so searches stay responsive during a scan.
+pub fn get_read_connection() -> std::result::Result<PooledConnection<SqliteConnectionManager>, String> {
+    READ_POOL
+        .get()
+        .map_err(|e| format!("Failed to get a database connection: {}", e))
+}
+
+/// Checks out every read connection, so no reader is mid-query while the database file is
+/// replaced. Other readers wait until the returned connections are dropped.
+pub fn drain_read_pool(
+) -> std::result::Result<Vec<PooledConnection<SqliteConnectionManager>>, String> {
+    (0..READ_POOL_SIZE).map(|_| get_read_connection()).collect()
+}
+
 pub fn initialize() -> Result<()> {
     let conn = get_connection();
 
@@ -63,7 +128,7 @@ pub fn initialize() -> Result<()> {
     // debug_print_file_vec_schema(&conn);
     // debug_print_available_functions(&conn);
 
-    conn.execute_batch(&schema::create_all_sql())?;
+    migrations::run_migrations(&conn)?;
     println!("Migrations executed successfully");
 
     // Seed data
@@ -73,48 +138,24 @@ pub fn initialize() -> Result<()> {
     Ok(())
 }
 
-/// Calculates and updates the score for each folder based on the average score of its files.
+/// Recomputes every folder's recursive file count, size, average/max score and latest
+/// modification time in a single bottom-up pass.
 pub fn update_folder_scores(conn: &Connection) -> Result<()> {
     println!("Starting to update folder scores...");
+    let updated = folder_stats::rollup_all_folders(conn)?;
+    println!("Successfully updated scores for {} folders.", updated);
+    Ok(())
+}
 
-    // Get all folder IDs and paths
-    let mut stmt = conn.prepare("SELECT id, path FROM folders")?;
-    let folder_iter = stmt.query_map([], |row| {
-        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
-    })?;
-
-    let mut folders_to_update = Vec::new();
-    for folder_result in folder_iter {
-        let (folder_id, folder_path) = folder_result?;
-        let like_pattern = format!("{}%", folder_path);
-
-        // Calculate the average score of files within that folder path
-        // Calculate the average score of files within that folder path
-        let avg_score: f64 = conn.query_row(
-            "SELECT AVG(score) FROM files WHERE path LIKE ?1",
-            [like_pattern],
-            |row| row.get(0).or(Ok(0.0)), // If no files, avg is NULL, so default to 0.0
-        )?;
-
-        // Clamp between 0.0 and 10.0 and round to 1 decimal place
-        let clamped = avg_score.max(0.0f64).min(10.0f64);
-        let rounded = (clamped * 10.0).round() / 10.0;
-
-        folders_to_update.push((folder_id, rounded));
-    }
-
-    // Update the scores in a single transaction
-    let tx = conn.unchecked_transaction()?;
-    for (folder_id, score) in folders_to_update {
-        tx.execute(
-            "UPDATE folders SET score = ?1 WHERE id = ?2",
-            params![score, folder_id],
-        )?;
-    }
-    tx.commit()?;
-
-    println!("Successfully updated scores for all folders.");
+/// Incremental variant for a scan: only the folders containing `paths` and their ancestors.
+pub fn update_folder_scores_for_paths<'a>(
+    conn: &Connection,
+    paths: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    let touched = folder_stats::folder_ids_for_paths(conn, paths)?;
+    let updated = folder_stats::rollup_folders(conn, &touched)?;
+    println!("Updated scores for {} touched folders.", updated);
     Ok(())
 }
 
-pub use search::{perform_file_search, SearchResult};
+pub use search::SearchResult;
@@ -0,0 +1,604 @@
+// Safe keyword query compiler: user syntax -> FTS5 MATCH, LanceDB filters and SQLite LIKE clauses
+//
+// Supported syntax:
+//   a b            both terms (AND is implicit, the AND keyword is accepted too)
+//   a OR b         either term
+//   NOT a, -a      exclude a term
+//   "exact phrase" phrase match
+//   prefix*        prefix match
+//   name:x         only match the file name
+//   content:x      only match the file content
+//
+// Malformed input never fails: unbalanced quotes close at the end of the query, dangling
+// operators are dropped and punctuation is quoted away before it reaches FTS5.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryField {
+    Any,
+    Name,
+    Content,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermKind {
+    Word,
+    Phrase,
+    Prefix,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryTerm {
+    pub text: String,
+    pub kind: TermKind,
+    pub field: QueryField,
+}
+
+/// One AND-ed element of the query: a set of OR-ed alternatives, or a single exclusion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryClause {
+    pub negated: bool,
+    pub alternatives: Vec<QueryTerm>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompiledQuery {
+    pub clauses: Vec<QueryClause>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Or,
+    And,
+    Not,
+    Field(QueryField),
+}
+
+/// Parses raw user input. Never fails; unusable fragments are dropped.
+pub fn compile(query: &str) -> CompiledQuery {
+    let tokens = tokenize(query);
+    let mut clauses: Vec<QueryClause> = Vec::new();
+
+    let mut negate_next = false;
+    let mut or_pending = false;
+    let mut field = QueryField::Any;
+
+    for token in tokens {
+        let term = match token {
+            Token::Or => {
+                or_pending = true;
+                continue;
+            }
+            Token::And => {
+                or_pending = false;
+                continue;
+            }
+            Token::Not => {
+                negate_next = true;
+                continue;
+            }
+            Token::Field(f) => {
+                field = f;
+                continue;
+            }
+            Token::Word(word) => word_term(&word, field),
+            Token::Phrase(phrase) => phrase_term(&phrase, field),
+        };
+        field = QueryField::Any;
+
+        let Some(term) = term else {
+            negate_next = false;
+            continue;
+        };
+
+        let joins_previous = or_pending
+            && !negate_next
+            && clauses.last().is_some_and(|clause| !clause.negated);
+        if joins_previous {
+            clauses.last_mut().unwrap().alternatives.push(term);
+        } else {
+            clauses.push(QueryClause {
+                negated: negate_next,
+                alternatives: vec![term],
+            });
+        }
+        negate_next = false;
+        or_pending = false;
+    }
+
+    CompiledQuery { clauses }
+}
+
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '"' {
+            chars.next();
+            // An unbalanced quote runs to the end of the query
+            let phrase: String = chars.by_ref().take_while(|&ch| ch != '"').collect();
+            tokens.push(Token::Phrase(phrase));
+            continue;
+        }
+
+        if c == '-' {
+            chars.next();
+            if chars.peek().is_some_and(|ch| !ch.is_whitespace()) {
+                tokens.push(Token::Not);
+            }
+            continue;
+        }
+
+        let mut word = String::new();
+        while let Some(&ch) = chars.peek() {
+            if ch.is_whitespace() || ch == '"' {
+                break;
+            }
+            word.push(ch);
+            chars.next();
+            if ch == ':' {
+                break;
+            }
+        }
+
+        match word.as_str() {
+            "OR" | "|" | "||" => tokens.push(Token::Or),
+            "AND" | "&&" => tokens.push(Token::And),
+            "NOT" => tokens.push(Token::Not),
+            _ => match word.to_lowercase().as_str() {
+                "name:" | "filename:" | "title:" => tokens.push(Token::Field(QueryField::Name)),
+                "content:" | "text:" | "body:" => tokens.push(Token::Field(QueryField::Content)),
+                _ => tokens.push(Token::Word(word)),
+            },
+        }
+    }
+    tokens
+}
+
+fn word_term(word: &str, field: QueryField) -> Option<QueryTerm> {
+    let (text, kind) = match word.strip_suffix('*') {
+        Some(stem) => (stem.trim_end_matches('*'), TermKind::Prefix),
+        None => (word, TermKind::Word),
+    };
+    let text = text.trim_matches(|c: char| !c.is_alphanumeric());
+    if !text.chars().any(|c| c.is_alphanumeric()) {
+        return None;
+    }
+    Some(QueryTerm {
+        text: text.to_string(),
+        kind,
+        field,
+    })
+}
+
+fn phrase_term(phrase: &str, field: QueryField) -> Option<QueryTerm> {
+    let text = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !text.chars().any(|c| c.is_alphanumeric()) {
+        return None;
+    }
+    Some(QueryTerm {
+        text,
+        kind: TermKind::Phrase,
+        field,
+    })
+}
+
+impl CompiledQuery {
+    fn positive(&self) -> impl Iterator<Item = &QueryClause> {
+        self.clauses.iter().filter(|c| !c.negated)
+    }
+
+    fn negative(&self) -> impl Iterator<Item = &QueryClause> {
+        self.clauses.iter().filter(|c| c.negated)
+    }
+
+    /// Text for the embedding prongs: every positive term, without operators or exclusions.
+    pub fn semantic_text(&self) -> String {
+        self.positive()
+            .flat_map(|c| c.alternatives.iter())
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Like `semantic_text`, but falls back to the original text for exclusion-only queries,
+    /// which still need something to embed.
+    pub fn semantic_text_or(&self, original: &str) -> String {
+        let text = self.semantic_text();
+        if text.is_empty() {
+            original.trim().to_string()
+        } else {
+            text
+        }
+    }
+
+    /// FTS5 MATCH expression. Every term is quoted, so user punctuation can never become
+    /// FTS5 syntax. Returns `None` when there is nothing positive to match (FTS5 cannot
+    /// express a query made only of exclusions).
+    pub fn to_fts5(&self) -> Option<String> {
+        let positive: Vec<String> = self
+            .positive()
+            .map(|clause| {
+                let alternatives: Vec<String> =
+                    clause.alternatives.iter().map(fts5_term).collect();
+                if alternatives.len() == 1 {
+                    alternatives.into_iter().next().unwrap()
+                } else {
+                    format!("({})", alternatives.join(" OR "))
+                }
+            })
+            .collect();
+        if positive.is_empty() {
+            return None;
+        }
+
+        let mut expression = positive.join(" AND ");
+        for clause in self.negative() {
+            for term in &clause.alternatives {
+                expression = format!("({}) NOT {}", expression, fts5_term(term));
+            }
+        }
+        Some(expression)
+    }
+
+    /// A looser FTS5 expression used when the strict one fails: any positive term matches.
+    pub fn to_fts5_fallback(&self) -> Option<String> {
+        let terms: Vec<String> = self
+            .positive()
+            .flat_map(|c| c.alternatives.iter())
+            .map(|t| fts5_quote(&t.text))
+            .collect();
+        (!terms.is_empty()).then(|| terms.join(" OR "))
+    }
+
+    /// LanceDB (DataFusion SQL) predicate for the vector prongs. Exclusions and explicitly
+    /// scoped or quoted terms become hard constraints; plain words stay semantic-only.
+    /// `content_column` is `None` for tables without a content column.
+    pub fn to_lancedb_filter(&self, name_column: &str, content_column: Option<&str>) -> Option<String> {
+        let mut predicates = Vec::new();
+
+        for clause in self.positive() {
+            let constraints: Vec<String> = clause
+                .alternatives
+                .iter()
+                .filter(|t| t.kind == TermKind::Phrase || t.field != QueryField::Any)
+                .filter_map(|t| lancedb_contains(t, name_column, content_column))
+                .collect();
+            // Only constrain when every alternative is a constraint, otherwise
+            // a semantic-only alternative could still satisfy the clause.
+            if !constraints.is_empty() && constraints.len() == clause.alternatives.len() {
+                predicates.push(format!("({})", constraints.join(" OR ")));
+            }
+        }
+
+        for clause in self.negative() {
+            for term in &clause.alternatives {
+                if let Some(contains) = lancedb_contains(term, name_column, content_column) {
+                    predicates.push(format!("NOT ({})", contains));
+                }
+            }
+        }
+
+        (!predicates.is_empty()).then(|| predicates.join(" AND "))
+    }
+
+    /// SQLite clause and LIKE parameters for prongs that only look at a name column.
+    /// Returns `None` when no term applies to names, in which case the prong should be skipped.
+    pub fn to_sqlite_name_filter(&self, name_column: &str) -> Option<(String, Vec<String>)> {
+        let mut clauses = Vec::new();
+        let mut params = Vec::new();
+
+        for clause in self.positive() {
+            let alternatives: Vec<&QueryTerm> = clause
+                .alternatives
+                .iter()
+                .filter(|t| t.field != QueryField::Content)
+                .collect();
+            if alternatives.is_empty() {
+                continue;
+            }
+            let likes: Vec<String> = alternatives
+                .iter()
+                .map(|t| {
+                    params.push(like_pattern(t));
+                    format!("{} LIKE ? ESCAPE '\\'", name_column)
+                })
+                .collect();
+            clauses.push(format!("({})", likes.join(" OR ")));
+        }
+
+        if clauses.is_empty() {
+            return None;
+        }
+
+        for clause in self.negative() {
+            for term in clause.alternatives.iter().filter(|t| t.field != QueryField::Content) {
+                params.push(like_pattern(term));
+                clauses.push(format!("{} NOT LIKE ? ESCAPE '\\'", name_column));
+            }
+        }
+
+        Some((clauses.join(" AND "), params))
+    }
+
+    /// Whether a result title/path passes the exclusions (used for prongs without SQL filters).
+    pub fn allows_name(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        !self.negative().flat_map(|c| c.alternatives.iter()).any(|t| {
+            t.field != QueryField::Content && name.contains(&t.text.to_lowercase())
+        })
+    }
+}
+
+fn fts5_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn fts5_term(term: &QueryTerm) -> String {
+    let quoted = fts5_quote(&term.text);
+    let body = match term.kind {
+        TermKind::Prefix => format!("{}*", quoted),
+        TermKind::Word | TermKind::Phrase => quoted,
+    };
+    match term.field {
+        QueryField::Any => body,
+        QueryField::Name => format!("name : {}", body),
+        QueryField::Content => format!("content : {}", body),
+    }
+}
+
+/// Escapes LIKE wildcards so user text matches literally; used with `ESCAPE '\'` in both stores.
+pub fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn lancedb_contains(term: &QueryTerm, name_column: &str, content_column: Option<&str>) -> Option<String> {
+    if term.text.trim().is_empty() {
+        return None;
+    }
+    // Backslashes aren't string escapes in LanceDB's SQL, only quotes need doubling
+    let needle = escape_like(&term.text.to_lowercase()).replace('\'', "''");
+
+    let name_like = format!("lower({}) LIKE '%{}%' ESCAPE '\\'", name_column, needle);
+    let content_like =
+        content_column.map(|c| format!("lower({}) LIKE '%{}%' ESCAPE '\\'", c, needle));
+    match (term.field, content_like) {
+        (QueryField::Name, _) => Some(name_like),
+        (QueryField::Content, Some(content_like)) => Some(content_like),
+        (QueryField::Content, None) => None,
+        (QueryField::Any, Some(content_like)) => Some(format!("{} OR {}", name_like, content_like)),
+        (QueryField::Any, None) => Some(name_like),
+    }
+}
+
+/// Every term kind matches anywhere in the value: a prefix anchored at the start could never
+/// match an absolute path, and would miss "final_report.pdf" for `report*`, which the FTS5
+/// prefix query finds.
+fn like_pattern(term: &QueryTerm) -> String {
+    format!("%{}%", escape_like(&term.text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Case<'a> {
+        query: &'a str,
+        fts5: Option<&'a str>,
+        fallback: Option<&'a str>,
+        lancedb: Option<&'a str>,
+        sqlite: Option<(&'a str, &'a [&'a str])>,
+        semantic: &'a str,
+    }
+
+    const NAME_LIKE: &str = r"name LIKE ? ESCAPE '\'";
+    const NOT_DRAFT: &str = concat!(
+        r"NOT (lower(name) LIKE '%draft%' ESCAPE '\' ",
+        r"OR lower(content) LIKE '%draft%' ESCAPE '\')"
+    );
+
+    fn empty(query: &str) -> Case<'_> {
+        Case { query, fts5: None, fallback: None, lancedb: None, sqlite: None, semantic: "" }
+    }
+
+    #[test]
+    fn compiles_every_output() {
+        let and_names = format!("({}) AND ({})", NAME_LIKE, NAME_LIKE);
+        let or_names = format!("({} OR {})", NAME_LIKE, NAME_LIKE);
+        let one_name = format!("({})", NAME_LIKE);
+        let name_not = format!("({}) AND name NOT LIKE ? ESCAPE '\\'", NAME_LIKE);
+        let or_not = format!("{} AND name NOT LIKE ? ESCAPE '\\'", or_names);
+        let phrase_filter = concat!(
+            r"(lower(name) LIKE '%annual report%' ESCAPE '\' ",
+            r"OR lower(content) LIKE '%annual report%' ESCAPE '\')"
+        );
+
+        let cases = [
+            Case {
+                query: "report budget",
+                fts5: Some(r#""report" AND "budget""#),
+                fallback: Some(r#""report" OR "budget""#),
+                lancedb: None,
+                sqlite: Some((&and_names, &["%report%", "%budget%"])),
+                semantic: "report budget",
+            },
+            Case {
+                query: "report AND budget",
+                fts5: Some(r#""report" AND "budget""#),
+                fallback: Some(r#""report" OR "budget""#),
+                lancedb: None,
+                sqlite: Some((&and_names, &["%report%", "%budget%"])),
+                semantic: "report budget",
+            },
+            Case {
+                query: "report OR budget",
+                fts5: Some(r#"("report" OR "budget")"#),
+                fallback: Some(r#""report" OR "budget""#),
+                lancedb: None,
+                sqlite: Some((&or_names, &["%report%", "%budget%"])),
+                semantic: "report budget",
+            },
+            Case {
+                query: "report OR budget NOT draft",
+                fts5: Some(r#"(("report" OR "budget")) NOT "draft""#),
+                fallback: Some(r#""report" OR "budget""#),
+                lancedb: Some(NOT_DRAFT),
+                sqlite: Some((&or_not, &["%report%", "%budget%", "%draft%"])),
+                semantic: "report budget",
+            },
+            Case {
+                query: "notes -draft",
+                fts5: Some(r#"("notes") NOT "draft""#),
+                fallback: Some(r#""notes""#),
+                lancedb: Some(NOT_DRAFT),
+                sqlite: Some((&name_not, &["%notes%", "%draft%"])),
+                semantic: "notes",
+            },
+            // An exclusion never joins an OR group
+            Case {
+                query: "report OR -draft",
+                fts5: Some(r#"("report") NOT "draft""#),
+                fallback: Some(r#""report""#),
+                lancedb: Some(NOT_DRAFT),
+                sqlite: Some((&name_not, &["%report%", "%draft%"])),
+                semantic: "report",
+            },
+            // Unbalanced quotes close at the end of the query
+            Case {
+                query: r#""annual   report"#,
+                fts5: Some(r#""annual report""#),
+                fallback: Some(r#""annual report""#),
+                lancedb: Some(phrase_filter),
+                sqlite: Some((&one_name, &["%annual report%"])),
+                semantic: "annual report",
+            },
+            Case {
+                query: "name:invoice content:paid",
+                fts5: Some(r#"name : "invoice" AND content : "paid""#),
+                fallback: Some(r#""invoice" OR "paid""#),
+                lancedb: Some(concat!(
+                    r"(lower(name) LIKE '%invoice%' ESCAPE '\') ",
+                    r"AND (lower(content) LIKE '%paid%' ESCAPE '\')"
+                )),
+                sqlite: Some((&one_name, &["%invoice%"])),
+                semantic: "invoice paid",
+            },
+            // Content-only terms leave nothing for a name-only prong
+            Case {
+                query: "content:paid",
+                fts5: Some(r#"content : "paid""#),
+                fallback: Some(r#""paid""#),
+                lancedb: Some(r"(lower(content) LIKE '%paid%' ESCAPE '\')"),
+                sqlite: None,
+                semantic: "paid",
+            },
+            // A prefix matches anywhere in a name or path, like the FTS5 prefix query
+            Case {
+                query: "report*",
+                fts5: Some(r#""report"*"#),
+                fallback: Some(r#""report""#),
+                lancedb: None,
+                sqlite: Some((&one_name, &["%report%"])),
+                semantic: "report",
+            },
+            Case {
+                query: "name:report*",
+                fts5: Some(r#"name : "report"*"#),
+                fallback: Some(r#""report""#),
+                lancedb: Some(r"(lower(name) LIKE '%report%' ESCAPE '\')"),
+                sqlite: Some((&one_name, &["%report%"])),
+                semantic: "report",
+            },
+            // LIKE wildcards and backslashes match literally; quotes are doubled for LanceDB
+            Case {
+                query: r"name:50%_off\x",
+                fts5: Some(r#"name : "50%_off\x""#),
+                fallback: Some(r#""50%_off\x""#),
+                lancedb: Some(r"(lower(name) LIKE '%50\%\_off\\x%' ESCAPE '\')"),
+                sqlite: Some((&one_name, &[r"%50\%\_off\\x%"])),
+                semantic: r"50%_off\x",
+            },
+            Case {
+                query: "name:it's",
+                fts5: Some(r#"name : "it's""#),
+                fallback: Some(r#""it's""#),
+                lancedb: Some(r"(lower(name) LIKE '%it''s%' ESCAPE '\')"),
+                sqlite: Some((&one_name, &["%it's%"])),
+                semantic: "it's",
+            },
+            // Degenerate input compiles to nothing rather than failing
+            empty(""),
+            empty("OR"),
+            empty("-"),
+            empty("name:"),
+            Case {
+                query: "a AND",
+                fts5: Some(r#""a""#),
+                fallback: Some(r#""a""#),
+                lancedb: None,
+                sqlite: Some((&one_name, &["%a%"])),
+                semantic: "a",
+            },
+            // Exclusions alone can't be expressed in FTS5
+            Case {
+                query: "NOT draft",
+                fts5: None,
+                fallback: None,
+                lancedb: Some(NOT_DRAFT),
+                sqlite: None,
+                semantic: "",
+            },
+        ];
+
+        for case in &cases {
+            let compiled = compile(case.query);
+            assert_eq!(compiled.to_fts5().as_deref(), case.fts5, "fts5: {}", case.query);
+            assert_eq!(
+                compiled.to_fts5_fallback().as_deref(),
+                case.fallback,
+                "fallback: {}",
+                case.query
+            );
+            assert_eq!(
+                compiled.to_lancedb_filter("name", Some("content")).as_deref(),
+                case.lancedb,
+                "lancedb: {}",
+                case.query
+            );
+            let sqlite = compiled.to_sqlite_name_filter("name");
+            let sqlite = sqlite.as_ref().map(|(clause, params)| {
+                (clause.as_str(), params.iter().map(String::as_str).collect())
+            });
+            let expected = case.sqlite.map(|(clause, params)| (clause, params.to_vec()));
+            assert_eq!(sqlite, expected, "sqlite: {}", case.query);
+            assert_eq!(compiled.semantic_text(), case.semantic, "semantic: {}", case.query);
+        }
+    }
+
+    #[test]
+    fn exclusion_only_queries_embed_the_original_text() {
+        assert_eq!(compile("NOT draft").semantic_text_or(" NOT draft "), "NOT draft");
+        assert_eq!(compile("notes -draft").semantic_text_or("notes -draft"), "notes");
+    }
+
+    #[test]
+    fn name_exclusions_ignore_content_terms() {
+        let compiled = compile("report -Draft -content:secret");
+        assert!(!compiled.allows_name("/docs/DRAFT report.txt"));
+        assert!(compiled.allows_name("/docs/secret report.txt"));
+    }
+}
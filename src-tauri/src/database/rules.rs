@@ -5,23 +5,32 @@ use chrono::Utc;
 use rusqlite::params;
 
 // Unified rule type enum
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RuleType {
     Include,
     Exclude,
 }
 
 impl RuleType {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             RuleType::Include => "include",
             RuleType::Exclude => "exclude",
         }
     }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "include" => RuleType::Include,
+            _ => RuleType::Exclude,
+        }
+    }
 }
 
 // Rule category enum
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RuleCategory {
     Path,
     Folder,
@@ -30,7 +39,14 @@ pub enum RuleCategory {
 }
 
 impl RuleCategory {
-    fn table_name(&self) -> &'static str {
+    pub const ALL: [RuleCategory; 4] = [
+        RuleCategory::Path,
+        RuleCategory::Folder,
+        RuleCategory::Extension,
+        RuleCategory::Filename,
+    ];
+
+    pub fn table_name(&self) -> &'static str {
         match self {
             RuleCategory::Path => "path_rules",
             RuleCategory::Folder => "folder_rules", 
@@ -39,7 +55,7 @@ impl RuleCategory {
         }
     }
 
-    fn column_name(&self) -> &'static str {
+    pub fn column_name(&self) -> &'static str {
         match self {
             RuleCategory::Path => "path",
             RuleCategory::Folder => "folder_name",
@@ -47,9 +63,210 @@ impl RuleCategory {
             RuleCategory::Filename => "filename",
         }
     }
+
+    /// Only path and folder rules carry `is_recursive`.
+    fn has_recursion(&self) -> bool {
+        matches!(self, RuleCategory::Path | RuleCategory::Folder)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchType {
+    Exact, // Paths match as prefixes, names case-insensitively
+    Glob,
+    Regex,
+}
+
+impl MatchType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchType::Exact => "exact",
+            MatchType::Glob => "glob",
+            MatchType::Regex => "regex",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "glob" => MatchType::Glob,
+            "regex" => MatchType::Regex,
+            _ => MatchType::Exact,
+        }
+    }
+}
+
+/// What a matching rule does to a file or folder. Ordered by severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    Full,     // Index metadata and content
+    Metadata, // Index names, sizes and dates only
+    Skip,     // Leave out entirely
+}
+
+impl RuleAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleAction::Full => "full",
+            RuleAction::Metadata => "metadata",
+            RuleAction::Skip => "skip",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "full" => Some(RuleAction::Full),
+            "metadata" => Some(RuleAction::Metadata),
+            "skip" => Some(RuleAction::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// A row of any of the four rule tables.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScanRule {
+    pub id: i64,
+    pub category: RuleCategory,
+    pub pattern: String,
+    pub match_type: MatchType,
+    pub rule_type: RuleType,
+    /// Explicit action; without one, include means full content and exclude means skip
+    pub action: Option<RuleAction>,
+    pub priority: i64,
+    pub is_recursive: bool,
+    /// Levels below the matched folder the rule reaches; overrides `is_recursive`
+    pub max_depth: Option<u32>,
+}
+
+impl ScanRule {
+    pub fn effective_action(&self) -> RuleAction {
+        self.action.unwrap_or(match self.rule_type {
+            RuleType::Include => RuleAction::Full,
+            RuleType::Exclude => RuleAction::Skip,
+        })
+    }
+
+    /// `None` means the whole subtree.
+    pub fn depth_limit(&self) -> Option<u32> {
+        self.max_depth.or(if self.is_recursive { None } else { Some(1) })
+    }
+
+    /// Folder a walk has to start from to reach everything this path rule can match: the
+    /// path itself for exact rules, the literal directory prefix for glob and regex rules.
+    /// `None` when the pattern doesn't start with an absolute literal path.
+    pub fn walk_root(&self) -> Option<String> {
+        if self.category != RuleCategory::Path {
+            return None;
+        }
+        let pattern = self.pattern.trim();
+        let literal = match self.match_type {
+            MatchType::Exact => return Some(pattern.to_string()),
+            MatchType::Glob => pattern
+                .split(|c| matches!(c, '*' | '?' | '[' | '{'))
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            MatchType::Regex => regex_literal_prefix(pattern.strip_prefix('^')?),
+        };
+        let dir = &literal[..literal.rfind(['/', '\\'])? + 1];
+        let dir = match dir.trim_end_matches(['/', '\\']) {
+            // Keep the separator of a filesystem root like `/` or `C:\`
+            trimmed if trimmed.is_empty() || trimmed.ends_with(':') => dir,
+            trimmed => trimmed,
+        };
+        std::path::Path::new(dir).is_absolute().then(|| dir.to_string())
+    }
+}
+
+/// The text an anchored regex must start with. A character followed by a quantifier is
+/// optional, so it is not part of the prefix.
+fn regex_literal_prefix(pattern: &str) -> String {
+    let mut literal = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let next = match c {
+            '\\' => match chars.next() {
+                Some(escaped) if !escaped.is_ascii_alphanumeric() => escaped,
+                _ => break,
+            },
+            '?' | '*' | '{' => {
+                literal.pop();
+                break;
+            }
+            '.' | '^' | '$' | '+' | '(' | ')' | '[' | ']' | '}' | '|' => break,
+            c => c,
+        };
+        literal.push(next);
+    }
+    literal
+}
+
+/// Fields of a rule the user creates or edits.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ScanRuleInput {
+    pub category: RuleCategory,
+    pub pattern: String,
+    pub match_type: MatchType,
+    pub rule_type: RuleType,
+    pub action: Option<RuleAction>,
+    pub priority: Option<i64>,
+    pub is_recursive: Option<bool>,
+    pub max_depth: Option<u32>,
+}
+
+/// Every rule of every category, in evaluation order.
+pub fn load_scan_rules_sync(db: &Connection) -> Result<Vec<ScanRule>, Box<dyn Error>> {
+    let mut rules = Vec::new();
+    for category in RuleCategory::ALL {
+        rules.extend(load_category_rules(db, category, "", [])?);
+    }
+    Ok(rules)
 }
 
-// Generic function to get rules
+/// Rules of one category, optionally narrowed by a `WHERE` clause.
+fn load_category_rules(
+    db: &Connection,
+    category: RuleCategory,
+    where_clause: &str,
+    params: impl rusqlite::Params,
+) -> rusqlite::Result<Vec<ScanRule>> {
+    let recursive_column = if category.has_recursion() { "is_recursive" } else { "1" };
+    let query = format!(
+        "SELECT id, {}, rule_type, match_type, action, priority, {}, max_depth
+         FROM {} {} ORDER BY priority DESC, id",
+        category.column_name(),
+        recursive_column,
+        category.table_name(),
+        where_clause
+    );
+    let mut stmt = db.prepare(&query)?;
+    let rows = stmt.query_map(params, |row| {
+        let action: Option<String> = row.get(4)?;
+        Ok(ScanRule {
+            id: row.get(0)?,
+            category,
+            pattern: row.get(1)?,
+            rule_type: RuleType::parse(&row.get::<_, String>(2)?),
+            match_type: MatchType::parse(&row.get::<_, String>(3)?),
+            action: action.as_deref().and_then(RuleAction::parse),
+            priority: row.get(5)?,
+            is_recursive: row.get(6)?,
+            max_depth: row.get(7)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn get_scan_rule_sync(db: &Connection, category: RuleCategory, id: i64) -> Result<ScanRule, String> {
+    load_category_rules(db, category, "WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?
+        .pop()
+        .ok_or_else(|| format!("Rule {} not found", id))
+}
+
+// Generic function to get rules (patterns of every match type)
 pub fn get_rules_sync(
     db: &Connection,
     category: RuleCategory,
@@ -65,13 +282,46 @@ pub fn get_rules_sync(
     let rules = stmt.query_map([rule_type.as_str()], |row| row.get(0))?
         .collect::<Result<Vec<String>, _>>()?;
     
-    println!("{:?} {:?}: {:?}", rule_type, category, rules);
     Ok(rules.into_iter().collect())
 }
 
+/// Paths of the plain (exact) path rules of `rule_type`, as the user entered them.
+fn get_exact_path_rules_sync(
+    db: &Connection,
+    rule_type: RuleType,
+) -> Result<HashSet<String>, Box<dyn Error>> {
+    let mut stmt =
+        db.prepare("SELECT path FROM path_rules WHERE rule_type = ?1 AND match_type = 'exact'")?;
+    let paths = stmt
+        .query_map([rule_type.as_str()], |row| row.get(0))?
+        .collect::<Result<HashSet<String>, _>>()?;
+    Ok(paths)
+}
+
+/// Folders to start include walks from: exact include paths, plus the literal prefix of glob
+/// and regex includes. Roots nested in another root are left out, since walking the outer
+/// one already reaches them. Includes without a literal prefix only apply during Phase 2.
+pub fn get_include_walk_roots_sync(db: &Connection) -> Result<HashSet<String>, Box<dyn Error>> {
+    let includes = load_category_rules(db, RuleCategory::Path, "WHERE rule_type = 'include'", [])?;
+    let roots: Vec<String> = includes
+        .iter()
+        .filter(|rule| rule.effective_action() != RuleAction::Skip)
+        .filter_map(ScanRule::walk_root)
+        .collect();
+    Ok(roots
+        .iter()
+        .filter(|root| {
+            !roots
+                .iter()
+                .any(|other| other != *root && std::path::Path::new(root).starts_with(other))
+        })
+        .cloned()
+        .collect())
+}
+
 // Simplified public functions using the generic function
 pub fn get_included_paths_sync(db: &Connection) -> Result<HashSet<String>, Box<dyn Error>> {
-    get_rules_sync(db, RuleCategory::Path, RuleType::Include)
+    get_exact_path_rules_sync(db, RuleType::Include)
 }
 
 pub fn get_included_folders_sync(db: &Connection) -> Result<HashSet<String>, Box<dyn Error>> {
@@ -95,7 +345,43 @@ pub fn get_excluded_folder_sync(db: &Connection) -> Result<HashSet<String>, Box<
 }
 
 pub fn get_excluded_paths_sync(db: &Connection) -> Result<HashSet<String>, Box<dyn Error>> {
-    get_rules_sync(db, RuleCategory::Path, RuleType::Exclude)
+    get_exact_path_rules_sync(db, RuleType::Exclude)
+}
+
+/// Makes the plain (exact, recursive) path rules of `rule_type` equal to `paths`.
+/// Glob and regex path rules are left alone.
+pub fn replace_exact_path_rules_sync(
+    db: &Connection,
+    rule_type: RuleType,
+    paths: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let wanted: HashSet<&str> = paths.iter().map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+    let existing: HashSet<String> = load_scan_rules_sync(db)?
+        .into_iter()
+        .filter(|r| {
+            r.category == RuleCategory::Path
+                && r.rule_type == rule_type
+                && r.match_type == MatchType::Exact
+        })
+        .map(|r| r.pattern)
+        .collect();
+
+    let tx = db.unchecked_transaction()?;
+    let now = Utc::now().to_rfc3339();
+    for path in existing.iter().filter(|p| !wanted.contains(p.as_str())) {
+        tx.execute(
+            "DELETE FROM path_rules WHERE path = ?1 AND rule_type = ?2 AND match_type = 'exact'",
+            params![path, rule_type.as_str()],
+        )?;
+    }
+    for path in wanted.iter().filter(|p| !existing.contains(**p)) {
+        tx.execute(
+            "INSERT INTO path_rules (path, rule_type, is_recursive, created_at) VALUES (?1, ?2, true, ?3)",
+            params![path, rule_type.as_str(), now],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
 }
 
 // Generic function for adding rules
@@ -228,6 +514,122 @@ pub fn get_setting_sync(db: &Connection, key: &str) -> Result<Option<String>, Bo
     }
 }
 
+pub fn set_setting_sync(db: &Connection, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let now = Utc::now().to_rfc3339();
+    db.execute(
+        "INSERT INTO settings (key, value, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![key, value, now],
+    )?;
+    Ok(())
+}
+
+/// Rejects patterns that wouldn't compile, so a bad rule never silently matches nothing.
+fn validate_rule_input(input: &ScanRuleInput) -> Result<(), String> {
+    if input.pattern.trim().is_empty() {
+        return Err("Rule pattern cannot be empty".to_string());
+    }
+    crate::file_scanner::rule_engine::validate_pattern(input.match_type, input.category, &input.pattern)
+}
+
+#[tauri::command]
+pub async fn list_scan_rules() -> Result<Vec<ScanRule>, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        load_scan_rules_sync(&db).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn add_scan_rule(rule: ScanRuleInput) -> Result<ScanRule, String> {
+    validate_rule_input(&rule)?;
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        let now = Utc::now().to_rfc3339();
+        let category = rule.category;
+        let columns = if category.has_recursion() { ", is_recursive" } else { "" };
+        let values = if category.has_recursion() { ", ?8" } else { "" };
+        let query = format!(
+            "INSERT INTO {} ({}, rule_type, match_type, action, priority, max_depth, created_at{})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7{})",
+            category.table_name(),
+            category.column_name(),
+            columns,
+            values
+        );
+        let mut values: Vec<rusqlite::types::Value> = vec![
+            rule.pattern.trim().to_string().into(),
+            rule.rule_type.as_str().to_string().into(),
+            rule.match_type.as_str().to_string().into(),
+            rule.action.map(|a| a.as_str().to_string()).into(),
+            rule.priority.unwrap_or(0).into(),
+            rule.max_depth.map(i64::from).into(),
+            now.into(),
+        ];
+        if category.has_recursion() {
+            values.push(rule.is_recursive.unwrap_or(true).into());
+        }
+        db.execute(&query, rusqlite::params_from_iter(values))
+            .map_err(|e| format!("Database error: {}", e))?;
+        get_scan_rule_sync(&db, category, db.last_insert_rowid())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn update_scan_rule(id: i64, rule: ScanRuleInput) -> Result<ScanRule, String> {
+    validate_rule_input(&rule)?;
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        let category = rule.category;
+        let recursion = if category.has_recursion() { ", is_recursive = ?7" } else { "" };
+        let query = format!(
+            "UPDATE {} SET {} = ?1, rule_type = ?2, match_type = ?3, action = ?4, priority = ?5,
+                           max_depth = ?6{}
+             WHERE id = ?8",
+            category.table_name(),
+            category.column_name(),
+            recursion
+        );
+        // ?7 is simply unused for categories without is_recursive
+        let values: Vec<rusqlite::types::Value> = vec![
+            rule.pattern.trim().to_string().into(),
+            rule.rule_type.as_str().to_string().into(),
+            rule.match_type.as_str().to_string().into(),
+            rule.action.map(|a| a.as_str().to_string()).into(),
+            rule.priority.unwrap_or(0).into(),
+            rule.max_depth.map(i64::from).into(),
+            rule.is_recursive.unwrap_or(true).into(),
+            id.into(),
+        ];
+        let updated = db
+            .execute(&query, rusqlite::params_from_iter(values))
+            .map_err(|e| format!("Database error: {}", e))?;
+        if updated == 0 {
+            return Err(format!("Rule {} not found", id));
+        }
+        get_scan_rule_sync(&db, category, id)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn delete_scan_rule(category: RuleCategory, id: i64) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        let query = format!("DELETE FROM {} WHERE id = ?1", category.table_name());
+        db.execute(&query, params![id])
+            .map_err(|e| format!("Database error: {}", e))?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
 #[tauri::command]
 pub async fn add_excluded_filename(filename: String) -> Result<(), String> {
     add_rule(RuleCategory::Filename, RuleType::Exclude, filename).await
@@ -238,3 +640,45 @@ pub async fn remove_excluded_filename(filename: String) -> Result<(), String> {
     remove_rule(RuleCategory::Filename, RuleType::Exclude, filename).await
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_rule(match_type: MatchType, pattern: &str) -> ScanRule {
+        ScanRule {
+            id: 1,
+            category: RuleCategory::Path,
+            pattern: pattern.to_string(),
+            match_type,
+            rule_type: RuleType::Include,
+            action: None,
+            priority: 0,
+            is_recursive: true,
+            max_depth: None,
+        }
+    }
+
+    #[test]
+    fn walk_root_is_the_literal_directory_prefix() {
+        let cases = [
+            (MatchType::Exact, "/home/me/docs", Some("/home/me/docs")),
+            (MatchType::Glob, "/home/me/**/*.pdf", Some("/home/me")),
+            (MatchType::Glob, "/home/me/proj*/src", Some("/home/me")),
+            (MatchType::Glob, "/*.txt", Some("/")),
+            (MatchType::Glob, "**/*.pdf", None),
+            (MatchType::Regex, r"^/srv/data/.*\.csv$", Some("/srv/data")),
+            (MatchType::Regex, r"^/srv/data\.old/x", Some("/srv/data.old")),
+            (MatchType::Regex, "^/srv/datas?/x", Some("/srv")),
+            (MatchType::Regex, "/srv/data/.*", None),
+        ];
+        for (match_type, pattern, expected) in cases {
+            assert_eq!(
+                path_rule(match_type, pattern).walk_root().as_deref(),
+                expected,
+                "{}",
+                pattern
+            );
+        }
+    }
+}
@@ -27,22 +27,17 @@ CREATE TABLE IF NOT EXISTS files (
 
 ";
 
-pub const CREATE_FILE_VEC_TABLE: &str = r#"
-CREATE VIRTUAL TABLE IF NOT EXISTS file_vec USING vec0(
-    content_vec float[768]
-);
-"#;
-
-pub const CREATE_FILE_VEC_MAP_TABLE: &str = r#"
-CREATE TABLE IF NOT EXISTS file_vec_map (
+// One row per embedded chunk of a file. Its id is the LanceDB `file_embeddings.id`,
+// just as `files.id` is the LanceDB `files.id`.
+pub const CREATE_FILE_CHUNKS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS file_chunks (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
-    vec_rowid INTEGER NOT NULL,
-    file_id INTEGER NOT NULL,
-    FOREIGN KEY(file_id) REFERENCES files(id)
+    file_id INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    chunk_index INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE(file_id, chunk_index)
 );
-"#;
-
-
+";
 
 pub const CREATE_PATH_RULES_TABLE: &str = "
 CREATE TABLE IF NOT EXISTS path_rules (
@@ -117,20 +112,124 @@ CREATE TABLE IF NOT EXISTS folders (
 );
 ";
 
+pub const CREATE_FILE_INTERACTIONS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS file_interactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL,
+    action TEXT NOT NULL, -- 'open', 'open_with' or 'reveal'
+    query TEXT,           -- Normalized query the result was picked from, if any
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_file_interactions_path ON file_interactions(path, created_at);
+";
+
+pub const CREATE_SEARCH_HISTORY_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS search_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    query TEXT NOT NULL,
+    filters TEXT,         -- SearchFilters as JSON
+    result_count INTEGER NOT NULL DEFAULT 0,
+    chosen_path TEXT,     -- Result the user opened or revealed, if any
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_search_history_query ON search_history(query);
+";
+
+pub const CREATE_SAVED_SEARCHES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    query TEXT NOT NULL,
+    filters TEXT,         -- Pinned SearchFilters as JSON
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+";
+
+pub const CREATE_VOLUMES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS volumes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    volume_key TEXT NOT NULL UNIQUE, -- Stable identity: filesystem UUID, share address or label
+    label TEXT,
+    mount_point TEXT NOT NULL,       -- Where the volume was last mounted
+    file_system TEXT NOT NULL,
+    is_removable BOOLEAN NOT NULL DEFAULT 0,
+    is_network BOOLEAN NOT NULL DEFAULT 0,
+    policy TEXT NOT NULL DEFAULT 'full', -- 'never', 'metadata' or 'full'
+    is_online BOOLEAN NOT NULL DEFAULT 1,
+    last_seen TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+";
+
+// Which LanceDB indexes exist, so they are built only once their table holds real data
+pub const CREATE_LANCEDB_INDEXES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS lancedb_indexes (
+    table_name TEXT NOT NULL,
+    column_name TEXT NOT NULL,
+    index_type TEXT NOT NULL,        -- 'fts' or 'vector'
+    status TEXT NOT NULL,            -- 'needed' or 'built'
+    indexed_rows INTEGER,            -- Table rows when the index was last built
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (table_name, column_name)
+);
+";
+
+// Writes that span SQLite and LanceDB; see database::journal
+pub const CREATE_WRITE_JOURNAL_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS write_journal (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation TEXT NOT NULL,
+    payload TEXT NOT NULL,           -- JournalOp as JSON
+    status TEXT NOT NULL,            -- 'pending', 'complete' or 'rolled_back'
+    created_at TEXT NOT NULL,
+    finished_at TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_write_journal_status ON write_journal(status);
+";
+
+/// Adds a column to an existing table if it is missing. `CREATE TABLE IF NOT EXISTS`
+/// never alters tables created by older versions, so new columns go through here.
+pub fn ensure_column(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name.eq_ignore_ascii_case(column));
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+        println!("Added column {}.{}", table, column);
+    }
+    Ok(())
+}
+
 pub fn create_all_sql() -> String {
     format!(
-        "{}{}{}{}{}{}{}{}{}{}{}",
+        "{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
         CREATE_USERS_TABLE,
         CREATE_FILES_TABLE,
-        CREATE_FILE_VEC_TABLE,
-        CREATE_FILE_VEC_MAP_TABLE,
         CREATE_FILES_FTS_TABLE,
+        CREATE_FILE_CHUNKS_TABLE,
         CREATE_FOLDERS_TABLE,
         
         CREATE_PATH_RULES_TABLE,
         CREATE_FOLDER_RULES_TABLE,
         CREATE_EXTENSION_RULES_TABLE,
         CREATE_FILENAME_RULES_TABLE,
-        CREATE_SETTINGS_TABLE
+        CREATE_SETTINGS_TABLE,
+        CREATE_FILE_INTERACTIONS_TABLE,
+        CREATE_SEARCH_HISTORY_TABLE,
+        CREATE_SAVED_SEARCHES_TABLE,
+        CREATE_VOLUMES_TABLE,
+        CREATE_LANCEDB_INDEXES_TABLE,
+        CREATE_WRITE_JOURNAL_TABLE
     )
 }
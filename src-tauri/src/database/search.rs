@@ -1,7 +1,9 @@
-use crate::database;
-use crate::embed_and_store;
-use bytemuck::cast_slice;
-use rusqlite::{params, Connection, Error, Result, Row};
+use crate::database::facets::{parse_filter_date, SearchFacets};
+use crate::database::query_compiler;
+use crate::database::temporal::{self, DateField};
+use chrono::Local;
+use rusqlite::types::Value;
+use rusqlite::{Connection, Error, Result, Row};
 use std::collections::HashMap;
 
 // --- DATA STRUCTURES ---
@@ -15,6 +17,8 @@ pub struct SearchResult {
     pub relevance_score: f32,
     pub match_type: SearchMatchType,
     pub snippet: Option<String>,
+    /// False when the file is on a volume that is currently disconnected
+    pub available: bool,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -29,78 +33,55 @@ pub struct SearchFilters {
     pub extensions: Option<Vec<String>>,
     pub date_from: Option<String>,
     pub date_to: Option<String>,
+    pub date_field: Option<DateField>, // Which timestamp date_from/date_to apply to (default: modified)
     pub min_size: Option<usize>,
     pub max_size: Option<usize>,
+    // Facet selections from a previous response (see `database::facets`)
+    pub categories: Option<Vec<String>>,
+    pub folders: Option<Vec<String>>,
+    pub modified_buckets: Option<Vec<String>>,
+    pub size_buckets: Option<Vec<String>>,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum SearchIntent {
-    KeywordBased,
-    NaturalLanguage,
-    // Simplified for now, can be expanded later
+impl SearchFilters {
+    /// The date filter as a SQL predicate over a `files` or `folders` row, which share the
+    /// timestamp columns. `qualifier` prefixes the columns, e.g. "f.".
+    pub fn sqlite_date_filter(&self, qualifier: &str) -> Option<(String, Vec<Value>)> {
+        if self.date_from.is_none() && self.date_to.is_none() {
+            return None;
+        }
+        let column = match self.date_field.unwrap_or(DateField::Modified) {
+            DateField::Created => "created_at",
+            DateField::Modified => "updated_at",
+            DateField::Accessed => "last_accessed",
+        };
+        // Timestamps are stored as RFC 3339 with varying precision, so compare as julian days
+        let mut clauses = vec![format!("{}{} IS NOT NULL", qualifier, column)];
+        let mut params = Vec::new();
+        for (value, end_of_day, op) in [(&self.date_from, false, ">="), (&self.date_to, true, "<=")] {
+            if let Some(bound) = value.as_deref().and_then(|d| parse_filter_date(d, end_of_day)) {
+                clauses.push(format!("julianday({}{}) {} julianday(?)", qualifier, column, op));
+                params.push(Value::Text(bound.to_rfc3339()));
+            }
+        }
+        Some((clauses.join(" AND "), params))
+    }
 }
 
-// --- MAIN SEARCH ORCHESTRATION ---
-
-pub fn perform_file_search(
-    query: String,
-    top_k: Option<usize>,
-    filters: Option<SearchFilters>,
-) -> Result<Vec<SearchResult>, String> {
-    println!("DEBUG: perform_file_search called with query: '{}'", query);
-    
-    let limit = top_k.unwrap_or(10);
-    let (search_term, parsed_filters) = parse_query(&query);
-    let final_filters = filters.unwrap_or(parsed_filters);
-
-    println!("DEBUG: Getting embedding for query");
-    let query_embedding = embed_and_store::get_embedding(&search_term)
-        .map_err(|e| format!("Embedding error: {}", e))?;
-    let normalized_embedding = embed_and_store::normalize(query_embedding);
-
-    println!("DEBUG: Getting database connection");
-    let db = database::get_connection();
-    
-    println!("DEBUG: Starting hybrid search");
-    let results = perform_hybrid_search(&db, &normalized_embedding, &search_term, final_filters, limit)?;
-    
-    println!("DEBUG: Search completed with {} results", results.len());
-    Ok(results)
+/// A page of search results plus facet counts over every keyword match and the candidate pool.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    pub facets: SearchFacets,
+    pub total_candidates: usize,
+    pub next_cursor: Option<String>, // Pass back to `search_files` to fetch the next page
 }
 
-// --- HYBRID SEARCH LOGIC ---
-
-pub fn perform_hybrid_search(
-    db: &Connection,
-    normalized_embedding: &[f32],
-    query: &str,
-    filters: SearchFilters,
-    limit: usize,
-) -> Result<Vec<SearchResult>, String> {
-    let intent = classify_intent(query);
-    println!("DEBUG: Classified intent: {:?}", intent);
-
-    // --- Stage 2: Parallel Search Execution ---
-    // (Executed sequentially here, but conceptually parallel)
-    let vector_results = search_similar_files(db, normalized_embedding, limit * 2)?;
-    let fts_results = search_files_fts(db, query, limit * 2)?;
-    let folder_results = search_folders_by_name(db, query, limit)?;
-    let metadata_results = advanced_search(db, Some(query.to_string()), filters, limit)?;
-
-    // --- Stage 3 & 4: Combine, Rank, and Finalize ---
-    let mut combined_results = combine_and_rank_results(
-        intent,
-        vector_results,
-        fts_results,
-        folder_results,
-        metadata_results,
-    );
-
-    combined_results.sort_by(|a, b| b.relevance_score.partial_cmp(&a.relevance_score).unwrap_or(std::cmp::Ordering::Equal));
-    combined_results.truncate(limit);
-
-    println!("DEBUG: Final hybrid search returned {} results", combined_results.len());
-    Ok(combined_results)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchIntent {
+    KeywordBased,
+    NaturalLanguage,
+    // Simplified for now, can be expanded later
 }
 
 // --- STAGE 1: QUERY ANALYSIS ---
@@ -115,68 +96,96 @@ pub fn classify_intent(query: &str) -> SearchIntent {
 }
 
 pub fn parse_query(query: &str) -> (String, SearchFilters) {
-    // Basic parsing, can be expanded
     let mut filters = SearchFilters::default();
-    let search_term = query.to_string();
-    // Placeholder for more advanced parsing logic if needed
+    let mut search_term = query.trim().to_string();
+
+    // Temporal expressions become date filters and are removed from the semantic text
+    if let Some(temporal) = temporal::extract_temporal_filter(query, Local::now().date_naive()) {
+        filters.date_from = temporal.date_from.map(|d| d.format("%Y-%m-%d").to_string());
+        filters.date_to = temporal.date_to.map(|d| d.format("%Y-%m-%d").to_string());
+        filters.date_field = Some(temporal.field);
+        // A query that is only a date ("yesterday") still needs some text for the prongs
+        if !temporal.remaining_query.trim().is_empty() {
+            search_term = temporal.remaining_query;
+        }
+    }
+
     (search_term, filters)
 }
 
+/// Combines filters passed by the caller with the ones parsed from the query text.
+/// Explicit filters win; parsed dates only fill in what the caller left empty.
+pub fn merge_filters(explicit: Option<SearchFilters>, parsed: SearchFilters) -> SearchFilters {
+    let Some(mut filters) = explicit else {
+        return parsed;
+    };
+    if filters.date_from.is_none() && filters.date_to.is_none() {
+        filters.date_from = parsed.date_from;
+        filters.date_to = parsed.date_to;
+        filters.date_field = filters.date_field.or(parsed.date_field);
+    }
+    filters
+}
+
 // --- STAGE 2: SEARCH PRONGS ---
 
-pub fn search_similar_files(
+pub fn search_files_fts(
     db: &Connection,
-    normalized_query: &[f32],
+    query: &str,
+    filters: &SearchFilters,
     limit: usize,
 ) -> Result<Vec<SearchResult>, String> {
-    println!("DEBUG: Starting vector search");
-    let vector_bytes: &[u8] = cast_slice(normalized_query);
-
-    let sql = r#"
-        SELECT f.id, f.name, f.path, f.score, distance
-        FROM file_vec fv
-        JOIN file_vec_map fvm ON fv.rowid = fvm.vec_rowid
-        JOIN files f ON fvm.file_id = f.id
-        WHERE fv.content_vec MATCH ?1 AND k = ?2
-        ORDER BY distance
-    "#;
-
-    let mut stmt = db.prepare(sql).map_err(|e| e.to_string())?;
-    let rows = stmt.query_map(params![vector_bytes, limit], |row| {
-        let id: i64 = row.get(0)?;
-        let distance: f32 = row.get(4)?;
-        let relevance = 1.0 - distance;
-        Ok(SearchResult {
-            id: format!("file-{}", id),
-            result_type: "file".to_string(),
-            title: row.get(1)?,
-            path: row.get(2)?,
-            relevance_score: relevance,
-            match_type: SearchMatchType::Vector(relevance),
-            snippet: None,
-        })
-    }).map_err(|e| e.to_string())?;
+    println!("DEBUG: Starting FTS search for: {}", query);
+    let compiled = query_compiler::compile(query);
+    let Some(expression) = compiled.to_fts5() else {
+        // Nothing positive to match (empty or exclusion-only query)
+        return Ok(Vec::new());
+    };
 
-    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    // A bad FTS expression must never fail the whole search: retry loosely, then give up on this prong
+    let date_filter = filters.sqlite_date_filter("f.");
+    match run_fts_query(db, &expression, date_filter.as_ref(), limit) {
+        Ok(results) => Ok(results),
+        Err(e) => {
+            eprintln!("FTS query '{}' failed: {}", expression, e);
+            let fallback = compiled.to_fts5_fallback().and_then(|fallback| {
+                run_fts_query(db, &fallback, date_filter.as_ref(), limit).ok()
+            });
+            Ok(fallback.unwrap_or_default())
+        }
+    }
 }
 
-pub fn search_files_fts(
+fn run_fts_query(
     db: &Connection,
-    query: &str,
+    expression: &str,
+    date_filter: Option<&(String, Vec<Value>)>,
     limit: usize,
 ) -> Result<Vec<SearchResult>, String> {
-    println!("DEBUG: Starting FTS search for: {}", query);
-    let sql = r#"
+    let mut params_values: Vec<Value> = vec![expression.to_string().into()];
+    let date_clause = match date_filter {
+        Some((clause, date_params)) => {
+            params_values.extend(date_params.iter().cloned());
+            format!("AND {}", clause)
+        }
+        None => String::new(),
+    };
+    params_values.push((limit as i64).into());
+    let sql = format!(
+        r#"
         SELECT f.id, f.name, f.path, f.score, rank
         FROM files_fts
         JOIN files f ON files_fts.rowid = f.id
-        WHERE files_fts MATCH ?1
+        WHERE files_fts MATCH ? {}
         ORDER BY rank
-        LIMIT ?2
-    "#;
+        LIMIT ?
+    "#,
+        date_clause
+    );
 
-    let mut stmt = db.prepare(sql).map_err(|e| e.to_string())?;
-    let rows = stmt.query_map(params![query, limit], |row| {
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_values.iter().map(|v| v as _).collect();
+    let mut stmt = db.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(&*params_refs, |row| {
         let id: i64 = row.get(0)?;
         let rank: f64 = row.get(4)?;
         Ok(SearchResult {
@@ -187,6 +196,7 @@ pub fn search_files_fts(
             relevance_score: rank as f32,
             match_type: SearchMatchType::Text(rank as f32),
             snippet: None,
+            available: true,
         })
     }).map_err(|e| e.to_string())?;
 
@@ -196,20 +206,28 @@ pub fn search_files_fts(
 pub fn search_folders_by_name(
     db: &Connection,
     query: &str,
+    filters: &SearchFilters,
     limit: usize,
 ) -> Result<Vec<SearchResult>, String> {
     println!("DEBUG: Starting folder name search for: {}", query);
-    let sql = r#"
-        SELECT id, name, path, score
-        FROM folders
-        WHERE name LIKE ?1 OR path LIKE ?1
-        ORDER BY score DESC
-        LIMIT ?2
-    "#;
-
-    let mut stmt = db.prepare(sql).map_err(|e| e.to_string())?;
-    let query_param = format!("%{}%", query);
-    let rows = stmt.query_map(params![query_param, limit], |row| {
+    // The path contains the name, so matching on it covers both; terms match anywhere in it
+    let Some((name_filter, name_params)) =
+        query_compiler::compile(query).to_sqlite_name_filter("path")
+    else {
+        return Ok(Vec::new());
+    };
+    let mut sql = format!("SELECT id, name, path, score FROM folders WHERE {}", name_filter);
+    let mut params_values: Vec<Value> = name_params.into_iter().map(Into::into).collect();
+    if let Some((date_clause, date_params)) = filters.sqlite_date_filter("") {
+        sql.push_str(&format!(" AND {}", date_clause));
+        params_values.extend(date_params);
+    }
+    sql.push_str(" ORDER BY score DESC LIMIT ?");
+    params_values.push((limit as i64).into());
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_values.iter().map(|v| v as _).collect();
+    let mut stmt = db.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(&*params_refs, |row| {
         let id: i64 = row.get(0)?;
         let score: f32 = row.get(3)?;
         Ok(SearchResult {
@@ -220,6 +238,7 @@ pub fn search_folders_by_name(
             relevance_score: score,
             match_type: SearchMatchType::Text(score),
             snippet: None,
+            available: true,
         })
     }).map_err(|e| e.to_string())?;
 
@@ -234,13 +253,41 @@ pub fn advanced_search(
 ) -> Result<Vec<SearchResult>, String> {
     println!("DEBUG: Starting advanced search");
     let mut sql = String::from("SELECT id, name, path, score FROM files WHERE 1 = 1");
-    let mut params_values: Vec<rusqlite::types::Value> = Vec::new();
+    let mut params_values: Vec<Value> = Vec::new();
 
     if let Some(query) = name_query {
-        sql.push_str(" AND name LIKE ?");
-        params_values.push(format!("%{}%", query).into());
+        let Some((name_filter, name_params)) =
+            query_compiler::compile(&query).to_sqlite_name_filter("name")
+        else {
+            // No term applies to file names, so this prong has nothing to match
+            return Ok(Vec::new());
+        };
+        sql.push_str(&format!(" AND {}", name_filter));
+        params_values.extend(name_params.into_iter().map(Into::into));
+    }
+    if let Some(extensions) = filters.extensions.as_ref().filter(|e| !e.is_empty()) {
+        sql.push_str(&format!(
+            " AND LOWER(extension) IN ({})",
+            vec!["?"; extensions.len()].join(", ")
+        ));
+        params_values.extend(
+            extensions
+                .iter()
+                .map(|e| Value::Text(e.trim_start_matches('.').to_lowercase())),
+        );
+    }
+    if let Some((date_clause, date_params)) = filters.sqlite_date_filter("") {
+        sql.push_str(&format!(" AND {}", date_clause));
+        params_values.extend(date_params);
+    }
+    if let Some(min_size) = filters.min_size {
+        sql.push_str(" AND file_size >= ?");
+        params_values.push((min_size as i64).into());
+    }
+    if let Some(max_size) = filters.max_size {
+        sql.push_str(" AND file_size <= ?");
+        params_values.push((max_size as i64).into());
     }
-    // Add other filters (extensions, dates) here...
 
     sql.push_str(" ORDER BY score DESC LIMIT ?");
     params_values.push((limit as i64).into());
@@ -257,6 +304,7 @@ pub fn advanced_search(
             relevance_score: row.get(3)?,
             match_type: SearchMatchType::Text(row.get(3)?),
             snippet: None,
+            available: true,
         })
     }).map_err(|e| e.to_string())?;
 
@@ -271,14 +319,15 @@ pub fn combine_and_rank_results(
     vector_results: Vec<SearchResult>,
     fts_results: Vec<SearchResult>,
     folder_results: Vec<SearchResult>,
+    folder_vector_results: Vec<SearchResult>,
     metadata_results: Vec<SearchResult>,
 ) -> Vec<SearchResult> {
     let mut combined: HashMap<String, (f32, SearchResult)> = HashMap::new();
 
     // Define weights based on intent
-    let (w_sem, w_key, w_folder, w_meta) = match intent {
-        SearchIntent::NaturalLanguage => (0.6, 0.2, 0.3, 0.1),
-        SearchIntent::KeywordBased => (0.2, 0.6, 0.4, 0.2),
+    let (w_sem, w_key, w_folder, w_folder_sem, w_meta) = match intent {
+        SearchIntent::NaturalLanguage => (0.6, 0.2, 0.3, 0.5, 0.1),
+        SearchIntent::KeywordBased => (0.2, 0.6, 0.4, 0.2, 0.2),
     };
 
     let hybrid_boost = 1.5;
@@ -319,6 +368,16 @@ pub fn combine_and_rank_results(
         }
     }
 
+    // Process and merge semantic folder results
+    for r in folder_vector_results {
+        let score = r.relevance_score * w_folder_sem;
+        if let Some(existing) = combined.get_mut(&r.id) {
+            existing.0 += score * hybrid_boost;
+        } else {
+            combined.insert(r.id.clone(), (score, r));
+        }
+    }
+
     // Finalize scores and collect results
     combined.into_values().map(|(score, mut result)| {
         result.relevance_score = score;
@@ -0,0 +1,100 @@
+// Cached, fully ranked candidate lists that back cursor-based pagination
+use crate::database::facets::SearchFacets;
+use crate::database::search::{SearchResponse, SearchResult};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const SESSION_TTL: Duration = Duration::from_secs(10 * 60);
+const MAX_SESSIONS: usize = 32;
+
+struct SearchSession {
+    results: Vec<SearchResult>,
+    facets: SearchFacets,
+    created_at: Instant,
+}
+
+static SEARCH_SESSIONS: Lazy<Mutex<HashMap<String, SearchSession>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Stores a ranked candidate list and returns its first page.
+/// Later pages are served from the stored list, so page boundaries never shift.
+pub fn start_session(
+    results: Vec<SearchResult>,
+    facets: SearchFacets,
+    limit: usize,
+) -> SearchResponse {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let first_page = build_page(&session_id, &results, &facets, 0, limit);
+
+    let mut sessions = SEARCH_SESSIONS.lock().expect("Failed to lock search sessions");
+    sessions.retain(|_, s| s.created_at.elapsed() < SESSION_TTL);
+    if sessions.len() >= MAX_SESSIONS {
+        if let Some(oldest) = sessions
+            .iter()
+            .min_by_key(|(_, s)| s.created_at)
+            .map(|(id, _)| id.clone())
+        {
+            sessions.remove(&oldest);
+        }
+    }
+    sessions.insert(
+        session_id,
+        SearchSession {
+            results,
+            facets,
+            created_at: Instant::now(),
+        },
+    );
+
+    first_page
+}
+
+/// Returns the page that starts at the position encoded in `cursor`.
+pub fn fetch_page(cursor: &str, limit: usize) -> Result<SearchResponse, String> {
+    let (session_id, offset) = decode_cursor(cursor)?;
+
+    let sessions = SEARCH_SESSIONS.lock().expect("Failed to lock search sessions");
+    let session = sessions
+        .get(session_id)
+        .filter(|s| s.created_at.elapsed() < SESSION_TTL)
+        .ok_or_else(|| "Search cursor has expired, please run the search again".to_string())?;
+
+    Ok(build_page(session_id, &session.results, &session.facets, offset, limit))
+}
+
+fn build_page(
+    session_id: &str,
+    results: &[SearchResult],
+    facets: &SearchFacets,
+    offset: usize,
+    limit: usize,
+) -> SearchResponse {
+    // Both come from the client, so a cursor or page size past the end must not overflow
+    let offset = offset.min(results.len());
+    let end = offset.saturating_add(limit).min(results.len());
+    let page = results[offset..end].to_vec();
+    let next_cursor = (end < results.len()).then(|| encode_cursor(session_id, end));
+
+    SearchResponse {
+        results: page,
+        facets: facets.clone(),
+        total_candidates: results.len(),
+        next_cursor,
+    }
+}
+
+fn encode_cursor(session_id: &str, offset: usize) -> String {
+    format!("{}:{}", session_id, offset)
+}
+
+fn decode_cursor(cursor: &str) -> Result<(&str, usize), String> {
+    let (session_id, offset) = cursor
+        .rsplit_once(':')
+        .ok_or_else(|| format!("Invalid search cursor: {}", cursor))?;
+    let offset = offset
+        .parse::<usize>()
+        .map_err(|_| format!("Invalid search cursor: {}", cursor))?;
+    Ok((session_id, offset))
+}
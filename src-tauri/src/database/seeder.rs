@@ -1,7 +1,6 @@
 use chrono::Utc;
 use rusqlite::{params, Connection, Result};
 use std::env;
-use sysinfo::Disks;
 use std::path::PathBuf;
 
 pub fn seed_initial_data(conn: &Connection) -> Result<()> {
@@ -240,11 +239,10 @@ fn seed_path_rules(conn: &Connection) -> Result<()> {
 }
 
 fn get_all_drives() -> Vec<String> {
-    let disks = Disks::new_with_refreshed_list();
-    disks
-        .list()
-        .iter()
-        .map(|d| d.mount_point().to_string_lossy().to_string())
+    crate::file_scanner::volumes::discover_volumes()
+        .into_iter()
+        .filter(|v| v.selected_by_default())
+        .map(|v| v.mount_point)
         .collect()
 }
 
@@ -253,6 +251,9 @@ fn seed_settings(conn: &Connection) -> Result<()> {
         ("max_file_size_mb", "5"),
         ("max_pdf_pages", "25"),
         ("max_index_depth", "10"),
+        ("search_history_enabled", "true"),
+        ("respect_ignore_files", "true"),
+        ("index_size_budget_mb", "10240"),
     ];
 
     let now = Utc::now().to_rfc3339();
@@ -0,0 +1,143 @@
+// Typed view of the settings table: defaults, validation and change notifications
+use crate::database::history::HISTORY_ENABLED_SETTING;
+use crate::database::rules::{get_setting_sync, set_setting_sync};
+use crate::file_scanner::walker::RESPECT_IGNORE_FILES_SETTING;
+use rusqlite::Connection;
+use std::str::FromStr;
+use tauri::{AppHandle, Emitter};
+
+pub const MAX_FILE_SIZE_SETTING: &str = "max_file_size_mb";
+pub const MAX_PDF_PAGES_SETTING: &str = "max_pdf_pages";
+pub const MAX_INDEX_DEPTH_SETTING: &str = "max_index_depth";
+pub const INDEX_SIZE_BUDGET_SETTING: &str = "index_size_budget_mb";
+
+const MAX_FILE_SIZE_RANGE: (u64, u64) = (1, 2048);
+const MAX_PDF_PAGES_RANGE: (usize, usize) = (1, 5000);
+const MAX_INDEX_DEPTH_RANGE: (usize, usize) = (1, 256);
+const INDEX_SIZE_BUDGET_RANGE: (u64, u64) = (0, 1024 * 1024);
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AppSettings {
+    /// Content is read only from files up to this size; larger files keep metadata only
+    pub max_file_size_mb: u64,
+    /// Pages of a PDF that are extracted
+    pub max_pdf_pages: usize,
+    /// Folder levels below a scan root that walks descend into
+    pub max_index_depth: usize,
+    pub search_history_enabled: bool,
+    pub respect_ignore_files: bool,
+    /// Estimated index size above which the lowest-scored files lose their content; 0 = no limit
+    pub index_size_budget_mb: u64,
+}
+
+impl Default for AppSettings {
+    // Matches what `seed_settings` writes
+    fn default() -> Self {
+        AppSettings {
+            max_file_size_mb: 5,
+            max_pdf_pages: 25,
+            max_index_depth: 10,
+            search_history_enabled: true,
+            respect_ignore_files: true,
+            index_size_budget_mb: 10 * 1024,
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn max_file_size_bytes(&self) -> u64 {
+        self.max_file_size_mb * 1024 * 1024
+    }
+
+    pub fn index_size_budget_bytes(&self) -> Option<u64> {
+        (self.index_size_budget_mb > 0).then(|| self.index_size_budget_mb * 1024 * 1024)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        check_range(MAX_FILE_SIZE_SETTING, self.max_file_size_mb, MAX_FILE_SIZE_RANGE)?;
+        check_range(MAX_PDF_PAGES_SETTING, self.max_pdf_pages, MAX_PDF_PAGES_RANGE)?;
+        check_range(MAX_INDEX_DEPTH_SETTING, self.max_index_depth, MAX_INDEX_DEPTH_RANGE)?;
+        check_range(INDEX_SIZE_BUDGET_SETTING, self.index_size_budget_mb, INDEX_SIZE_BUDGET_RANGE)
+    }
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(key: &str, value: T, (min, max): (T, T)) -> Result<(), String> {
+    if value < min || value > max {
+        return Err(format!("{} must be between {} and {}", key, min, max));
+    }
+    Ok(())
+}
+
+/// A stored value, or `default` when it is missing, unparsable or out of range.
+fn read_value<T: FromStr + PartialOrd + Copy>(db: &Connection, key: &str, default: T, range: Option<(T, T)>) -> T {
+    let Some(raw) = get_setting_sync(db, key).ok().flatten() else {
+        return default;
+    };
+    match raw.trim().parse::<T>() {
+        Ok(value) if range.is_none_or(|(min, max)| value >= min && value <= max) => value,
+        _ => {
+            eprintln!("Invalid value '{}' for setting {}, using the default", raw, key);
+            default
+        }
+    }
+}
+
+pub fn load_settings(db: &Connection) -> AppSettings {
+    let defaults = AppSettings::default();
+    AppSettings {
+        max_file_size_mb: read_value(db, MAX_FILE_SIZE_SETTING, defaults.max_file_size_mb, Some(MAX_FILE_SIZE_RANGE)),
+        max_pdf_pages: read_value(db, MAX_PDF_PAGES_SETTING, defaults.max_pdf_pages, Some(MAX_PDF_PAGES_RANGE)),
+        max_index_depth: read_value(db, MAX_INDEX_DEPTH_SETTING, defaults.max_index_depth, Some(MAX_INDEX_DEPTH_RANGE)),
+        search_history_enabled: read_value(db, HISTORY_ENABLED_SETTING, defaults.search_history_enabled, None),
+        respect_ignore_files: read_value(db, RESPECT_IGNORE_FILES_SETTING, defaults.respect_ignore_files, None),
+        index_size_budget_mb: read_value(db, INDEX_SIZE_BUDGET_SETTING, defaults.index_size_budget_mb, Some(INDEX_SIZE_BUDGET_RANGE)),
+    }
+}
+
+pub fn save_settings(db: &Connection, settings: AppSettings) -> Result<AppSettings, String> {
+    settings.validate()?;
+    let values = [
+        (MAX_FILE_SIZE_SETTING, settings.max_file_size_mb.to_string()),
+        (MAX_PDF_PAGES_SETTING, settings.max_pdf_pages.to_string()),
+        (MAX_INDEX_DEPTH_SETTING, settings.max_index_depth.to_string()),
+        (HISTORY_ENABLED_SETTING, settings.search_history_enabled.to_string()),
+        (RESPECT_IGNORE_FILES_SETTING, settings.respect_ignore_files.to_string()),
+        (INDEX_SIZE_BUDGET_SETTING, settings.index_size_budget_mb.to_string()),
+    ];
+    let tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
+    for (key, value) in &values {
+        set_setting_sync(&tx, key, value).map_err(|e| format!("Database error: {}", e))?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(settings)
+}
+
+/// Emits "settings_changed" after a single setting was stored by its own command, so
+/// listeners see every change and not only those made through `update_settings`.
+pub fn notify_settings_changed(app: &AppHandle, db: &Connection) {
+    app.emit("settings_changed", load_settings(db)).ok();
+}
+
+#[tauri::command]
+pub async fn get_settings() -> Result<AppSettings, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        Ok(load_settings(&db))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+/// Validates and stores all settings, then emits "settings_changed" with the new values.
+/// Limits apply from the next scan on.
+#[tauri::command]
+pub async fn update_settings(settings: AppSettings, app: AppHandle) -> Result<AppSettings, String> {
+    let saved = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        save_settings(&db, settings)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))??;
+    app.emit("settings_changed", saved).ok();
+    Ok(saved)
+}
@@ -0,0 +1,362 @@
+// Where the index's disk space goes, and the size budget that keeps it in check. Files over
+// budget are degraded lowest score first: their content and content chunks are dropped, but
+// their metadata and metadata vectors stay, as for files indexed by the metadata-only pass.
+use crate::database::consistency::IndexedFile;
+use crate::database::journal::{self, JournalOp};
+use crate::database::lancedb_maintenance::{dir_size, MAINTAINED_TABLES};
+use crate::database::lancedb_ops::{get_app_data_dir, lancedb_path};
+use crate::database::migrations::backups_dir;
+use crate::database::settings::load_settings;
+use crate::embed_and_store::EMBEDDING_DIM;
+use crate::file_scanner::claim_scan_slot;
+use crate::file_scanner::volume_registry::mount_prefix;
+use rusqlite::{params, Connection};
+
+// Content is stored in `files`, in the full-text index and in LanceDB
+const CONTENT_COPIES: u64 = 3;
+// Row, path and FTS overhead of a file besides its content and vectors
+const FILE_OVERHEAD_BYTES: u64 = 512;
+const VECTOR_BYTES: u64 = (EMBEDDING_DIM * 4) as u64;
+
+// Candidates loaded per step while degrading
+const DEGRADE_BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StoreUsage {
+    pub store: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TableUsage {
+    pub store: String,
+    /// Table or index name
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// Files in a category or under a root, and how much extracted content they hold.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GroupUsage {
+    pub name: String,
+    pub files: u64,
+    pub content_files: u64,
+    pub content_bytes: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StorageStats {
+    /// On disk, including old LanceDB versions and backups
+    pub total_bytes: u64,
+    pub stores: Vec<StoreUsage>,
+    pub tables: Vec<TableUsage>,
+    pub categories: Vec<GroupUsage>,
+    pub roots: Vec<GroupUsage>,
+    /// What the budget is compared against
+    pub estimated_index_bytes: u64,
+    /// None when there is no budget
+    pub budget_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct BudgetReport {
+    pub budget_bytes: Option<u64>,
+    pub estimated_before_bytes: u64,
+    pub estimated_after_bytes: u64,
+    /// Files that are metadata-only now
+    pub files_degraded: Vec<IndexedFile>,
+}
+
+/// Logical size of the index. Disk usage lags behind deletes until old LanceDB versions are
+/// pruned, so the budget is checked against this instead.
+fn estimate_index_bytes(db: &Connection) -> rusqlite::Result<u64> {
+    let (files, content_bytes): (i64, i64) = db.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(length(CAST(content AS BLOB))), 0) FROM files",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let chunks: i64 = db.query_row("SELECT COUNT(*) FROM file_chunks", [], |row| row.get(0))?;
+    Ok(CONTENT_COPIES * content_bytes as u64
+        + chunks as u64 * VECTOR_BYTES
+        + files as u64 * (FILE_OVERHEAD_BYTES + VECTOR_BYTES))
+}
+
+fn file_size(path: &std::path::Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn store_usage() -> Vec<StoreUsage> {
+    let sqlite_bytes: u64 = get_app_data_dir()
+        .map(|dir| {
+            ["database.db", "database.db-wal", "database.db-shm"]
+                .iter()
+                .map(|name| file_size(&dir.join(name)))
+                .sum()
+        })
+        .unwrap_or(0);
+    vec![
+        StoreUsage {
+            store: "sqlite".to_string(),
+            bytes: sqlite_bytes,
+        },
+        StoreUsage {
+            store: "lancedb".to_string(),
+            bytes: lancedb_path().map(|p| dir_size(&p)).unwrap_or(0),
+        },
+        StoreUsage {
+            store: "backups".to_string(),
+            bytes: backups_dir().map(|p| dir_size(&p)).unwrap_or(0),
+        },
+    ]
+}
+
+fn table_usage(db: &Connection) -> rusqlite::Result<Vec<TableUsage>> {
+    let mut stmt =
+        db.prepare("SELECT name, SUM(pgsize) FROM dbstat GROUP BY name ORDER BY SUM(pgsize) DESC")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TableUsage {
+            store: "sqlite".to_string(),
+            name: row.get(0)?,
+            bytes: row.get::<_, i64>(1)? as u64,
+        })
+    })?;
+    let mut tables = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+    if let Some(dir) = lancedb_path() {
+        for name in MAINTAINED_TABLES {
+            tables.push(TableUsage {
+                store: "lancedb".to_string(),
+                name: name.to_string(),
+                bytes: dir_size(&dir.join(format!("{}.lance", name))),
+            });
+        }
+    }
+    Ok(tables)
+}
+
+fn group_usage(row: &rusqlite::Row) -> rusqlite::Result<GroupUsage> {
+    Ok(GroupUsage {
+        name: row.get(0)?,
+        files: row.get::<_, i64>(1)? as u64,
+        content_files: row.get::<_, i64>(2)? as u64,
+        content_bytes: row.get::<_, i64>(3)? as u64,
+    })
+}
+
+fn category_usage(db: &Connection) -> rusqlite::Result<Vec<GroupUsage>> {
+    let mut stmt = db.prepare(
+        "SELECT COALESCE(category, 'Unknown'), COUNT(*), COALESCE(SUM(content_processed), 0),
+                COALESCE(SUM(length(CAST(content AS BLOB))), 0) AS bytes
+         FROM files GROUP BY 1 ORDER BY bytes DESC",
+    )?;
+    let rows = stmt.query_map([], group_usage)?;
+    rows.collect()
+}
+
+fn root_usage(db: &Connection) -> Result<Vec<GroupUsage>, String> {
+    // Exact include paths only: a glob or regex include is no folder to group by
+    let roots = crate::database::rules::get_included_paths_sync(db).map_err(|e| e.to_string())?;
+    let mut usage = Vec::with_capacity(roots.len());
+    for root in roots {
+        let prefix = mount_prefix(&root);
+        // SQLite's substr counts characters, not bytes
+        let group = db
+            .query_row(
+                "SELECT ?1, COUNT(*), COALESCE(SUM(content_processed), 0),
+                        COALESCE(SUM(length(CAST(content AS BLOB))), 0)
+                 FROM files WHERE substr(path, 1, ?2) = ?3",
+                params![root, prefix.chars().count() as i64, prefix],
+                group_usage,
+            )
+            .map_err(|e| format!("Database error: {}", e))?;
+        usage.push(group);
+    }
+    usage.sort_by(|a, b| b.content_bytes.cmp(&a.content_bytes));
+    Ok(usage)
+}
+
+fn collect_storage_stats(db: &Connection) -> Result<StorageStats, String> {
+    let stores = store_usage();
+    Ok(StorageStats {
+        total_bytes: stores.iter().map(|s| s.bytes).sum(),
+        stores,
+        tables: table_usage(db).map_err(|e| format!("Database error: {}", e))?,
+        categories: category_usage(db).map_err(|e| format!("Database error: {}", e))?,
+        roots: root_usage(db)?,
+        estimated_index_bytes: estimate_index_bytes(db)
+            .map_err(|e| format!("Database error: {}", e))?,
+        budget_bytes: load_settings(db).index_size_budget_bytes(),
+    })
+}
+
+/// Disk usage by store, table, file category and scan root, with the budget it is held to.
+#[tauri::command]
+pub async fn get_storage_stats() -> Result<StorageStats, String> {
+    tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_read_connection()?;
+        collect_storage_stats(&db)
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))?
+}
+
+struct Candidate {
+    file: IndexedFile,
+    freed_bytes: u64,
+}
+
+/// Lowest-scored files that still have content, with what degrading each would save.
+fn load_candidates(db: &Connection) -> rusqlite::Result<Vec<Candidate>> {
+    let mut stmt = db.prepare(
+        "SELECT f.id, f.path, length(CAST(f.content AS BLOB)),
+                (SELECT COUNT(*) FROM file_chunks c WHERE c.file_id = f.id AND c.chunk_index > 0)
+         FROM files f WHERE f.content_processed = 1
+         ORDER BY f.score, f.id LIMIT ?1",
+    )?;
+    let rows = stmt.query_map(params![DEGRADE_BATCH_SIZE], |row| {
+        let content_bytes: i64 = row.get(2)?;
+        let content_chunks: i64 = row.get(3)?;
+        Ok(Candidate {
+            file: IndexedFile {
+                id: row.get(0)?,
+                path: row.get(1)?,
+            },
+            freed_bytes: CONTENT_COPIES * content_bytes as u64
+                + content_chunks as u64 * VECTOR_BYTES,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Drops the content of files in SQLite and journals the LanceDB half. Returns the journal
+/// entry and the ids of the dropped content chunks. Chunk 0 is the metadata chunk and stays.
+fn degrade_sqlite(db: &Connection, file_ids: &[i64]) -> rusqlite::Result<(i64, Vec<i64>)> {
+    let tx = db.unchecked_transaction()?;
+    let mut chunk_ids = Vec::new();
+    for id in file_ids {
+        let mut stmt =
+            tx.prepare("SELECT id FROM file_chunks WHERE file_id = ?1 AND chunk_index > 0")?;
+        let ids = stmt.query_map(params![id], |row| row.get::<_, i64>(0))?;
+        chunk_ids.extend(ids.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        // files_fts is an external-content table; a delete has to repeat the indexed values
+        tx.execute(
+            "INSERT INTO files_fts (files_fts, rowid, name, content)
+             SELECT 'delete', id, name, content FROM files WHERE id = ?1",
+            params![id],
+        )?;
+        tx.execute(
+            "UPDATE files SET content = '', content_processed = 0, content_hash = NULL
+             WHERE id = ?1",
+            params![id],
+        )?;
+        tx.execute(
+            "INSERT INTO files_fts (rowid, name, content) SELECT id, name, '' FROM files WHERE id = ?1",
+            params![id],
+        )?;
+        tx.execute(
+            "DELETE FROM file_chunks WHERE file_id = ?1 AND chunk_index > 0",
+            params![id],
+        )?;
+    }
+    let op = JournalOp::DegradeFiles {
+        file_ids: file_ids.to_vec(),
+        chunk_ids: chunk_ids.clone(),
+    };
+    let journal_id = journal::begin(&tx, &op)?;
+    tx.commit()?;
+    Ok((journal_id, chunk_ids))
+}
+
+async fn degrade_files(file_ids: Vec<i64>) -> Result<(), String> {
+    let ids = file_ids.clone();
+    let (journal_id, chunk_ids) = tokio::task::spawn_blocking(move || {
+        let db = crate::database::get_connection();
+        degrade_sqlite(&db, &ids).map_err(|e| format!("Database error: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Task spawn error: {}", e))??;
+
+    // SQLite goes first; if LanceDB fails the journal entry stays pending and startup
+    // recovery clears the content and chunks LanceDB still holds
+    if let Err(e) = journal::degrade_lancedb_rows(&file_ids, &chunk_ids).await {
+        return Err(format!("{} (retried at the next start)", e));
+    }
+    let db = crate::database::get_connection();
+    journal::complete(&db, journal_id).map_err(|e| format!("Database error: {}", e))
+}
+
+fn budget_state() -> Result<(Option<u64>, u64), String> {
+    let db = crate::database::get_read_connection()?;
+    let estimate = estimate_index_bytes(&db).map_err(|e| format!("Database error: {}", e))?;
+    Ok((load_settings(&db).index_size_budget_bytes(), estimate))
+}
+
+/// Degrades the lowest-scored files to metadata-only until the estimated index size fits the
+/// budget. Callers make sure no scan is writing.
+pub async fn enforce_storage_budget() -> Result<BudgetReport, String> {
+    let (budget, estimate) = tokio::task::spawn_blocking(budget_state)
+        .await
+        .map_err(|e| format!("Task spawn error: {}", e))??;
+    let mut report = BudgetReport {
+        budget_bytes: budget,
+        estimated_before_bytes: estimate,
+        estimated_after_bytes: estimate,
+        ..BudgetReport::default()
+    };
+    let Some(budget) = budget else {
+        return Ok(report);
+    };
+
+    let mut excess = estimate.saturating_sub(budget);
+    while excess > 0 {
+        let candidates = tokio::task::spawn_blocking(move || {
+            let db = crate::database::get_read_connection()?;
+            load_candidates(&db).map_err(|e| format!("Database error: {}", e))
+        })
+        .await
+        .map_err(|e| format!("Task spawn error: {}", e))??;
+        if candidates.is_empty() {
+            break;
+        }
+
+        let mut batch = Vec::new();
+        for candidate in candidates {
+            if excess == 0 {
+                break;
+            }
+            excess = excess.saturating_sub(candidate.freed_bytes);
+            batch.push(candidate.file);
+        }
+        degrade_files(batch.iter().map(|f| f.id).collect()).await?;
+        report.files_degraded.extend(batch);
+    }
+
+    if !report.files_degraded.is_empty() {
+        report.estimated_after_bytes = tokio::task::spawn_blocking(budget_state)
+            .await
+            .map_err(|e| format!("Task spawn error: {}", e))??
+            .1;
+        println!(
+            "Index over budget: degraded {} files to metadata-only ({} -> {} bytes estimated)",
+            report.files_degraded.len(),
+            report.estimated_before_bytes,
+            report.estimated_after_bytes
+        );
+    }
+    Ok(report)
+}
+
+/// Applies the size budget now, then reclaims the freed LanceDB space.
+#[tauri::command]
+pub async fn apply_storage_budget() -> Result<BudgetReport, String> {
+    let _scan_guard =
+        claim_scan_slot().ok_or("Cannot apply the size budget while a scan is running")?;
+    let report = enforce_storage_budget().await?;
+    if !report.files_degraded.is_empty() {
+        if let Err(e) = crate::database::lancedb_maintenance::run_lancedb_maintenance().await {
+            eprintln!("LanceDB maintenance failed: {}", e);
+        }
+    }
+    Ok(report)
+}
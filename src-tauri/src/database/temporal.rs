@@ -0,0 +1,620 @@
+// Natural-language date expressions in search queries ("last March", "yesterday", "between June and August")
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+
+/// Which timestamp a date filter applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateField {
+    Created,
+    Modified,
+    Accessed,
+}
+
+/// The result of pulling a temporal expression out of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemporalFilter {
+    pub date_from: Option<NaiveDate>,
+    pub date_to: Option<NaiveDate>,
+    pub field: DateField,
+    /// The query with the temporal expression (and its verb, e.g. "I edited") removed.
+    pub remaining_query: String,
+}
+
+/// An inclusive range of calendar days.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DateRange {
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+impl DateRange {
+    fn day(date: NaiveDate) -> Self {
+        Self { start: date, end: date }
+    }
+}
+
+/// How a temporal expression bounds the search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    Within(DateRange),
+    Since(NaiveDate),
+    Until(NaiveDate),
+}
+
+/// A query token, keeping the original text so unmatched tokens survive unchanged.
+struct Token<'a> {
+    original: &'a str,
+    word: String,
+}
+
+const FIELD_VERBS: &[(&str, DateField)] = &[
+    ("created", DateField::Created),
+    ("made", DateField::Created),
+    ("added", DateField::Created),
+    ("downloaded", DateField::Created),
+    ("saved", DateField::Modified),
+    ("edited", DateField::Modified),
+    ("modified", DateField::Modified),
+    ("changed", DateField::Modified),
+    ("updated", DateField::Modified),
+    ("wrote", DateField::Modified),
+    ("written", DateField::Modified),
+    ("opened", DateField::Accessed),
+    ("accessed", DateField::Accessed),
+    ("viewed", DateField::Accessed),
+    ("read", DateField::Accessed),
+    ("used", DateField::Accessed),
+];
+
+/// Words that may sit between a verb and the thing it refers to ("notes that I edited").
+const VERB_FILLERS: &[&str] = &["i", "we", "you", "that", "which", "was", "were", "been", "have", "had"];
+
+const PREPOSITIONS: &[&str] = &["from", "in", "during", "on", "of", "for", "at"];
+
+/// Finds the first temporal expression in `query`, resolved relative to `today`.
+/// Returns `None` when the query has no date expression, leaving the query untouched.
+pub fn extract_temporal_filter(query: &str, today: NaiveDate) -> Option<TemporalFilter> {
+    let tokens: Vec<Token> = query
+        .split_whitespace()
+        .map(|original| Token {
+            original,
+            word: original
+                .trim_matches(|c: char| !c.is_alphanumeric() && c != '-')
+                .to_lowercase(),
+        })
+        .collect();
+    let words: Vec<&str> = tokens.iter().map(|t| t.word.as_str()).collect();
+
+    for start in 0..words.len() {
+        let introduced = start > 0 && PREPOSITIONS.contains(&words[start - 1]);
+        let Some((bound, consumed)) = match_expression(&words[start..], today, introduced) else {
+            continue;
+        };
+
+        let mut remove_from = start;
+        let remove_to = start + consumed;
+        // Drop a leading preposition that only introduces the date ("invoices from last March")
+        if remove_from > 0 && PREPOSITIONS.contains(&words[remove_from - 1]) {
+            remove_from -= 1;
+        }
+
+        let mut removed: Vec<bool> = vec![false; tokens.len()];
+        removed[remove_from..remove_to].iter_mut().for_each(|r| *r = true);
+        let field = take_field_verb(&words, &mut removed, remove_from, remove_to)
+            .unwrap_or(DateField::Modified);
+
+        let remaining_query = tokens
+            .iter()
+            .zip(&removed)
+            .filter(|(_, removed)| !**removed)
+            .map(|(t, _)| t.original)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let (date_from, date_to) = match bound {
+            Bound::Within(range) => (Some(range.start), Some(range.end)),
+            Bound::Since(date) => (Some(date), None),
+            Bound::Until(date) => (None, Some(date)),
+        };
+
+        return Some(TemporalFilter {
+            date_from,
+            date_to,
+            field,
+            remaining_query,
+        });
+    }
+    None
+}
+
+/// Takes a verb that names the timestamp ("edited", "opened") from right before or right
+/// after the expression at `from..to`, together with the pronouns in front of it.
+/// Verbs elsewhere in the query are ordinary search words ("used cars from 2023").
+fn take_field_verb(words: &[&str], removed: &mut [bool], from: usize, to: usize) -> Option<DateField> {
+    let field_of = |i: usize| {
+        let word = words.get(i)?;
+        FIELD_VERBS
+            .iter()
+            .find(|(verb, _)| verb == word)
+            .map(|(_, field)| *field)
+    };
+    let i = from
+        .checked_sub(1)
+        .filter(|&i| field_of(i).is_some())
+        .or_else(|| field_of(to).is_some().then_some(to))?;
+    let field = field_of(i)?;
+
+    removed[i] = true;
+    let mut j = i;
+    while j > 0 && !removed[j - 1] && VERB_FILLERS.contains(&words[j - 1]) {
+        j -= 1;
+        removed[j] = true;
+    }
+    Some(field)
+}
+
+/// Tries every expression form at the start of `words`; returns the bound and tokens consumed.
+/// `introduced` is set when a preposition precedes the words ("in May", "from 2023"), which
+/// makes ambiguous forms such as bare years and the month "may" acceptable.
+fn match_expression(words: &[&str], today: NaiveDate, introduced: bool) -> Option<(Bound, usize)> {
+    if let Some(result) = match_range(words, today, introduced) {
+        return Some(result);
+    }
+
+    let (keyword, rest) = words.split_first()?;
+    match *keyword {
+        "since" | "after" => {
+            let (range, consumed) = match_point(rest, today, true)?;
+            let bound = if *keyword == "since" {
+                Bound::Since(range.start)
+            } else {
+                Bound::Since(range.end.succ_opt()?)
+            };
+            return Some((bound, consumed + 1));
+        }
+        "before" | "until" | "till" => {
+            let (range, consumed) = match_point(rest, today, true)?;
+            let bound = if *keyword == "before" {
+                Bound::Until(range.start.pred_opt()?)
+            } else {
+                Bound::Until(range.end)
+            };
+            return Some((bound, consumed + 1));
+        }
+        _ => {}
+    }
+
+    let (range, consumed) = match_point(words, today, introduced)?;
+    Some((Bound::Within(range), consumed))
+}
+
+/// "between June and August", "from 2021 to 2023", "June through August"
+/// Bare years and "may"/"march" need the same introduction as in `match_expression`;
+/// "between" and "from" count as one.
+fn match_range(words: &[&str], today: NaiveDate, introduced: bool) -> Option<(Bound, usize)> {
+    let (skip, separators): (usize, &[&str]) = match *words.first()? {
+        "between" => (1, &["and"]),
+        "from" => (1, &["to", "until", "till", "through", "-"]),
+        _ => (0, &["to", "until", "till", "through", "-"]),
+    };
+    let introduced = introduced || skip > 0;
+
+    let first_words = &words[skip..];
+    let (first, first_len) = match_named_period(first_words, today, None, introduced)?;
+    let separator = *first_words.get(first_len)?;
+    if !separators.contains(&separator) {
+        return None;
+    }
+
+    let second_words = &first_words[first_len + 1..];
+    let (second, second_len) = match_named_period(second_words, today, None, introduced)?;
+
+    // A yearless start month belongs to the year of the end ("between November and February")
+    let (first, _) = match_named_period(first_words, today, Some(second.end), introduced)
+        .unwrap_or((first, first_len));
+    let first = if first.start > second.start {
+        shift_back_one_year(first)?
+    } else {
+        first
+    };
+
+    let range = DateRange {
+        start: first.start,
+        end: second.end,
+    };
+    Some((Bound::Within(range), skip + first_len + 1 + second_len))
+}
+
+/// A single period: relative expressions, named periods and explicit dates.
+fn match_point(words: &[&str], today: NaiveDate, introduced: bool) -> Option<(DateRange, usize)> {
+    match_relative(words, today, introduced)
+        .or_else(|| match_named_period(words, today, None, introduced))
+        .or_else(|| match_iso_date(words))
+}
+
+/// "today", "yesterday", "this week", "last month", "past 3 days", "2 weeks ago", "recently".
+/// A bare weekday ("friday") counts only when introduced, like bare years and "may"/"march".
+fn match_relative(words: &[&str], today: NaiveDate, introduced: bool) -> Option<(DateRange, usize)> {
+    let first = *words.first()?;
+    match first {
+        "today" => return Some((DateRange::day(today), 1)),
+        "yesterday" => return Some((DateRange::day(today.pred_opt()?), 1)),
+        "recently" | "lately" => {
+            return Some((
+                DateRange {
+                    start: today - Duration::days(30),
+                    end: today,
+                },
+                1,
+            ))
+        }
+        _ => {}
+    }
+
+    // "3 days ago", "a week ago"
+    if words.get(2) == Some(&"ago") {
+        let amount = parse_amount(first)?;
+        let unit = parse_unit(words.get(1)?)?;
+        return Some((ago(today, amount, unit)?, 3));
+    }
+
+    // A trailing "friday" has no second word, so only "this"/"last" below need one
+    let second = words.get(1).copied().unwrap_or("");
+    match (first, second) {
+        ("this", "week") => return Some((DateRange { start: week_start(today), end: today }, 2)),
+        ("last", "week") | ("previous", "week") => {
+            let start = week_start(today) - Duration::days(7);
+            return Some((DateRange { start, end: start + Duration::days(6) }, 2));
+        }
+        ("this", "weekend") | ("last", "weekend") => {
+            let saturday = week_start(today) + Duration::days(5);
+            let saturday = if first == "last" || saturday > today {
+                saturday - Duration::days(7)
+            } else {
+                saturday
+            };
+            return Some((DateRange { start: saturday, end: saturday + Duration::days(1) }, 2));
+        }
+        ("this", "month") => return Some((DateRange { start: today.with_day(1)?, end: today }, 2)),
+        ("last", "month") | ("previous", "month") => {
+            let last_month = today.with_day(1)?.checked_sub_months(Months::new(1))?;
+            return Some((month_range(last_month.year(), last_month.month())?, 2));
+        }
+        ("this", "year") => return Some((DateRange { start: NaiveDate::from_ymd_opt(today.year(), 1, 1)?, end: today }, 2)),
+        ("last", "year") | ("previous", "year") => return Some((year_range(today.year() - 1)?, 2)),
+        ("this", "quarter") => {
+            let quarter = quarter_range(today.year(), quarter_of(today))?;
+            return Some((DateRange { start: quarter.start, end: today }, 2));
+        }
+        ("last", "quarter") | ("previous", "quarter") => {
+            let (year, quarter) = match quarter_of(today) {
+                1 => (today.year() - 1, 4),
+                q => (today.year(), q - 1),
+            };
+            return Some((quarter_range(year, quarter)?, 2));
+        }
+        _ => {}
+    }
+
+    // "past 3 days", "last two weeks", "last few months", "past couple of weeks"
+    if matches!(first, "past" | "last" | "previous") {
+        let mut index = 1;
+        let amount = match words.get(index) {
+            Some(&"few") => 3,
+            Some(&"couple") => {
+                if words.get(index + 1) == Some(&"of") {
+                    index += 1;
+                }
+                2
+            }
+            Some(word) => parse_amount(word).unwrap_or(0),
+            None => 0,
+        };
+        if amount > 0 {
+            index += 1;
+            let unit = parse_unit(words.get(index)?)?;
+            let start = subtract(today, amount, unit)?;
+            return Some((DateRange { start, end: today }, index + 1));
+        }
+    }
+
+    // "last friday", "on monday"
+    let (weekday_word, consumed) = if matches!(first, "last" | "this") {
+        (second, 2)
+    } else if introduced {
+        (first, 1)
+    } else {
+        return None;
+    };
+    if let Some(weekday) = parse_weekday(weekday_word) {
+        let mut date = today.pred_opt()?;
+        while date.weekday() != weekday {
+            date = date.pred_opt()?;
+        }
+        return Some((DateRange::day(date), consumed));
+    }
+
+    None
+}
+
+/// Months, quarters and years, optionally qualified: "March", "last March", "March 2023",
+/// "Q3", "Q3 2023", "first quarter of 2022", "2023".
+/// `anchor` resolves yearless periods to the latest occurrence not after it (defaults to today).
+fn match_named_period(
+    words: &[&str],
+    today: NaiveDate,
+    anchor: Option<NaiveDate>,
+    introduced: bool,
+) -> Option<(DateRange, usize)> {
+    let anchor = anchor.unwrap_or(today);
+    let (qualifier, offset) = match words.first() {
+        Some(&"last") | Some(&"previous") => (Some("last"), 1),
+        Some(&"this") => (Some("this"), 1),
+        _ => (None, 0),
+    };
+    let word = *words.get(offset)?;
+
+    if let Some(month) = parse_month(word) {
+        // "may" and "march" double as ordinary words; only accept them when introduced or qualified
+        let explicit_year = words.get(offset + 1).and_then(|w| parse_year(w));
+        if matches!(word, "may" | "march") && !introduced && qualifier.is_none() && explicit_year.is_none() {
+            return None;
+        }
+        if let Some(year) = explicit_year {
+            return Some((month_range(year, month)?, offset + 2));
+        }
+        let year = match qualifier {
+            Some("this") => anchor.year(),
+            Some(_) if month >= anchor.month() => anchor.year() - 1,
+            _ if month > anchor.month() => anchor.year() - 1,
+            _ => anchor.year(),
+        };
+        return Some((month_range(year, month)?, offset + 1));
+    }
+
+    if let Some((quarter, quarter_len)) = parse_quarter(&words[offset..]) {
+        let mut consumed = offset + quarter_len;
+        let explicit_year = match (words.get(consumed), words.get(consumed + 1)) {
+            (Some(&"of"), Some(w)) if parse_year(w).is_some() => {
+                consumed += 2;
+                parse_year(w)
+            }
+            (Some(w), _) if parse_year(w).is_some() => {
+                consumed += 1;
+                parse_year(w)
+            }
+            _ => None,
+        };
+        let year = explicit_year.unwrap_or_else(|| match qualifier {
+            Some("this") => anchor.year(),
+            Some(_) if quarter >= quarter_of(anchor) => anchor.year() - 1,
+            _ if quarter > quarter_of(anchor) => anchor.year() - 1,
+            _ => anchor.year(),
+        });
+        return Some((quarter_range(year, quarter)?, consumed));
+    }
+
+    // Bare years only count when introduced by a preposition, so a file named
+    // "budget 2023" is still searchable by its name.
+    if introduced && qualifier.is_none() {
+        if let Some(year) = parse_year(word) {
+            return Some((year_range(year)?, 1));
+        }
+    }
+
+    None
+}
+
+/// "2023-03-15"
+fn match_iso_date(words: &[&str]) -> Option<(DateRange, usize)> {
+    let date = NaiveDate::parse_from_str(words.first()?, "%Y-%m-%d").ok()?;
+    Some((DateRange::day(date), 1))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+fn parse_unit(word: &str) -> Option<Unit> {
+    match word {
+        "day" | "days" => Some(Unit::Day),
+        "week" | "weeks" => Some(Unit::Week),
+        "month" | "months" => Some(Unit::Month),
+        "year" | "years" => Some(Unit::Year),
+        _ => None,
+    }
+}
+
+fn parse_amount(word: &str) -> Option<u32> {
+    let amount = match word {
+        "a" | "an" | "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        "twelve" => 12,
+        _ => word.parse::<u32>().ok()?,
+    };
+    (amount > 0 && amount <= 1000).then_some(amount)
+}
+
+fn subtract(today: NaiveDate, amount: u32, unit: Unit) -> Option<NaiveDate> {
+    match unit {
+        Unit::Day => today.checked_sub_signed(Duration::days(amount as i64)),
+        Unit::Week => today.checked_sub_signed(Duration::weeks(amount as i64)),
+        Unit::Month => today.checked_sub_months(Months::new(amount)),
+        Unit::Year => today.checked_sub_months(Months::new(amount * 12)),
+    }
+}
+
+fn ago(today: NaiveDate, amount: u32, unit: Unit) -> Option<DateRange> {
+    let date = subtract(today, amount, unit)?;
+    match unit {
+        Unit::Day => Some(DateRange::day(date)),
+        Unit::Week => Some(DateRange {
+            start: week_start(date),
+            end: week_start(date) + Duration::days(6),
+        }),
+        Unit::Month => month_range(date.year(), date.month()),
+        Unit::Year => year_range(date.year()),
+    }
+}
+
+fn parse_month(word: &str) -> Option<u32> {
+    let month = match word {
+        "january" | "jan" => 1,
+        "february" | "feb" => 2,
+        "march" | "mar" => 3,
+        "april" | "apr" => 4,
+        "may" => 5,
+        "june" | "jun" => 6,
+        "july" | "jul" => 7,
+        "august" | "aug" => 8,
+        "september" | "sep" | "sept" => 9,
+        "october" | "oct" => 10,
+        "november" | "nov" => 11,
+        "december" | "dec" => 12,
+        _ => return None,
+    };
+    Some(month)
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" => Some(Weekday::Mon),
+        "tuesday" => Some(Weekday::Tue),
+        "wednesday" => Some(Weekday::Wed),
+        "thursday" => Some(Weekday::Thu),
+        "friday" => Some(Weekday::Fri),
+        "saturday" => Some(Weekday::Sat),
+        "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// "q3" or "third quarter"; returns the quarter and tokens consumed.
+fn parse_quarter(words: &[&str]) -> Option<(u32, usize)> {
+    let first = *words.first()?;
+    if let Some(digit) = first.strip_prefix('q') {
+        let quarter = digit.parse::<u32>().ok()?;
+        return (1..=4).contains(&quarter).then_some((quarter, 1));
+    }
+    if words.get(1) != Some(&"quarter") {
+        return None;
+    }
+    let quarter = match first {
+        "first" | "1st" => 1,
+        "second" | "2nd" => 2,
+        "third" | "3rd" => 3,
+        "fourth" | "4th" => 4,
+        _ => return None,
+    };
+    Some((quarter, 2))
+}
+
+fn parse_year(word: &str) -> Option<i32> {
+    if word.len() != 4 {
+        return None;
+    }
+    let year = word.parse::<i32>().ok()?;
+    (1970..=2100).contains(&year).then_some(year)
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn quarter_of(date: NaiveDate) -> u32 {
+    (date.month() - 1) / 3 + 1
+}
+
+fn month_range(year: i32, month: u32) -> Option<DateRange> {
+    let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let end = start.checked_add_months(Months::new(1))?.pred_opt()?;
+    Some(DateRange { start, end })
+}
+
+fn quarter_range(year: i32, quarter: u32) -> Option<DateRange> {
+    let start = NaiveDate::from_ymd_opt(year, (quarter - 1) * 3 + 1, 1)?;
+    let end = start.checked_add_months(Months::new(3))?.pred_opt()?;
+    Some(DateRange { start, end })
+}
+
+fn year_range(year: i32) -> Option<DateRange> {
+    Some(DateRange {
+        start: NaiveDate::from_ymd_opt(year, 1, 1)?,
+        end: NaiveDate::from_ymd_opt(year, 12, 31)?,
+    })
+}
+
+fn shift_back_one_year(range: DateRange) -> Option<DateRange> {
+    Some(DateRange {
+        start: range.start.checked_sub_months(Months::new(12))?,
+        end: range.end.checked_sub_months(Months::new(12))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Saturday
+    const TODAY: &str = "2024-06-15";
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    type Expected = Option<(Option<&'static str>, Option<&'static str>, DateField, &'static str)>;
+
+    #[test]
+    fn extracts_temporal_filters() {
+        use DateField::*;
+        let cases: &[(&str, Expected)] = &[
+            ("meeting notes", None),
+            ("invoices from last March", Some((Some("2024-03-01"), Some("2024-03-31"), Modified, "invoices"))),
+            ("notes I edited yesterday", Some((Some("2024-06-14"), Some("2024-06-14"), Modified, "notes"))),
+            ("photos opened last week", Some((Some("2024-06-03"), Some("2024-06-09"), Accessed, "photos"))),
+            ("last week edited draft", Some((Some("2024-06-03"), Some("2024-06-09"), Modified, "draft"))),
+            ("files I read last month", Some((Some("2024-05-01"), Some("2024-05-31"), Accessed, "files"))),
+            ("3 days ago", Some((Some("2024-06-12"), Some("2024-06-12"), Modified, ""))),
+            ("q3 2023 reports", Some((Some("2023-07-01"), Some("2023-09-30"), Modified, "reports"))),
+            ("since last monday", Some((Some("2024-06-10"), None, Modified, ""))),
+            ("notes from friday", Some((Some("2024-06-14"), Some("2024-06-14"), Modified, "notes"))),
+            ("edited on monday", Some((Some("2024-06-10"), Some("2024-06-10"), Modified, ""))),
+            ("friday notes", None),
+            ("before 2020", Some((None, Some("2019-12-31"), Modified, ""))),
+            // Bare years and "may"/"march" are ordinary words unless introduced
+            ("budget 2023", None),
+            ("march madness", None),
+            ("report from 2023", Some((Some("2023-01-01"), Some("2023-12-31"), Modified, "report"))),
+            ("2023 to 2024", None),
+            ("march to may", None),
+            ("from 2023 to 2024", Some((Some("2023-01-01"), Some("2024-12-31"), Modified, ""))),
+            ("between may and june", Some((Some("2024-05-01"), Some("2024-06-30"), Modified, ""))),
+            ("between November and February", Some((Some("2023-11-01"), Some("2024-02-29"), Modified, ""))),
+            // Verbs away from the expression stay in the query
+            ("used cars from 2023", Some((Some("2023-01-01"), Some("2023-12-31"), Modified, "used cars"))),
+            ("read me from last week", Some((Some("2024-06-03"), Some("2024-06-09"), Modified, "read me"))),
+        ];
+
+        for (query, expected) in cases {
+            let actual = extract_temporal_filter(query, date(TODAY)).map(|f| {
+                (f.date_from, f.date_to, f.field, f.remaining_query)
+            });
+            let expected = expected.map(|(from, to, field, rest)| {
+                (from.map(date), to.map(date), field, rest.to_string())
+            });
+            assert_eq!(actual, expected, "query: {}", query);
+        }
+    }
+}
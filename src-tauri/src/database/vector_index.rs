@@ -0,0 +1,466 @@
+// The file vector store behind one interface. LanceDB is the backend; the sqlite-vec tables
+// used before it are emptied into it by `migrate_legacy_vectors`.
+use crate::database::lancedb_ops::connect_lancedb;
+use crate::embed_and_store::{normalize, EMBEDDING_DIM};
+use crate::file_scanner::lancedb::{delete_rows_with_ids_lancedb, int32_column};
+use crate::file_scanner::pipeline::lancedb_id;
+use arrow::array::ArrayData;
+use arrow_array::{
+    Array, FixedSizeListArray, Float32Array, Int32Array, RecordBatch, RecordBatchIterator,
+    StringArray,
+};
+use arrow_schema::{DataType, Field};
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use lancedb::{DistanceType, Table};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::Arc;
+
+/// One file as the vector index stores it. `file_id` is the SQLite `files.id`.
+#[derive(Debug, Clone)]
+pub struct IndexedVector {
+    pub file_id: i64,
+    pub path: String,
+    pub content: String,
+    pub content_hash: Option<String>,
+    /// None when nothing could be embedded for the file
+    pub vector: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VectorHit {
+    pub file_id: i64,
+    pub name: String,
+    pub path: String,
+    /// 1 - cosine distance
+    pub relevance: f32,
+}
+
+/// Filters are SQL predicates over the `id`, `name`, `path` and `content` of a file.
+#[async_trait]
+pub trait VectorIndex: Send + Sync {
+    /// Adds files that aren't in the index yet.
+    async fn insert(&self, records: &[IndexedVector]) -> Result<(), String>;
+    /// Removes files; ids without a row are ignored.
+    async fn delete(&self, file_ids: &[i64]) -> Result<(), String>;
+    /// The files nearest to a normalized query vector, best first.
+    async fn search(
+        &self,
+        query: &[f32],
+        filter: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<VectorHit>, String>;
+    /// Ids of the files matching `filter`, unranked.
+    async fn filter(&self, filter: &str, limit: usize) -> Result<Vec<i64>, String>;
+    /// Which of `file_ids` are stored with a vector a search can find, i.e. not all zeros.
+    async fn with_vectors(&self, file_ids: &[i64]) -> Result<Vec<i64>, String>;
+}
+
+/// The LanceDB `files` table.
+pub struct LanceVectorIndex {
+    files: Table,
+}
+
+impl LanceVectorIndex {
+    pub async fn open() -> Result<Self, String> {
+        let files = connect_lancedb()
+            .await?
+            .open_table("files")
+            .execute()
+            .await
+            .map_err(|e| format!("Failed to open LanceDB files table: {}", e))?;
+        Ok(Self { files })
+    }
+
+    /// Wraps a `files` table that is already open.
+    pub fn from_table(files: Table) -> Self {
+        Self { files }
+    }
+}
+
+fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray, String> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| format!("'{}' column missing from LanceDB result", name))
+}
+
+#[async_trait]
+impl VectorIndex for LanceVectorIndex {
+    async fn insert(&self, records: &[IndexedVector]) -> Result<(), String> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let len = records.len();
+        let mut ids = Vec::with_capacity(len);
+        let mut names = Vec::with_capacity(len);
+        let mut exts = Vec::with_capacity(len);
+        let mut paths = Vec::with_capacity(len);
+        let mut contents = Vec::with_capacity(len);
+        let mut hashes = Vec::with_capacity(len);
+        let mut all_vectors_flat = Vec::with_capacity(len * EMBEDDING_DIM);
+
+        for record in records {
+            let path_obj = Path::new(&record.path);
+            ids.push(lancedb_id(record.file_id)?);
+            names.push(path_obj.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string());
+            exts.push(path_obj.extension().and_then(|e| e.to_str()).unwrap_or("").to_string());
+            paths.push(record.path.clone());
+            contents.push(record.content.clone());
+            hashes.push(record.content_hash.clone());
+            match &record.vector {
+                Some(vector) if vector.len() == EMBEDDING_DIM => {
+                    all_vectors_flat.extend(normalize(vector.clone()))
+                }
+                // Zero vectors never match a search; files without one are found by keyword
+                _ => all_vectors_flat.extend(vec![0.0; EMBEDDING_DIM]),
+            }
+        }
+
+        let schema = self.files.schema().await.map_err(|e| e.to_string())?;
+        let list_data_type = DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            EMBEDDING_DIM as i32,
+        );
+        let list_array_data = ArrayData::builder(list_data_type)
+            .len(len)
+            .add_child_data(Float32Array::from(all_vectors_flat).into_data())
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(StringArray::from(names)),
+                Arc::new(StringArray::from(exts)),
+                Arc::new(StringArray::from(paths)),
+                Arc::new(StringArray::from(contents)),
+                Arc::new(FixedSizeListArray::from(list_array_data)),
+                Arc::new(StringArray::from(hashes)),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        self.files
+            .add(batches)
+            .execute()
+            .await
+            .map_err(|e| format!("Batch metadata insert failed: {}", e))?;
+        Ok(())
+    }
+
+    async fn delete(&self, file_ids: &[i64]) -> Result<(), String> {
+        delete_rows_with_ids_lancedb(&self.files, file_ids)
+            .await
+            .map_err(|e| format!("Failed to remove LanceDB files: {}", e))
+    }
+
+    async fn search(
+        &self,
+        query: &[f32],
+        filter: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<VectorHit>, String> {
+        let mut vector_query = self
+            .files
+            .vector_search(query.to_vec())
+            .map_err(|e| format!("Failed to create vector query: {}", e))?
+            .distance_type(DistanceType::Cosine) // Match index training distance type
+            .limit(limit)
+            .nprobes(20) // Search 5-15% of partitions for good recall/latency balance
+            .refine_factor(10); // Add refine step for better accuracy
+        if let Some(filter) = filter {
+            vector_query = vector_query.only_if(filter);
+        }
+        let mut stream = vector_query
+            .execute()
+            .await
+            .map_err(|e| format!("LanceDB search error: {}", e))?;
+
+        let mut hits = Vec::new();
+        while let Some(batch) = stream
+            .try_next()
+            .await
+            .map_err(|e| format!("Error reading batch: {}", e))?
+        {
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            let ids = int32_column(&batch, "id").map_err(|e| e.to_string())?;
+            let names = string_column(&batch, "name")?;
+            let paths = string_column(&batch, "path")?;
+            let distances = batch
+                .column_by_name("_distance")
+                .and_then(|c| c.as_any().downcast_ref::<Float32Array>())
+                .ok_or("'_distance' column missing from LanceDB result")?;
+
+            for i in 0..batch.num_rows() {
+                let relevance = 1.0 - distances.value(i);
+                // Zero vectors produce NaN distances
+                if !relevance.is_finite() {
+                    continue;
+                }
+                hits.push(VectorHit {
+                    file_id: ids.value(i) as i64,
+                    name: names.value(i).to_string(),
+                    path: paths.value(i).to_string(),
+                    relevance,
+                });
+            }
+        }
+        Ok(hits)
+    }
+
+    async fn filter(&self, filter: &str, limit: usize) -> Result<Vec<i64>, String> {
+        let mut stream = self
+            .files
+            .query()
+            .only_if(filter)
+            .select(Select::columns(&["id"]))
+            .limit(limit)
+            .execute()
+            .await
+            .map_err(|e| format!("LanceDB query error: {}", e))?;
+
+        let mut ids = Vec::new();
+        while let Some(batch) = stream
+            .try_next()
+            .await
+            .map_err(|e| format!("Error reading batch: {}", e))?
+        {
+            let column = int32_column(&batch, "id").map_err(|e| e.to_string())?;
+            ids.extend((0..batch.num_rows()).map(|i| column.value(i) as i64));
+        }
+        Ok(ids)
+    }
+
+    async fn with_vectors(&self, file_ids: &[i64]) -> Result<Vec<i64>, String> {
+        if file_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let id_list = file_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+        let mut stream = self
+            .files
+            .query()
+            .only_if(format!("id IN ({})", id_list))
+            .select(Select::columns(&["id", "vector"]))
+            .execute()
+            .await
+            .map_err(|e| format!("LanceDB query error: {}", e))?;
+
+        let mut ids = Vec::new();
+        while let Some(batch) = stream
+            .try_next()
+            .await
+            .map_err(|e| format!("Error reading batch: {}", e))?
+        {
+            let column = int32_column(&batch, "id").map_err(|e| e.to_string())?;
+            let vectors = batch
+                .column_by_name("vector")
+                .and_then(|c| c.as_any().downcast_ref::<FixedSizeListArray>())
+                .ok_or("'vector' column missing from LanceDB result")?;
+            for i in 0..batch.num_rows() {
+                if vectors.is_null(i) {
+                    continue;
+                }
+                let values = vectors.value(i);
+                let values = values
+                    .as_any()
+                    .downcast_ref::<Float32Array>()
+                    .ok_or("'vector' is not a float vector")?;
+                if values.values().iter().any(|v| *v != 0.0) {
+                    ids.push(column.value(i) as i64);
+                }
+            }
+        }
+        Ok(ids)
+    }
+}
+
+// Files moved per step; each step is its own delete and insert
+const LEGACY_BATCH_SIZE: usize = 500;
+
+fn legacy_table_exists(db: &Connection) -> rusqlite::Result<bool> {
+    db.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master
+                       WHERE type = 'table' AND name = 'legacy_file_vectors')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Next batch of copied sqlite-vec vectors, joined with what the index stores about each file.
+fn load_legacy_batch(db: &Connection) -> rusqlite::Result<Vec<IndexedVector>> {
+    let mut stmt = db.prepare(
+        "SELECT l.file_id, f.path, f.content, f.content_hash, l.vector
+         FROM legacy_file_vectors l JOIN files f ON f.id = l.file_id
+         ORDER BY l.file_id LIMIT ?1",
+    )?;
+    let rows = stmt.query_map(params![LEGACY_BATCH_SIZE as i64], |row| {
+        let bytes: Vec<u8> = row.get(4)?;
+        let vector: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Ok(IndexedVector {
+            file_id: row.get(0)?,
+            path: row.get(1)?,
+            content: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            content_hash: row.get(3)?,
+            vector: Some(vector),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Moves the vectors SQLite migration 4 copied out of sqlite-vec into the vector index.
+/// Files the index already holds with a real vector keep it; rows stored with a zero vector
+/// are replaced. Safe to rerun after a crash: a legacy vector is only dropped once inserted.
+pub async fn migrate_legacy_vectors(index: &dyn VectorIndex) -> Result<usize, String> {
+    let has_legacy = {
+        let db = crate::database::get_connection();
+        legacy_table_exists(&db).map_err(|e| e.to_string())?
+    };
+    if !has_legacy {
+        return Ok(0);
+    }
+
+    let mut migrated = 0;
+    loop {
+        let batch = {
+            let db = crate::database::get_connection();
+            // Vectors of files deleted since the copy have nowhere to go
+            db.execute(
+                "DELETE FROM legacy_file_vectors WHERE file_id NOT IN (SELECT id FROM files)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+            load_legacy_batch(&db).map_err(|e| e.to_string())?
+        };
+        if batch.is_empty() {
+            break;
+        }
+
+        let ids: Vec<i64> = batch.iter().map(|r| r.file_id).collect();
+        let searchable = index.with_vectors(&ids).await?;
+        let replaced: Vec<IndexedVector> = batch
+            .iter()
+            .filter(|r| !searchable.contains(&r.file_id))
+            .cloned()
+            .collect();
+        // Missing files and zero-vector rows alike; deleting an id without a row is a no-op
+        let replaced_ids: Vec<i64> = replaced.iter().map(|r| r.file_id).collect();
+        index.delete(&replaced_ids).await?;
+        index.insert(&replaced).await?;
+        migrated += replaced.len();
+
+        let db = crate::database::get_connection();
+        let tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
+        for record in &batch {
+            tx.execute(
+                "DELETE FROM legacy_file_vectors WHERE file_id = ?1",
+                params![record.file_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+
+    crate::database::get_connection()
+        .execute_batch("DROP TABLE IF EXISTS legacy_file_vectors;")
+        .map_err(|e| e.to_string())?;
+    println!("Moved {} sqlite-vec vectors into the vector index", migrated);
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::lancedb_ops::files_schema;
+
+    fn vector(weights: &[(usize, f32)]) -> Vec<f32> {
+        let mut vector = vec![0.0; EMBEDDING_DIM];
+        for &(i, w) in weights {
+            vector[i] = w;
+        }
+        vector
+    }
+
+    fn record(file_id: i64, path: &str, vector: Option<Vec<f32>>) -> IndexedVector {
+        IndexedVector {
+            file_id,
+            path: path.to_string(),
+            content: String::new(),
+            content_hash: None,
+            vector,
+        }
+    }
+
+    fn hit_ids(hits: &[VectorHit]) -> Vec<i64> {
+        hits.iter().map(|h| h.file_id).collect()
+    }
+
+    /// What every backend must do; callers only ever see `&dyn VectorIndex`.
+    async fn check_contract(index: &dyn VectorIndex) {
+        index
+            .insert(&[
+                record(1, "/a/one.txt", Some(vector(&[(0, 1.0)]))),
+                record(2, "/a/two.txt", Some(vector(&[(0, 0.8), (1, 0.6)]))),
+                record(3, "/b/three.txt", Some(vector(&[(1, 1.0)]))),
+                record(4, "/b/none.txt", None),
+                record(5, "/b/short.txt", Some(vec![1.0; 3])),
+            ])
+            .await
+            .unwrap();
+        let query = vector(&[(0, 1.0)]);
+
+        // Best first; files without a usable vector never come back
+        let hits = index.search(&query, None, 10).await.unwrap();
+        assert_eq!(hit_ids(&hits), [1, 2, 3]);
+        assert!(hits[0].relevance > hits[1].relevance);
+        assert_eq!(hits[0].path, "/a/one.txt");
+        assert_eq!(hits[0].name, "one.txt");
+
+        let mut searchable = index.with_vectors(&[1, 2, 3, 4, 5, 99]).await.unwrap();
+        searchable.sort();
+        assert_eq!(searchable, [1, 2, 3]);
+
+        // The filter applies before the limit, not to the top hits afterwards
+        let hits = index.search(&query, Some("id <> 1"), 1).await.unwrap();
+        assert_eq!(hit_ids(&hits), [2]);
+
+        let mut ids = index.filter("path LIKE '/b/%'", 10).await.unwrap();
+        ids.sort();
+        assert_eq!(ids, [3, 4, 5]);
+        assert_eq!(index.filter("id > 0", 2).await.unwrap().len(), 2);
+
+        // Unknown ids are ignored
+        index.delete(&[1, 99]).await.unwrap();
+        assert!(index.filter("id = 1", 10).await.unwrap().is_empty());
+        let hits = index.search(&query, None, 10).await.unwrap();
+        assert_eq!(hit_ids(&hits), [2, 3]);
+        index.delete(&[]).await.unwrap();
+        index.insert(&[]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lance_index_meets_the_contract() {
+        let dir = std::env::temp_dir()
+            .join(format!("vector-index-contract-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let db = lancedb::connect(dir.to_str().unwrap()).execute().await.unwrap();
+        let files = db
+            .create_empty_table("files", files_schema())
+            .execute()
+            .await
+            .unwrap();
+
+        check_contract(&LanceVectorIndex { files }).await;
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -4,6 +4,10 @@ use std::sync::{Arc};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use rayon::ThreadPoolBuilder;
 
+// Vectors from different models are not comparable, so an index only works with the model that built it
+pub const EMBEDDING_MODEL: &str = "nomic-embed-text";
+pub const EMBEDDING_DIM: usize = 768;
+
 #[derive(Debug, Deserialize)]
 struct EmbeddingResponse {
     embedding: Vec<f32>,
@@ -24,7 +28,7 @@ pub fn get_embedding(text: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>>
     let res: EmbeddingResponse = client
         .post("http://localhost:11434/api/embeddings")
         .json(&serde_json::json!({
-            "model": "nomic-embed-text",
+            "model": EMBEDDING_MODEL,
             "prompt": text
         }))
         .send()?
@@ -43,7 +47,7 @@ pub fn get_batch_embeddings(texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn s
             let res: EmbeddingResponse = client
                 .post("http://localhost:11434/api/embeddings")
                 .json(&serde_json::json!({
-                    "model": "nomic-embed-text",
+                    "model": EMBEDDING_MODEL,
                     "prompt": text
                 }))
                 .send()?
@@ -80,7 +84,7 @@ where
                 let res: EmbeddingResponse = client
                     .post("http://localhost:11434/api/embeddings")
                     .json(&serde_json::json!({
-                        "model": "nomic-embed-text",
+                        "model": EMBEDDING_MODEL,
                         "prompt": text
                     }))
                     .send()?
@@ -1,15 +1,34 @@
 // Content extraction (read_file_content_with_category)
 use super::types::{FileCategory, FileContent};
 use super::utils::extract_drive;
+use crate::database::settings::AppSettings;
 use std::error::Error;
 use std::fs;
 use std::path::Path;
 use tokio::runtime::Runtime;
 
+// Plain-text reads give up above this size
+pub const MAX_TEXT_READ_BYTES: u64 = 10_000_000;
+
+/// The extractor `read_file_content_with_category` uses for an extension, or `None` when
+/// the category's content is discarded (media).
+pub fn extractor_for(extension: &str) -> Option<&'static str> {
+    match extension {
+        "pdf" => Some("pdf"),
+        "csv" | "tsv" => Some("csv"),
+        ext => match FileCategory::from_extension(ext) {
+            FileCategory::Code => Some("code metadata"),
+            FileCategory::Media => None,
+            _ => Some("plain text"),
+        },
+    }
+}
+
 pub async fn read_file_content_with_category(
     path: &str,
     max_chars: Option<usize>,
     process_content: bool,
+    settings: &AppSettings,
 ) -> Result<(String, FileCategory), Box<dyn Error + Send + Sync>> {
     let path_obj = Path::new(path);
     let extension = path_obj
@@ -25,15 +44,18 @@ pub async fn read_file_content_with_category(
     }
 
     let mut content = match extension.as_str() {
-        "pdf" => extract_pdf_text(path).await?,
+        "pdf" => extract_pdf_text(path, settings.max_pdf_pages).await?,
         "csv" | "tsv" => {
             let path_str = path.to_string();
             let ext = extension.clone();
             tokio::task::spawn_blocking(move || read_csv_to_string(&path_str, &ext)).await??
         }
         _ => {
+            // The indexing size limit is applied by the pipeline; previews only need a
+            // guard against huge files, raised when indexing allows bigger ones
+            let limit = MAX_TEXT_READ_BYTES.max(settings.max_file_size_bytes());
             if let Ok(metadata) = fs::metadata(path) {
-                if metadata.len() > 10_000_000 {
+                if metadata.len() > limit {
                     return Err("File too large for processing".into());
                 }
             }
@@ -97,12 +119,14 @@ fn read_csv_to_string(path: &str, ext: &str) -> Result<String, Box<dyn Error + S
     Ok(rows.join("\n"))
 }
 
-pub async fn extract_pdf_text(path: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+pub async fn extract_pdf_text(
+    path: &str,
+    max_pages: usize,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
     let path_str = path.to_string();
     tokio::task::spawn_blocking(move || {
         use lopdf::Document;
         use rayon::prelude::*;
-        const MAX_PAGES: usize = 25;
 
         let doc = Document::load(&path_str).map_err(|e| {
             Box::<dyn Error + Send + Sync>::from(format!(
@@ -111,7 +135,7 @@ pub async fn extract_pdf_text(path: &str) -> Result<String, Box<dyn Error + Send
                 e
             ))
         })?;
-        let page_ids: Vec<u32> = doc.get_pages().keys().copied().take(MAX_PAGES).collect();
+        let page_ids: Vec<u32> = doc.get_pages().keys().copied().take(max_pages).collect();
         if page_ids.is_empty() {
             return Err(Box::<dyn Error + Send + Sync>::from(
                 "PDF appears to be empty",
@@ -249,16 +273,45 @@ pub fn create_metadata_string(path_obj: &Path) -> String {
     )
 }
 
+/// Builds the text that represents a folder in the semantic folder index: its name,
+/// its path hierarchy and a summary of the files and subfolders it contains.
+pub fn create_folder_metadata_string(path_obj: &Path, child_files: &[String], child_folders: &[String]) -> String {
+    let folder_name = path_obj
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let folder_hierarchy: Vec<String> = path_obj
+        .components()
+        .filter_map(|comp| match comp {
+            std::path::Component::Normal(os_str) => os_str.to_str().map(String::from),
+            _ => None,
+        })
+        .collect();
+
+    format!(
+        "folder: {} path: {} folder_hierarchy: {} subfolders: {} contains_files: {} drive: {}",
+        folder_name,
+        path_obj.to_string_lossy(),
+        folder_hierarchy.join(" > "),
+        child_folders.join(", "),
+        child_files.join(", "),
+        extract_drive(&path_obj.to_string_lossy())
+    )
+}
+
 pub fn read_files_content_with_processing(
     paths: &[String],
     max_chars: Option<usize>,
     process_content: bool,
+    settings: &AppSettings,
 ) -> Vec<FileContent> {
     let rt = Runtime::new().expect("Failed to create runtime");
     rt.block_on(async {
         let mut results = Vec::new();
         for path in paths {
-            match read_file_content_with_category(path, max_chars, process_content).await {
+            match read_file_content_with_category(path, max_chars, process_content, settings).await {
                 Ok((content, category)) => results.push(FileContent {
                     path: path.clone(),
                     content,
@@ -274,6 +327,10 @@ pub fn read_files_content_with_processing(
     })
 }
 
-pub fn read_files_content(paths: &[String], max_chars: Option<usize>) -> Vec<FileContent> {
-    read_files_content_with_processing(paths, max_chars, true)
+pub fn read_files_content(
+    paths: &[String],
+    max_chars: Option<usize>,
+    settings: &AppSettings,
+) -> Vec<FileContent> {
+    read_files_content_with_processing(paths, max_chars, true, settings)
 }
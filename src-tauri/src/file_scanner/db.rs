@@ -1,13 +1,31 @@
 // All database interactions
 use super::types::FileContent;
-use crate::embed_and_store::normalize;
 use anyhow;
-use bytemuck::cast_slice;
 use chrono::{DateTime, Utc};
 use rusqlite::OptionalExtension;
 use rusqlite::{params, Connection, Result, Transaction};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+// Folders recorded per writer transaction during a walk
+const FOLDER_BATCH_SIZE: usize = 500;
+
+/// Records the folders a walk found, parents first. The writer is released between batches,
+/// so a long walk never holds up other writes.
+pub fn insert_walked_folders(paths: &[PathBuf]) -> Result<(), String> {
+    for batch in paths.chunks(FOLDER_BATCH_SIZE) {
+        let db = crate::database::get_connection();
+        let tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
+        for path in batch {
+            if let Err(e) = insert_folder_metadata(&tx, path) {
+                eprintln!("Failed to store folder {}: {}", path.display(), e);
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
 
 pub fn insert_folder_metadata(tx: &Transaction, path: &Path) -> anyhow::Result<i64> {
     let name = path
@@ -68,7 +86,7 @@ pub fn file_exists(db: &Connection, path: &str) -> Result<bool> {
     stmt.exists(params![path])
 }
 
-pub fn insert_file_metadata(tx: &Transaction, file: &FileContent) -> anyhow::Result<i64> {
+pub fn insert_file_metadata(tx: &Connection, file: &FileContent) -> anyhow::Result<i64> {
     let path_obj = Path::new(&file.path);
     let file_name = path_obj
         .file_name()
@@ -82,13 +100,21 @@ pub fn insert_file_metadata(tx: &Transaction, file: &FileContent) -> anyhow::Res
         .to_string();
     let metadata = fs::metadata(&file.path).map_err(|_| rusqlite::Error::InvalidQuery)?;
 
-    let created = Utc::now().to_rfc3339();
-    let updated = Utc::now().to_rfc3339();
+    // Store the filesystem timestamps so date filters ("edited last week") mean something
+    let created = metadata
+        .created()
+        .map(|t| DateTime::<Utc>::from(t).to_rfc3339())
+        .unwrap_or_else(|_| Utc::now().to_rfc3339());
+    let updated = metadata
+        .modified()
+        .map(|t| DateTime::<Utc>::from(t).to_rfc3339())
+        .unwrap_or_else(|_| Utc::now().to_rfc3339());
     let accessed = Into::<DateTime<Utc>>::into(metadata.accessed()?).to_rfc3339();
+    let folder_id = find_parent_folder_id(tx, path_obj)?;
 
     let mut stmt = tx.prepare(
-        "INSERT INTO files (name, extension, path, content, author, file_size, category, score, content_processed, created_at, updated_at, last_accessed)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+        "INSERT INTO files (name, extension, path, content, author, file_size, category, score, content_processed, created_at, updated_at, last_accessed, folder_id, content_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)"
     )?;
     stmt.execute(params![
         file_name,
@@ -103,31 +129,50 @@ pub fn insert_file_metadata(tx: &Transaction, file: &FileContent) -> anyhow::Res
         created,
         updated,
         accessed,
+        folder_id,
+        content_hash(file),
     ])?;
-    Ok(tx.last_insert_rowid())
-}
+    let file_id = tx.last_insert_rowid();
 
-pub fn insert_file_embedding(tx: &Transaction, file_id: i64, vector: Vec<f32>) -> Result<()> {
-    if vector.is_empty() {
-        return Ok(());
-    }
-    let normalized_vec = normalize(vector);
-    let vector_bytes: &[u8] = cast_slice(&normalized_vec);
+    // files_fts is an external-content table, so it is kept in step by hand
+    tx.execute(
+        "INSERT INTO files_fts (rowid, name, content) VALUES (?1, ?2, ?3)",
+        params![file_id, file_name, file.content],
+    )?;
+    Ok(file_id)
+}
 
-    let mut stmt_vec = tx.prepare("INSERT INTO file_vec(content_vec) VALUES(?1)")?;
-    stmt_vec.execute(params![vector_bytes])?;
-    let vec_rowid = tx.last_insert_rowid();
+/// SHA-256 of the extracted content; None for metadata-only files, whose content is a stand-in.
+pub fn content_hash(file: &FileContent) -> Option<String> {
+    file.content_processed
+        .then(|| format!("{:x}", Sha256::digest(file.content.as_bytes())))
+}
 
-    let mut stmt_map = tx.prepare("INSERT INTO file_vec_map(vec_rowid, file_id) VALUES(?1, ?2)")?;
-    stmt_map.execute(params![vec_rowid, file_id])?;
+/// Flags a file whose chunk vectors were stored.
+pub fn mark_vector_indexed(tx: &Connection, file_id: i64) -> Result<()> {
+    tx.execute("UPDATE files SET vector_indexed = 1 WHERE id = ?1", params![file_id])?;
     Ok(())
 }
 
-pub fn verify_embeddings(db: &Connection, file_id: i64) -> Result<bool, rusqlite::Error> {
-    let count: i64 = db.query_row(
-        "SELECT COUNT(*) FROM file_vec_map WHERE file_id = ?1",
-        params![file_id],
-        |row| row.get(0),
+/// Records one embedded chunk of a file; the returned id is its LanceDB `file_embeddings.id`.
+pub fn insert_file_chunk(tx: &Connection, file_id: i64, chunk_index: usize) -> Result<i64> {
+    let mut stmt = tx.prepare_cached(
+        "INSERT INTO file_chunks (file_id, chunk_index, created_at) VALUES (?1, ?2, ?3)",
     )?;
-    Ok(count > 0)
+    stmt.execute(params![file_id, chunk_index as i64, Utc::now().to_rfc3339()])?;
+    Ok(tx.last_insert_rowid())
+}
+
+/// The nearest indexed folder above `path`, used for the folder rollup.
+fn find_parent_folder_id(tx: &Connection, path: &Path) -> Result<Option<i64>> {
+    let mut stmt = tx.prepare_cached("SELECT id FROM folders WHERE path = ?1")?;
+    for ancestor in path.ancestors().skip(1) {
+        let id = stmt
+            .query_row(params![ancestor.to_string_lossy().to_string()], |row| row.get(0))
+            .optional()?;
+        if id.is_some() {
+            return Ok(id);
+        }
+    }
+    Ok(None)
 }
@@ -1,8 +1,13 @@
-// File finding logic (find_files, discover_drives)
-use super::db::insert_folder_metadata;
-use super::scoring::check_phase1_rules;
+// File finding logic (find_files, Phase 2 volume walking)
+use super::db::insert_walked_folders;
+use super::rule_engine::RuleSet;
 use super::types::ScannedFile;
 use super::utils::emit_scan_progress;
+use super::restrict_to_root;
+use super::volumes::selected_volume_paths;
+use super::walker::IgnoreStack;
+use crate::database::rules::RuleAction;
+use crate::database::settings::load_settings;
 use rusqlite::Connection;
 use std::path::{Path, PathBuf};
 use tauri::AppHandle;
@@ -10,13 +15,34 @@ use walkdir::{DirEntry, WalkDir};
 
 pub struct ScanConfig<'a> {
     pub base_paths: Vec<String>,
-    pub include_exts: Vec<String>,
-    pub exclude_folders: &'a [String],
-    pub exclude_paths: &'a [String],
+    pub rules: &'a RuleSet,
+    pub respect_ignore_files: bool,
+    /// Folder levels below a depth root to descend into
+    pub max_depth: usize,
+    /// Included paths `max_depth` is measured from. Entries outside all of them, such as the
+    /// rest of a volume in Phase 2, are not depth-limited.
+    pub depth_roots: Vec<String>,
 }
 
-/// Generic file discovery function.
-pub fn find_files(config: ScanConfig, app: &AppHandle, progress_stage: &str) -> Vec<String> {
+impl ScanConfig<'_> {
+    /// Whether `path` lies more than `max_depth` levels below the deepest depth root
+    /// containing it.
+    fn is_too_deep(&self, path: &Path) -> bool {
+        self.depth_roots
+            .iter()
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .map(|rest| rest.components().count())
+            .min()
+            .is_some_and(|depth| depth > self.max_depth)
+    }
+}
+
+/// Generic file discovery: every file the rules don't skip, with the action they decided.
+pub fn find_files(
+    config: ScanConfig,
+    app: &AppHandle,
+    progress_stage: &str,
+) -> Vec<(String, RuleAction)> {
     let mut found_files = Vec::new();
     let mut scanned_count = 0;
 
@@ -26,8 +52,11 @@ pub fn find_files(config: ScanConfig, app: &AppHandle, progress_stage: &str) ->
             continue;
         }
 
+        let mut ignores = IgnoreStack::new(&path_buf, config.respect_ignore_files);
         let walker = WalkDir::new(path_buf).into_iter();
-        let filtered_walker = walker.filter_entry(|e| !is_excluded_dir(e, &config));
+        let filtered_walker = walker.filter_entry(|e| {
+            !config.is_too_deep(e.path()) && !is_excluded_dir(e, &config) && !ignores.is_ignored(e)
+        });
 
         for entry in filtered_walker.filter_map(|e| e.ok()) {
             let path = entry.path();
@@ -35,17 +64,13 @@ pub fn find_files(config: ScanConfig, app: &AppHandle, progress_stage: &str) ->
                 continue;
             }
 
-            if should_exclude_path(
-                path,
-                config.exclude_folders,
-                config.exclude_paths,
-                Some(&config.include_exts),
-            ) {
+            let action = config.rules.evaluate(path, false).action;
+            if action == RuleAction::Skip {
                 continue;
             }
 
             let path_str = path.to_string_lossy().to_string();
-            found_files.push(path_str.clone());
+            found_files.push((path_str.clone(), action));
             scanned_count += 1;
             if scanned_count % 1000 == 0 {
                 emit_scan_progress(app, scanned_count, 0, &path_str, progress_stage);
@@ -56,102 +81,36 @@ pub fn find_files(config: ScanConfig, app: &AppHandle, progress_stage: &str) ->
 }
 
 fn is_excluded_dir(entry: &DirEntry, config: &ScanConfig) -> bool {
-    if !entry.file_type().is_dir() {
-        return false;
-    }
-
-    should_exclude_path(
-        entry.path(),
-        config.exclude_folders,
-        config.exclude_paths,
-        None, // Don't check extensions for directories
-    )
-}
-
-pub fn should_exclude_path(
-    path: &Path,
-    exclude_folders: &[String],
-    exclude_paths: &[String],
-    include_exts: Option<&[String]>,
-) -> bool {
-    let path_str = path.to_string_lossy();
-
-    if exclude_paths.iter().any(|p| {
-        let exclude_path = p.trim();
-        if exclude_path.is_empty() {
-            false
-        } else {
-            path_str.starts_with(exclude_path)
-        }
-    }) {
-        return true;
-    }
-
-    if path.ancestors().any(|ancestor| {
-        ancestor
-            .file_name()
-            .and_then(|n| n.to_str())
-            .map_or(false, |name| {
-                let lname = name.to_lowercase();
-                if lname.starts_with('.') {
-                    return true;
-                }
-                exclude_folders.iter().any(|ex| {
-                    let exclude_folder = ex.trim();
-                    !exclude_folder.is_empty() && ex.eq_ignore_ascii_case(name)
-                })
-            })
-    }) {
-        return true;
-    }
-
-    if let Some(include_exts) = include_exts {
-        if !include_exts.is_empty() {
-            let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-            if !include_exts
-                .iter()
-                .any(|ext| ext.eq_ignore_ascii_case(extension))
-            {
-                return true;
-            }
-        }
-    }
-
-    false
+    entry.file_type().is_dir() && config.rules.skips_subtree(entry.path())
 }
 
 /// Phase 1: Find text files based on user-defined rules.
 pub fn find_text_files(conn: &Connection, app: &AppHandle) -> Result<Vec<ScannedFile>, String> {
     println!("--- RUNNING find_text_files ---");
     emit_scan_progress(app, 0, 0, "", "scanning");
-    let include_exts: Vec<String> = crate::database::rules::get_included_extensions_sync(conn)
-        .map_err(|e| e.to_string())?
-        .into_iter()
-        .collect();
-    let exclude_folders: Vec<String> = crate::database::rules::get_excluded_folder_sync(conn)
-        .map_err(|e| e.to_string())?
-        .into_iter()
-        .collect();
-    let base_paths: Vec<String> = crate::database::rules::get_included_paths_sync(conn)
+    let rules = RuleSet::load(conn)?;
+    let settings = load_settings(conn);
+    let base_paths: Vec<String> = crate::database::rules::get_include_walk_roots_sync(conn)
         .map_err(|e| e.to_string())?
         .into_iter()
         .collect();
     let config = ScanConfig {
+        depth_roots: base_paths.clone(),
         base_paths,
-        include_exts,
-        exclude_folders: &exclude_folders,
-        exclude_paths: &[],
+        rules: &rules,
+        respect_ignore_files: settings.respect_ignore_files,
+        max_depth: settings.max_index_depth,
     };
-    let file_paths = find_files(config, app, "scanning");
 
-    let mut scanned_files = Vec::new();
-    for path in file_paths {
-        let (should_crawl, _) = check_phase1_rules(conn, &path)?;
-        scanned_files.push(ScannedFile {
+    // Only files whose content the rules allow to be read
+    let scanned_files: Vec<ScannedFile> = find_files(config, app, "scanning")
+        .into_iter()
+        .filter(|(_, action)| *action == RuleAction::Full)
+        .map(|(path, _)| ScannedFile {
             path,
-            content_processed: should_crawl,
-        });
-    }
+            content_processed: true,
+        })
+        .collect();
 
     emit_scan_progress(
         app,
@@ -164,11 +123,13 @@ pub fn find_text_files(conn: &Connection, app: &AppHandle) -> Result<Vec<Scanned
 }
 
 /// Phase 2: Find all files across all drives (metadata only).
+/// A non-empty `root` limits the walk to the selected volumes inside it.
 pub fn find_all_drive_files(
     conn: &Connection,
     app: &AppHandle,
+    root: &str,
 ) -> Result<Vec<ScannedFile>, String> {
-    let files = find_all_drive_files_internal(conn, app)?;
+    let files = find_all_drive_files_internal(conn, app, root)?;
     Ok(files
         .into_iter()
         .map(|path| ScannedFile {
@@ -181,38 +142,36 @@ pub fn find_all_drive_files(
 fn find_all_drive_files_internal(
     conn: &Connection,
     app: &AppHandle,
+    root: &str,
 ) -> Result<Vec<String>, String> {
     emit_scan_progress(app, 0, 0, "", "phase2_discovery");
 
-    let exclude_folders: Vec<String> = crate::database::rules::get_excluded_folder_sync(conn)
-        .map_err(|e| e.to_string())?
-        .into_iter()
-        .collect();
-
-    let exclude_paths: Vec<String> = crate::database::rules::get_excluded_paths_sync(conn)
-        .map_err(|e| e.to_string())?
-        .into_iter()
-        .collect();
-
+    let rules = RuleSet::load(conn)?;
+    let settings = load_settings(conn);
     let config = ScanConfig {
-        base_paths: discover_drives(),
-        include_exts: Vec::new(),
-        exclude_folders: &exclude_folders,
-        exclude_paths: &exclude_paths,
+        base_paths: restrict_to_root(selected_volume_paths(conn), root),
+        rules: &rules,
+        respect_ignore_files: settings.respect_ignore_files,
+        max_depth: settings.max_index_depth,
+        depth_roots: crate::database::rules::get_include_walk_roots_sync(conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect(),
     };
 
     let mut found_files = Vec::new();
+    let mut folders = Vec::new();
     let mut scanned_count = 0;
 
-    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
-
     for base_path in &config.base_paths {
         let path_buf = PathBuf::from(base_path);
         if !path_buf.exists() {
             continue;
         }
 
-        let mut walker = WalkDir::new(path_buf).into_iter();
+        let mut ignores = IgnoreStack::new(&path_buf, config.respect_ignore_files);
+        // Each volume is walked on its own, so never follow mounts into other filesystems
+        let mut walker = WalkDir::new(path_buf).same_file_system(true).into_iter();
         'walker_loop: while let Some(entry_result) = walker.next() {
             let entry = match entry_result {
                 Ok(entry) => entry,
@@ -221,20 +180,24 @@ fn find_all_drive_files_internal(
 
             let path = entry.path();
 
-            if path.is_dir() {
-                if let Err(e) = insert_folder_metadata(&tx, path) {
-                    eprintln!("Failed to store folder {}: {}", path.display(), e);
+            if config.is_too_deep(path) || ignores.is_ignored(&entry) {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
                 }
+                continue 'walker_loop;
+            }
+
+            if path.is_dir() {
+                folders.push(path.to_path_buf());
 
-                if should_exclude_path(path, config.exclude_folders, config.exclude_paths, None) {
+                if config.rules.skips_subtree(path) {
                     walker.skip_current_dir();
-                    continue 'walker_loop;
                 }
                 continue 'walker_loop;
             }
 
             if path.is_file() {
-                if should_exclude_path(path, config.exclude_folders, config.exclude_paths, None) {
+                if config.rules.is_skipped(path, false) {
                     continue 'walker_loop;
                 }
 
@@ -248,7 +211,7 @@ fn find_all_drive_files_internal(
         }
     }
 
-    tx.commit().map_err(|e| e.to_string())?;
+    insert_walked_folders(&folders)?;
 
     emit_scan_progress(
         app,
@@ -260,18 +223,3 @@ fn find_all_drive_files_internal(
 
     Ok(found_files)
 }
-
-#[cfg(target_os = "windows")]
-pub fn discover_drives() -> Vec<String> {
-    // Original implementation (commented for reference):
-
-    (b'A'..=b'Z')
-        .filter_map(|drive_letter| {
-            let path_str = format!("{}:\\", drive_letter as char);
-            Path::new(&path_str).exists().then_some(path_str)
-        })
-        .collect()
-
-    // Test path instead of discovering drives
-    // vec![r"C:\Users\Jai Mishra\Downloads\drive-test".to_string()]
-}
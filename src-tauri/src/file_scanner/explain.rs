@@ -0,0 +1,353 @@
+// "Why is this file (not) indexed?": runs one path through the checks a scan applies, in the
+// order the scan applies them, and reports where it currently sits in the index.
+use super::content::extractor_for;
+use super::lancedb::{count_path_vectors_lancedb, get_lancedb_tables};
+use super::rule_engine::RuleSet;
+use super::volume_registry::{load_tracked_volumes, owning_volume, VolumePolicy};
+use super::volumes::selected_volume_paths;
+use super::walker::ignored_by;
+use crate::database::rules::{load_scan_rules_sync, RuleAction, RuleType, ScanRule};
+use crate::database::settings::{
+    load_settings, AppSettings, MAX_FILE_SIZE_SETTING, MAX_INDEX_DEPTH_SETTING,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// One check in the decision trace.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceStep {
+    pub check: &'static str,
+    /// The most this check lets through: full leaves the file alone, skip drops it
+    pub allows: RuleAction,
+    pub detail: String,
+    /// The stored rule row behind this step, if one decided it
+    pub rule: Option<ScanRule>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexState {
+    pub file_id: Option<i64>,
+    pub content_processed: Option<bool>,
+    pub in_fts: bool,
+    /// Chunk rows SQLite holds ids for; LanceDB should have as many vectors
+    pub sqlite_chunks: i64,
+    pub lancedb_rows: usize,
+    pub lancedb_chunks: usize,
+    /// Set when LanceDB could not be queried; the counts above are then unknown
+    pub lancedb_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathExplanation {
+    pub path: String,
+    pub exists: bool,
+    pub is_dir: bool,
+    pub steps: Vec<TraceStep>,
+    /// What the next scan would do with the path: the strictest step wins
+    pub outcome: RuleAction,
+    pub index_state: IndexState,
+}
+
+fn step(check: &'static str, allows: RuleAction, detail: String, rule: Option<ScanRule>) -> TraceStep {
+    TraceStep {
+        check,
+        allows,
+        detail,
+        rule,
+    }
+}
+
+/// Everything but the LanceDB counts, which need an async context.
+pub fn explain_path_sync(db: &Connection, path: &str) -> Result<PathExplanation, String> {
+    let path_obj = Path::new(path);
+    let metadata = fs::metadata(path_obj).ok();
+    let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+    let stored_rules = load_scan_rules_sync(db).map_err(|e| e.to_string())?;
+    let settings = load_settings(db);
+    let mut steps = Vec::new();
+
+    let (reach, walk_root) = reach_step(db, path_obj, &stored_rules);
+    steps.push(reach);
+    if let Some(root) = walk_root {
+        steps.push(depth_step(&settings, Path::new(&root), path_obj));
+    }
+    steps.push(volume_step(db, path)?);
+    steps.push(ignore_step(&settings, path_obj));
+
+    let rules = RuleSet::from_rules(stored_rules);
+    steps.push(folder_step(&rules, path_obj));
+    steps.push(rule_step(&rules, path_obj, is_dir));
+
+    if let Some(metadata) = metadata.as_ref().filter(|m| m.is_file()) {
+        steps.push(size_step(&settings, metadata.len()));
+        steps.push(extractor_step(path_obj));
+    }
+
+    let outcome = steps.iter().map(|s| s.allows).max().unwrap_or(RuleAction::Metadata);
+    Ok(PathExplanation {
+        path: path.to_string(),
+        exists: metadata.is_some(),
+        is_dir,
+        steps,
+        outcome,
+        index_state: sqlite_index_state(db, path_obj).map_err(|e| e.to_string())?,
+    })
+}
+
+/// Fills in how many LanceDB rows and chunk vectors the path has.
+pub async fn add_lancedb_state(explanation: &mut PathExplanation) {
+    let counts = match get_lancedb_tables().await {
+        Ok((files_table, file_emb_table)) => {
+            count_path_vectors_lancedb(&files_table, &file_emb_table, &explanation.path).await
+        }
+        Err(e) => Err(e),
+    };
+    match counts {
+        Ok((rows, chunks)) => {
+            explanation.index_state.lancedb_rows = rows;
+            explanation.index_state.lancedb_chunks = chunks;
+        }
+        Err(e) => explanation.index_state.lancedb_error = Some(e.to_string()),
+    }
+}
+
+/// Whether any walk visits the path: a Phase 1 included path, or a volume selected for Phase 2.
+/// Also returns the included path the walk depth is measured from, if any.
+fn reach_step(db: &Connection, path: &Path, stored_rules: &[ScanRule]) -> (TraceStep, Option<String>) {
+    let include_root = stored_rules
+        .iter()
+        .filter(|r| r.rule_type == RuleType::Include && r.effective_action() != RuleAction::Skip)
+        .filter_map(|r| r.walk_root().map(|root| (r, root)))
+        .filter(|(_, root)| path.starts_with(root))
+        .max_by_key(|(_, root)| Path::new(root).components().count());
+    if let Some((rule, root)) = include_root {
+        let reach = step(
+            "included_paths",
+            RuleAction::Full,
+            format!("Inside the included path {}", root),
+            Some(rule.clone()),
+        );
+        return (reach, Some(root));
+    }
+
+    match selected_volume_paths(db).into_iter().find(|v| path.starts_with(v)) {
+        Some(volume) => {
+            let reach = step(
+                "included_paths",
+                RuleAction::Metadata,
+                format!(
+                    "Not inside an included path; only the metadata scan of {} reaches it",
+                    volume
+                ),
+                None,
+            );
+            // Outside included paths the volume walk is not depth-limited
+            (reach, None)
+        }
+        None => {
+            let reach = step(
+                "included_paths",
+                RuleAction::Skip,
+                "Not inside an included path or a volume selected for the metadata scan".to_string(),
+                None,
+            );
+            (reach, None)
+        }
+    }
+}
+
+fn depth_step(settings: &AppSettings, root: &Path, path: &Path) -> TraceStep {
+    let depth = path.components().count().saturating_sub(root.components().count());
+    if depth > settings.max_index_depth {
+        step(
+            MAX_INDEX_DEPTH_SETTING,
+            RuleAction::Skip,
+            format!(
+                "{} levels below {}, deeper than the limit of {}",
+                depth,
+                root.display(),
+                settings.max_index_depth
+            ),
+            None,
+        )
+    } else {
+        step(
+            MAX_INDEX_DEPTH_SETTING,
+            RuleAction::Full,
+            format!(
+                "{} levels below {}, within the limit of {}",
+                depth,
+                root.display(),
+                settings.max_index_depth
+            ),
+            None,
+        )
+    }
+}
+
+fn volume_step(db: &Connection, path: &str) -> Result<TraceStep, String> {
+    let volumes = load_tracked_volumes(db).map_err(|e| e.to_string())?;
+    let Some(volume) = owning_volume(&volumes, path) else {
+        return Ok(step(
+            "volume_policy",
+            RuleAction::Full,
+            "Not on a tracked volume".to_string(),
+            None,
+        ));
+    };
+    let name = volume.label.as_deref().unwrap_or(&volume.mount_point);
+    let (allows, policy) = match volume.policy {
+        VolumePolicy::Never => (RuleAction::Skip, "never indexed"),
+        VolumePolicy::Metadata => (RuleAction::Metadata, "indexed by metadata only"),
+        VolumePolicy::Full => (RuleAction::Full, "fully indexed"),
+    };
+    let online = if volume.is_online { "" } else { " (currently disconnected)" };
+    Ok(step(
+        "volume_policy",
+        allows,
+        format!("On volume {}{}, {}", name, online, policy),
+        None,
+    ))
+}
+
+fn ignore_step(settings: &AppSettings, path: &Path) -> TraceStep {
+    if !settings.respect_ignore_files {
+        return step(
+            "ignore_files",
+            RuleAction::Full,
+            "Ignore files are turned off".to_string(),
+            None,
+        );
+    }
+    match ignored_by(path) {
+        Some(pattern) => step(
+            "ignore_files",
+            RuleAction::Skip,
+            format!("Ignored by {}", pattern),
+            None,
+        ),
+        None => step(
+            "ignore_files",
+            RuleAction::Full,
+            "No ignore file matches".to_string(),
+            None,
+        ),
+    }
+}
+
+/// Walks stop at the shallowest folder a rule prunes, so nothing below it is ever seen.
+fn folder_step(rules: &RuleSet, path: &Path) -> TraceStep {
+    let mut parents: Vec<&Path> = path.ancestors().skip(1).collect();
+    parents.reverse();
+    match parents.into_iter().find(|folder| rules.skips_subtree(folder)) {
+        Some(folder) => step(
+            "excluded_folders",
+            RuleAction::Skip,
+            format!("The folder {} is skipped with everything in it", folder.display()),
+            rules.evaluate(folder, true).rule,
+        ),
+        None => step(
+            "excluded_folders",
+            RuleAction::Full,
+            "No enclosing folder is skipped".to_string(),
+            None,
+        ),
+    }
+}
+
+fn rule_step(rules: &RuleSet, path: &Path, is_dir: bool) -> TraceStep {
+    let decision = rules.evaluate(path, is_dir);
+    let detail = match &decision.rule {
+        None => "No rule matches; metadata only by default".to_string(),
+        Some(rule) if rule.id == 0 => "Hidden (dot-prefixed) entries are skipped".to_string(),
+        Some(rule) => format!(
+            "{:?} rule '{}' ({}, priority {}) decides: {}",
+            rule.category,
+            rule.pattern,
+            rule.match_type.as_str(),
+            rule.priority,
+            rule.effective_action().as_str()
+        ),
+    };
+    let detail = if decision.limited_by_extension_allow_list {
+        format!("{}; lowered to metadata, the extension is not an included extension", detail)
+    } else {
+        detail
+    };
+    step("scan_rules", decision.action, detail, decision.rule)
+}
+
+fn size_step(settings: &AppSettings, size: u64) -> TraceStep {
+    let max = settings.max_file_size_bytes();
+    if size > max {
+        step(
+            MAX_FILE_SIZE_SETTING,
+            RuleAction::Metadata,
+            format!("{} bytes is over the {} MB limit for content", size, settings.max_file_size_mb),
+            None,
+        )
+    } else {
+        step(
+            MAX_FILE_SIZE_SETTING,
+            RuleAction::Full,
+            format!("{} bytes is within the {} MB limit", size, settings.max_file_size_mb),
+            None,
+        )
+    }
+}
+
+fn extractor_step(path: &Path) -> TraceStep {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match extractor_for(extension) {
+        None => step(
+            "extractor",
+            RuleAction::Metadata,
+            "No content extractor for media files".to_string(),
+            None,
+        ),
+        Some(extractor) => step(
+            "extractor",
+            RuleAction::Full,
+            format!("Content is read by the {} extractor", extractor),
+            None,
+        ),
+    }
+}
+
+fn sqlite_index_state(db: &Connection, path: &Path) -> rusqlite::Result<IndexState> {
+    let row: Option<(i64, Option<bool>)> = db
+        .query_row(
+            "SELECT id, content_processed FROM files WHERE path = ?1",
+            params![path.to_string_lossy()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((file_id, content_processed)) = row else {
+        return Ok(IndexState::default());
+    };
+
+    // files_fts is an external-content table, so only a MATCH proves the row made it into the index
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let in_fts = db
+        .query_row(
+            "SELECT COUNT(*) FROM files_fts WHERE files_fts MATCH ?1 AND rowid = ?2",
+            params![format!("name : \"{}\"", name.replace('"', "\"\"")), file_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .is_ok_and(|count| count > 0);
+    let sqlite_chunks = db.query_row(
+        "SELECT COUNT(*) FROM file_chunks WHERE file_id = ?1",
+        params![file_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(IndexState {
+        file_id: Some(file_id),
+        content_processed,
+        in_fts,
+        sqlite_chunks,
+        ..IndexState::default()
+    })
+}
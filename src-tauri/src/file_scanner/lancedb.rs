@@ -1,16 +1,18 @@
 use anyhow::anyhow;
 use arrow_array::{
-    Array, Float32Array, FixedSizeListArray, Int32Array, RecordBatch, RecordBatchIterator,
-    StringArray,
+    Array, BooleanArray, Float32Array, FixedSizeListArray, Int32Array, RecordBatch,
+    RecordBatchIterator, StringArray,
 };
 use arrow::array::ArrayData;
+use arrow::compute::filter_record_batch;
 use arrow_schema::{Field, Schema, DataType};
+use futures::TryStreamExt;
 use lancedb::connect;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
 use lancedb::table::Table;
-use std::path::Path;
 use std::sync::Arc;
 
-use super::types::FileContent;
+use super::types::FolderDescription;
 use crate::database::lancedb_ops::get_app_data_dir;
 use crate::embed_and_store::normalize;
 
@@ -29,51 +31,61 @@ pub async fn get_lancedb_tables() -> anyhow::Result<(Table, Table)> {
     Ok((files_table, file_emb_table))
 }
 
-/// Inserts a batch of file metadata records into the `files` table.
-pub async fn insert_file_metadata_batch(
-    files_table: &Table,
-    files: &[FileContent],
-    vectors: Vec<Option<Vec<f32>>>,
-) -> anyhow::Result<Vec<i32>> {
+/// Opens the LanceDB `folder` table used by the semantic folder prong.
+pub async fn get_lancedb_folder_table() -> anyhow::Result<Table> {
+    let database_path = get_app_data_dir()
+        .ok_or_else(|| anyhow!("Could not get app data directory"))?
+        .join("my-lancedb");
+
+    let db = connect(database_path.to_str().unwrap()).execute().await?;
+    let folder_table = db.open_table("folder").execute().await?;
+    Ok(folder_table)
+}
+
+/// Replaces the embeddings of a batch of folders in the `folder` table.
+/// Rows are keyed by the SQLite `folders.id`, so re-embedding a folder overwrites its old vector.
+pub async fn upsert_folder_embedding_batch(
+    folder_table: &Table,
+    folders: &[FolderDescription],
+    vectors: &[Vec<f32>],
+) -> anyhow::Result<usize> {
     const VECTOR_DIM: i32 = 768;
-    if files.is_empty() {
-        return Ok(Vec::new());
+    let clean_data: Vec<_> = folders
+        .iter()
+        .zip(vectors.iter())
+        .filter(|(folder, vector)| vector.len() == VECTOR_DIM as usize && i32::try_from(folder.id).is_ok())
+        .collect();
+
+    if clean_data.is_empty() {
+        return Ok(0);
     }
 
-    let len = files.len();
+    let len = clean_data.len();
     let mut ids = Vec::with_capacity(len);
     let mut names = Vec::with_capacity(len);
-    let mut exts = Vec::with_capacity(len);
-    let mut paths = Vec::with_capacity(len);
-    let mut contents = Vec::with_capacity(len);
+    let mut created_dates = Vec::with_capacity(len);
     let mut all_vectors_flat = Vec::with_capacity(len * VECTOR_DIM as usize);
 
-    for (i, file) in files.iter().enumerate() {
-        let path_obj = Path::new(&file.path);
-        ids.push(rand::random::<i32>());
-        names.push(path_obj.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string());
-        exts.push(path_obj.extension().and_then(|e| e.to_str()).unwrap_or("").to_string());
-        paths.push(file.path.clone());
-        contents.push(file.content.clone());
-
-        let vector = vectors.get(i).and_then(|v| v.as_ref());
-        if let Some(vec) = vector {
-            all_vectors_flat.extend(normalize(vec.clone()));
-        } else {
-            all_vectors_flat.extend(vec![0.0; VECTOR_DIM as usize]);
-        }
+    for (folder, vector) in clean_data {
+        ids.push(folder.id as i32);
+        names.push(folder.name.clone());
+        created_dates.push(folder.created_at.clone());
+        all_vectors_flat.extend(normalize(vector.clone()));
     }
 
-    let schema = files_table.schema().await?;
+    let id_list = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+    folder_table.delete(&format!("id IN ({})", id_list)).await?;
 
-    // Manually construct FixedSizeListArray for compatibility
+    let schema = folder_table.schema().await?;
+
+    // Manually construct FixedSizeListArray
     let list_data_type = DataType::FixedSizeList(
         Arc::new(Field::new("item", DataType::Float32, true)),
         VECTOR_DIM,
     );
     let values_array_data = Float32Array::from(all_vectors_flat).into_data();
     let list_array_data = ArrayData::builder(list_data_type)
-        .len(len) // Number of lists/vectors
+        .len(len)
         .add_child_data(values_array_data)
         .build()?;
     let vector_array = Arc::new(FixedSizeListArray::from(list_array_data));
@@ -81,27 +93,26 @@ pub async fn insert_file_metadata_batch(
     let batch = RecordBatch::try_new(
         schema.clone(),
         vec![
-            Arc::new(Int32Array::from(ids.clone())),
+            Arc::new(Int32Array::from(ids)),
             Arc::new(StringArray::from(names)),
-            Arc::new(StringArray::from(exts)),
-            Arc::new(StringArray::from(paths)),
-            Arc::new(StringArray::from(contents)),
+            Arc::new(StringArray::from(created_dates)),
             vector_array,
         ],
     )?;
 
     let batches_to_add = RecordBatchIterator::new(vec![Ok(batch)], schema);
-    files_table.add(batches_to_add).execute().await?;
-    Ok(ids)
+    folder_table.add(batches_to_add).execute().await?;
+    Ok(len)
 }
 
 /// Inserts a batch of file embedding chunks into the `file_embeddings` table.
+/// Ids come from SQLite: `file_chunks.id` for the row and `files.id` for its file.
 pub async fn insert_file_embedding_batch(
     file_emb_table: &Table,
-    embedding_data: &[(i32, String, Vec<f32>)], // (file_id, chunk_text, vector)
+    embedding_data: &[(i32, i32, i32, String, Vec<f32>)], // (chunk_id, file_id, chunk_index, chunk_text, vector)
 ) -> anyhow::Result<()> {
     const VECTOR_DIM: i32 = 768;
-    let clean_data: Vec<_> = embedding_data.iter().filter(|d| !d.2.is_empty()).collect();
+    let clean_data: Vec<_> = embedding_data.iter().filter(|d| !d.4.is_empty()).collect();
 
     if clean_data.is_empty() {
         return Ok(());
@@ -114,10 +125,10 @@ pub async fn insert_file_embedding_batch(
     let mut chunk_texts = Vec::with_capacity(len);
     let mut all_vectors_flat = Vec::with_capacity(len * VECTOR_DIM as usize);
 
-    for (file_id, chunk_text, vector) in clean_data {
-        ids.push(rand::random::<i32>());
+    for (chunk_id, file_id, chunk_index, chunk_text, vector) in clean_data {
+        ids.push(*chunk_id);
         file_ids.push(*file_id);
-        chunk_nos.push(0); // Placeholder for chunk number
+        chunk_nos.push(*chunk_index);
         chunk_texts.push(chunk_text.clone());
         all_vectors_flat.extend(normalize(vector.clone()));
     }
@@ -152,102 +163,269 @@ pub async fn insert_file_embedding_batch(
     Ok(())
 }
 
+/// Rewrites the `path` of every row under `old_prefix` to start with `new_prefix` instead.
+/// Safe to run again, as long as `old_prefix` doesn't lie inside `new_prefix`.
+pub async fn remap_path_prefix_lancedb(
+    files_table: &Table,
+    old_prefix: &str,
+    new_prefix: &str,
+) -> anyhow::Result<()> {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    // substr is 1-based and counts characters, like the SQLite side of the remap
+    let old_len = old_prefix.chars().count();
+    let mut filter = format!("starts_with(path, {})", quote(old_prefix));
+    // Rows already under a nested new prefix have moved; skipping them makes a replay a no-op
+    if new_prefix.starts_with(old_prefix) {
+        filter.push_str(&format!(" AND NOT starts_with(path, {})", quote(new_prefix)));
+    }
+    files_table
+        .update()
+        .only_if(filter)
+        .column(
+            "path",
+            format!("concat({}, substr(path, {}))", quote(new_prefix), old_len + 1),
+        )
+        .execute()
+        .await?;
+    Ok(())
+}
 
-// --- DEPRECATED ---
-// The following functions are inefficient and should be replaced with their `_batch` counterparts.
-// They are kept for reference and to avoid breaking the build immediately.
-
-// Corrected function to save to LanceDB's `files` table
-pub async fn insert_file_metadata_lancedb(
+/// Counts the LanceDB rows stored for `path`: (rows in `files`, chunks in `file_embeddings`).
+pub async fn count_path_vectors_lancedb(
     files_table: &Table,
-    file: &FileContent,
-    vector: Option<Vec<f32>>,
-) -> anyhow::Result<i32> {
-    const VECTOR_DIM: i32 = 768;
+    file_emb_table: &Table,
+    path: &str,
+) -> anyhow::Result<(usize, usize)> {
+    let mut stream = files_table
+        .query()
+        .only_if(format!("path = '{}'", path.replace('\'', "''")))
+        .select(Select::columns(&["id"]))
+        .execute()
+        .await?;
+
+    let mut ids = Vec::new();
+    while let Some(batch) = stream.try_next().await? {
+        ids.extend(int32_column(&batch, "id")?.iter().flatten());
+    }
+    if ids.is_empty() {
+        return Ok((0, 0));
+    }
 
-    let path_obj = Path::new(&file.path);
-    let file_name = path_obj.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
-    let extension = path_obj.extension().and_then(|e| e.to_str()).unwrap_or("").to_string();
-    let id: i32 = rand::random();
+    let id_list = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+    let chunks = file_emb_table
+        .count_rows(Some(format!("file_id IN ({})", id_list)))
+        .await?;
+    Ok((ids.len(), chunks))
+}
 
-    let vector_values = if let Some(vec) = vector {
-        normalize(vec)
-    } else {
-        vec![0.0; VECTOR_DIM as usize]
-    };
+fn id_filter(ids: &[i64]) -> String {
+    let id_list = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+    format!("id IN ({})", id_list)
+}
 
-    let schema = files_table.schema().await?;
+/// Blanks the stored content of the given `files` rows; their vectors stay.
+pub async fn clear_content_lancedb(files_table: &Table, ids: &[i64]) -> anyhow::Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    files_table
+        .update()
+        .only_if(id_filter(ids))
+        .column("content", "''")
+        .column("content_hash", "CAST(NULL AS STRING)")
+        .execute()
+        .await?;
+    Ok(())
+}
 
-    // Manually construct FixedSizeListArray
-    let list_data_type = DataType::FixedSizeList(
-        Arc::new(Field::new("item", DataType::Float32, true)),
-        VECTOR_DIM,
-    );
-    let values_array_data = Float32Array::from(vector_values).into_data();
-    let list_array_data = ArrayData::builder(list_data_type)
-        .len(1) // Only one list
-        .add_child_data(values_array_data)
-        .build()?;
-    let vector_array = Arc::new(FixedSizeListArray::from(list_array_data));
+/// How many of `ids` the table has a row for.
+pub async fn count_rows_with_ids_lancedb(table: &Table, ids: &[i64]) -> anyhow::Result<usize> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    Ok(table.count_rows(Some(id_filter(ids))).await?)
+}
 
-    let batch = RecordBatch::try_new(
-        schema.clone(),
-        vec![
-            Arc::new(Int32Array::from(vec![id])),
-            Arc::new(StringArray::from(vec![file_name])),
-            Arc::new(StringArray::from(vec![extension])),
-            Arc::new(StringArray::from(vec![file.path.clone()])),
-            Arc::new(StringArray::from(vec![file.content.clone()])),
-            vector_array,
-        ],
-    )?;
+/// Deletes the rows with the given ids; ids without a row are ignored.
+pub async fn delete_rows_with_ids_lancedb(table: &Table, ids: &[i64]) -> anyhow::Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    table.delete(&id_filter(ids)).await?;
+    Ok(())
+}
 
-    let batches_to_add = RecordBatchIterator::new(vec![Ok(batch)], schema);
-    files_table.add(batches_to_add).execute().await?;
-    Ok(id)
+/// Every `(id, path)` in the `files` table.
+pub async fn load_file_ids_lancedb(files_table: &Table) -> anyhow::Result<Vec<(i32, String)>> {
+    let mut stream = files_table
+        .query()
+        .select(Select::columns(&["id", "path"]))
+        .execute()
+        .await?;
+
+    let mut rows = Vec::new();
+    while let Some(batch) = stream.try_next().await? {
+        let ids = int32_column(&batch, "id")?;
+        let paths = batch
+            .column_by_name("path")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .ok_or_else(|| anyhow!("'path' column missing from files table"))?;
+        for i in 0..batch.num_rows() {
+            rows.push((ids.value(i), paths.value(i).to_string()));
+        }
+    }
+    Ok(rows)
 }
 
-// Corrected function to save to LanceDB's `file_embeddings` table
-pub async fn insert_file_embedding_lancedb(
-    file_emb_table: &Table,
-    file_id: i32,
-    chunk_text: &str,
-    vector: Vec<f32>,
-) -> anyhow::Result<()> {
-    const VECTOR_DIM: i32 = 768;
+/// Every `(id, file_id)` in the `file_embeddings` table.
+pub async fn load_chunk_ids_lancedb(file_emb_table: &Table) -> anyhow::Result<Vec<(i32, i32)>> {
+    let mut stream = file_emb_table
+        .query()
+        .select(Select::columns(&["id", "file_id"]))
+        .execute()
+        .await?;
+
+    let mut rows = Vec::new();
+    while let Some(batch) = stream.try_next().await? {
+        let ids = int32_column(&batch, "id")?;
+        let file_ids = int32_column(&batch, "file_id")?;
+        for i in 0..batch.num_rows() {
+            rows.push((ids.value(i), file_ids.value(i)));
+        }
+    }
+    Ok(rows)
+}
 
-    if vector.is_empty() {
-        return Ok(());
+/// Why a stored vector can't be searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorFault {
+    Missing,
+    Zero,
+    WrongDimension,
+}
+
+/// Ids of the rows whose `column` vector is null, all zeros or not `expected_dim` long.
+pub async fn find_vector_faults_lancedb(
+    table: &Table,
+    column: &str,
+    expected_dim: usize,
+) -> anyhow::Result<Vec<(i32, VectorFault)>> {
+    let mut stream = table
+        .query()
+        .select(Select::columns(&["id", column]))
+        .execute()
+        .await?;
+
+    let mut faults = Vec::new();
+    while let Some(batch) = stream.try_next().await? {
+        let ids = int32_column(&batch, "id")?;
+        let vectors = batch
+            .column_by_name(column)
+            .and_then(|c| c.as_any().downcast_ref::<FixedSizeListArray>())
+            .ok_or_else(|| anyhow!("'{}' column missing from LanceDB result", column))?;
+        for i in 0..batch.num_rows() {
+            let fault = if vectors.is_null(i) {
+                Some(VectorFault::Missing)
+            } else if vectors.value_length() as usize != expected_dim {
+                Some(VectorFault::WrongDimension)
+            } else {
+                let values = vectors.value(i);
+                let values = values
+                    .as_any()
+                    .downcast_ref::<Float32Array>()
+                    .ok_or_else(|| anyhow!("'{}' is not a float vector", column))?;
+                values.values().iter().all(|v| *v == 0.0).then_some(VectorFault::Zero)
+            };
+            if let Some(fault) = fault {
+                faults.push((ids.value(i), fault));
+            }
+        }
     }
+    Ok(faults)
+}
 
-    let normalized_vec = normalize(vector);
-    
-    let schema = file_emb_table.schema().await?;
+async fn add_batches(table: &Table, batches: Vec<RecordBatch>) -> anyhow::Result<()> {
+    let Some(schema) = batches.first().map(|b| b.schema()) else {
+        return Ok(());
+    };
+    table
+        .add(RecordBatchIterator::new(batches.into_iter().map(Ok), schema))
+        .execute()
+        .await?;
+    Ok(())
+}
 
-    // Manually construct FixedSizeListArray
-    let list_data_type = DataType::FixedSizeList(
-        Arc::new(Field::new("item", DataType::Float32, true)),
-        VECTOR_DIM,
-    );
-    let values_array_data = Float32Array::from(normalized_vec).into_data();
-    let list_array_data = ArrayData::builder(list_data_type)
-        .len(1) // Only one list
-        .add_child_data(values_array_data)
-        .build()?;
-    let vector_array = Arc::new(FixedSizeListArray::from(list_array_data));
+/// Rewrites the int32 `columns` of the rows matching `filter`. `rekey` gets a row's current
+/// values of `columns` and of the `text_column` and returns the new values, or `None` to drop
+/// the row. Rows are read in full, deleted and added back, so a rewritten id is never caught
+/// by the filter halfway through; if the add fails the original rows are put back.
+/// Returns the number of rows changed or dropped.
+pub async fn rekey_rows_lancedb(
+    table: &Table,
+    filter: &str,
+    columns: &[&str],
+    text_column: Option<&str>,
+    mut rekey: impl FnMut(&[i32], Option<&str>) -> Option<Vec<i32>>,
+) -> anyhow::Result<usize> {
+    let original: Vec<RecordBatch> =
+        table.query().only_if(filter).execute().await?.try_collect().await?;
+
+    let mut rewritten = Vec::with_capacity(original.len());
+    let mut changed = 0;
+    for batch in &original {
+        let current: Vec<&Int32Array> = columns
+            .iter()
+            .map(|name| int32_column(batch, name))
+            .collect::<anyhow::Result<_>>()?;
+        let text = match text_column {
+            Some(name) => Some(
+                batch
+                    .column_by_name(name)
+                    .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+                    .ok_or_else(|| anyhow!("'{}' column missing from LanceDB result", name))?,
+            ),
+            None => None,
+        };
+
+        let mut keep = Vec::with_capacity(batch.num_rows());
+        let mut values: Vec<Vec<i32>> = vec![Vec::with_capacity(batch.num_rows()); columns.len()];
+        for row in 0..batch.num_rows() {
+            let old: Vec<i32> = current.iter().map(|c| c.value(row)).collect();
+            let new = rekey(&old, text.map(|t| t.value(row)));
+            changed += usize::from(new.as_ref() != Some(&old));
+            keep.push(new.is_some());
+            for (column, value) in values.iter_mut().zip(new.unwrap_or(old)) {
+                column.push(value);
+            }
+        }
 
-    let batch = RecordBatch::try_new(
-        schema.clone(),
-        vec![
-            Arc::new(Int32Array::from(vec![rand::random::<i32>()])),
-            Arc::new(Int32Array::from(vec![file_id])),
-            Arc::new(Int32Array::from(vec![0])), // Chunk number
-            Arc::new(StringArray::from(vec![chunk_text.to_string()])),
-            vector_array,
-        ],
-    )?;
+        let mut arrays = batch.columns().to_vec();
+        for (name, column) in columns.iter().zip(values) {
+            let index = batch.schema().index_of(name)?;
+            arrays[index] = Arc::new(Int32Array::from(column));
+        }
+        let batch = RecordBatch::try_new(batch.schema(), arrays)?;
+        rewritten.push(filter_record_batch(&batch, &BooleanArray::from(keep))?);
+    }
+    if changed == 0 {
+        return Ok(0);
+    }
 
-    let batches_to_add = RecordBatchIterator::new(vec![Ok(batch)], schema);
-    file_emb_table.add(batches_to_add).execute().await?;
-    Ok(())
-}
\ No newline at end of file
+    table.delete(filter).await?;
+    if let Err(e) = add_batches(table, rewritten).await {
+        add_batches(table, original).await?;
+        return Err(e);
+    }
+    Ok(changed)
+}
+
+pub(crate) fn int32_column<'a>(
+    batch: &'a RecordBatch,
+    name: &str,
+) -> anyhow::Result<&'a Int32Array> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<Int32Array>())
+        .ok_or_else(|| anyhow!("'{}' column missing from LanceDB result", name))
+}
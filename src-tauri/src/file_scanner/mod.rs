@@ -2,41 +2,102 @@
 pub mod content;
 pub mod db;
 pub mod discovery;
+pub mod explain;
 pub mod pipeline;
+pub mod rule_engine;
 pub mod scoring;
 pub mod types;
 pub mod utils;
 pub mod lancedb;
+pub mod volume_registry;
+pub mod volumes;
+pub mod walker;
 
 use crate::embed_and_store;
-use db::insert_folder_metadata;
-use discovery::{find_all_drive_files, should_exclude_path};
-use pipeline::{build_embedding_chunks, prepare_files_for_processing, store_results};
+use db::insert_walked_folders;
+use crate::database::lancedb_maintenance::{run_lancedb_maintenance, LARGE_SCAN_FILES};
+use crate::database::rules::RuleAction;
+use crate::database::settings::load_settings;
+use crate::database::storage::enforce_storage_budget;
+use discovery::find_all_drive_files;
+use pipeline::{
+    build_embedding_chunks, build_folder_descriptions, prepare_files_for_processing,
+    store_folder_embeddings, store_results,
+};
+use rule_engine::RuleSet;
 use rusqlite::Connection;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{AppHandle, Emitter, Manager};
 use tokio::runtime::Runtime;
 use types::ScannedFile;
 use utils::emit_scan_progress;
+use volume_registry::{load_tracked_volumes, policy_for_path, VolumePolicy};
 use walkdir::WalkDir;
+use walker::IgnoreStack;
 
 static IS_SCANNING: AtomicBool = AtomicBool::new(false);
 
 /// Scans files based on Phase 1 rules (included paths, extensions).
+/// A non-empty `dir` limits the scan to the included paths inside it.
 pub fn scan_and_store_files(
-    db: &Connection,
-    _dir: &str,
+    dir: &str,
     max_chars: Option<usize>,
     _max_file_size: Option<u64>,
     app: tauri::AppHandle,
 ) -> Result<usize, String> {
-    scan_and_store_files_with_mode(db, _dir, max_chars, _max_file_size, app, false)
+    scan_and_store_files_with_mode(dir, max_chars, _max_file_size, app, false)
+}
+
+/// Indexes one volume: its Phase 1 included paths, then its metadata if Phase 2 covers it.
+pub fn scan_volume(mount_point: &str, app: AppHandle) -> Result<usize, String> {
+    let mut processed =
+        scan_and_store_files_with_mode(mount_point, None, None, app.clone(), false)?;
+    let selected = volumes::selected_volume_paths(&crate::database::get_read_connection()?);
+    if selected.iter().any(|p| p == mount_point) {
+        processed += scan_and_store_files_with_mode(mount_point, None, None, app, true)?;
+    }
+    Ok(processed)
+}
+
+/// Narrows scan roots to `root`: roots inside it are kept, and a root containing it is
+/// replaced by `root` itself. An empty `root` keeps everything.
+pub fn restrict_to_root(base_paths: Vec<String>, root: &str) -> Vec<String> {
+    if root.is_empty() {
+        return base_paths;
+    }
+    let mut restricted: Vec<String> = Vec::new();
+    for base_path in base_paths {
+        let narrowed = if Path::new(&base_path).starts_with(root) {
+            base_path
+        } else if Path::new(root).starts_with(&base_path) {
+            root.to_string()
+        } else {
+            continue;
+        };
+        if !restricted.contains(&narrowed) {
+            restricted.push(narrowed);
+        }
+    }
+    restricted
+}
+
+/// Phase 1 roots to walk: the included paths, minus those on volumes set to never index.
+fn phase1_base_paths(db: &Connection, dir: &str) -> Result<Vec<String>, String> {
+    let base_paths: Vec<String> = crate::database::rules::get_include_walk_roots_sync(db)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+    let tracked = load_tracked_volumes(db).map_err(|e| e.to_string())?;
+    Ok(restrict_to_root(base_paths, dir)
+        .into_iter()
+        .filter(|p| policy_for_path(&tracked, p) != VolumePolicy::Never)
+        .collect())
 }
 
 /// Scans all drives for metadata (Phase 2).
-pub fn scan_drives_metadata_only(db: &Connection, app: &AppHandle) -> Result<usize, String> {
-    scan_and_store_files_with_mode(db, "", None, None, app.clone(), true)
+pub fn scan_drives_metadata_only(app: &AppHandle) -> Result<usize, String> {
+    scan_and_store_files_with_mode("", None, None, app.clone(), true)
 }
 
 /// Discovers files based on Phase 1 rules without indexing, for UI display.
@@ -44,14 +105,12 @@ pub fn discover_files_with_rules(
     db: &Connection,
     app: &AppHandle,
 ) -> Result<Vec<ScannedFile>, String> {
-    let base_paths: Vec<String> = crate::database::rules::get_included_paths_sync(db)
-        .map_err(|e| e.to_string())?
-        .into_iter()
-        .collect();
-    let exclude_folders: Vec<String> = crate::database::rules::get_excluded_folder_sync(db)
+    let base_paths: Vec<String> = crate::database::rules::get_include_walk_roots_sync(db)
         .map_err(|e| e.to_string())?
         .into_iter()
         .collect();
+    let rules = RuleSet::load(db)?;
+    let settings = load_settings(db);
 
     let mut found_files = Vec::new();
     for base_path in &base_paths {
@@ -60,16 +119,20 @@ pub fn discover_files_with_rules(
             continue;
         }
 
-        let walker = WalkDir::new(path_buf).into_iter();
+        let mut ignores = IgnoreStack::new(&path_buf, settings.respect_ignore_files);
+        let walker = WalkDir::new(path_buf)
+            .max_depth(settings.max_index_depth)
+            .into_iter()
+            .filter_entry(|e| {
+                !(e.file_type().is_dir() && rules.skips_subtree(e.path())) && !ignores.is_ignored(e)
+            });
         for entry_result in walker.filter_map(|e| e.ok()) {
             if entry_result.file_type().is_file() {
-                if !should_exclude_path(entry_result.path(), &exclude_folders, &[], None) {
-                    let path_str = entry_result.path().to_string_lossy().into_owned();
-                    let (should_crawl, _) =
-                        scoring::check_phase1_rules(db, &path_str)?;
+                let action = rules.evaluate(entry_result.path(), false).action;
+                if action != RuleAction::Skip {
                     found_files.push(ScannedFile {
-                        path: path_str,
-                        content_processed: should_crawl,
+                        path: entry_result.path().to_string_lossy().into_owned(),
+                        content_processed: action == RuleAction::Full,
                     });
                 }
             }
@@ -78,43 +141,36 @@ pub fn discover_files_with_rules(
     Ok(found_files)
 }
 
-/// Main orchestrator for scanning files.
+/// Main orchestrator for scanning files. Reads go through a pooled reader and the writer is
+/// only taken for each write, so searches and settings changes are not blocked by a scan.
 pub fn scan_and_store_files_with_mode(
-    db: &Connection,
-    _dir: &str,
+    dir: &str,
     max_chars: Option<usize>,
     _max_file_size: Option<u64>,
     app: tauri::AppHandle,
     is_phase2: bool,
 ) -> Result<usize, String> {
-    if IS_SCANNING
-        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-        .is_err()
-    {
+    let Some(_guard) = claim_scan_slot() else {
         emit_scan_progress(&app, 0, 0, "A scan is already in progress.", "error");
         return Err("A scan is already in progress.".to_string());
-    }
-    let _guard = ScanGuard;
+    };
 
     let rt = Runtime::new().map_err(|e| e.to_string())?;
+    let reader = crate::database::get_read_connection()?;
+    let db: &Connection = &reader;
     emit_scan_progress(&app, 0, 0, "Scanning for files and folders...", "scanning");
     println!("scanning for files and folders");
 
     let paths: Vec<String> = if is_phase2 {
-        let scanned_files = find_all_drive_files(db, &app)?;
+        let scanned_files = find_all_drive_files(db, &app, dir)?;
         scanned_files.iter().map(|sf| sf.path.clone()).collect()
     } else {
-        let base_paths: Vec<String> = crate::database::rules::get_included_paths_sync(db)
-            .map_err(|e| e.to_string())?
-            .into_iter()
-            .collect();
-        let exclude_folders: Vec<String> = crate::database::rules::get_excluded_folder_sync(db)
-            .map_err(|e| e.to_string())?
-            .into_iter()
-            .collect();
+        let base_paths = phase1_base_paths(db, dir)?;
+        let rules = RuleSet::load(db)?;
+        let settings = load_settings(db);
 
         let mut found_files = Vec::new();
-        let tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
+        let mut folders = Vec::new();
 
         for base_path in &base_paths {
             let path_buf = PathBuf::from(base_path);
@@ -122,41 +178,40 @@ pub fn scan_and_store_files_with_mode(
                 continue;
             }
 
-            let mut walker = WalkDir::new(path_buf).into_iter();
+            let mut ignores = IgnoreStack::new(&path_buf, settings.respect_ignore_files);
+            let mut walker = WalkDir::new(path_buf)
+                .max_depth(settings.max_index_depth)
+                .into_iter();
             'walker_loop: while let Some(entry_result) = walker.next() {
                 let entry = match entry_result {
                     Ok(entry) => entry,
                     Err(_) => continue,
                 };
 
-                if entry.file_type().is_dir() {
-                    if let Err(e) = insert_folder_metadata(&tx, entry.path()) {
-                        eprintln!("Failed to store folder {}: {}", entry.path().display(), e);
+                // Ignored folders are neither recorded nor descended into
+                if ignores.is_ignored(&entry) {
+                    if entry.file_type().is_dir() {
+                        walker.skip_current_dir();
                     }
+                    continue 'walker_loop;
+                }
 
-                    if should_exclude_path(
-                        entry.path(),
-                        &exclude_folders,
-                        &[],
-                        None,
-                    ) {
+                if entry.file_type().is_dir() {
+                    folders.push(entry.path().to_path_buf());
+
+                    if rules.skips_subtree(entry.path()) {
                         walker.skip_current_dir();
                         continue 'walker_loop;
                     }
                 } else if entry.file_type().is_file() {
-                    if should_exclude_path(
-                        entry.path(),
-                        &exclude_folders,
-                        &[],
-                        None,
-                    ) {
+                    if rules.is_skipped(entry.path(), false) {
                         continue 'walker_loop;
                     }
                     found_files.push(entry.path().to_string_lossy().into_owned());
                 }
             }
         }
-        tx.commit().map_err(|e| e.to_string())?;
+        insert_walked_folders(&folders)?;
         found_files
     };
 
@@ -217,7 +272,16 @@ pub fn scan_and_store_files_with_mode(
         .map_err(|e| e.to_string())?
     };
 
-    let inserted_count = rt.block_on(store_results(db, &new_files, &chunks, &embeddings, &file_chunk_map, &app)).map_err(|e| e.to_string())?;
+    let inserted_count = rt
+        .block_on(store_results(&new_files, &chunks, &embeddings, &file_chunk_map, &app))
+        .map_err(|e| e.to_string())?;
+    // Large scans get the full maintenance pass once folders are embedded too
+    let large_scan = inserted_count >= LARGE_SCAN_FILES;
+    if !large_scan {
+        if let Err(e) = rt.block_on(crate::database::lancedb_ops::ensure_lancedb_indexes()) {
+            eprintln!("Failed to update LanceDB indexes: {}", e);
+        }
+    }
 
     emit_scan_progress(
         &app,
@@ -226,7 +290,34 @@ pub fn scan_and_store_files_with_mode(
         "Calculating folder scores...",
         "scoring_folders",
     );
-    crate::database::update_folder_scores(db).map_err(|e| e.to_string())?;
+    crate::database::update_folder_scores_for_paths(
+        &crate::database::get_connection(),
+        new_files.iter().map(|f| f.path.as_str()),
+    )
+    .map_err(|e| e.to_string())?;
+
+    emit_scan_progress(&app, 0, 0, "Embedding folders...", "embedding_folders");
+    if let Err(e) = embed_touched_folders(db, &new_files, &rt, &app) {
+        // Folder embeddings only feed the semantic folder prong, so a failure here
+        // should not fail a scan whose files were already stored.
+        eprintln!("Failed to embed folders: {}", e);
+    }
+
+    // Budget first, so maintenance also reclaims what degrading frees
+    let degraded = match rt.block_on(enforce_storage_budget()) {
+        Ok(report) => report.files_degraded.len(),
+        Err(e) => {
+            eprintln!("Failed to apply the index size budget: {}", e);
+            0
+        }
+    };
+
+    if large_scan || degraded > 0 {
+        emit_scan_progress(&app, 0, 0, "Optimizing index...", "optimizing");
+        if let Err(e) = rt.block_on(run_lancedb_maintenance()) {
+            eprintln!("LanceDB maintenance failed: {}", e);
+        }
+    }
 
     emit_scan_progress(
         &app,
@@ -239,7 +330,48 @@ pub fn scan_and_store_files_with_mode(
     Ok(inserted_count)
 }
 
-struct ScanGuard;
+/// Embeds the folders containing newly indexed files into the LanceDB `folder` table.
+fn embed_touched_folders(
+    db: &Connection,
+    new_files: &[types::FileContent],
+    rt: &Runtime,
+    app: &AppHandle,
+) -> Result<usize, String> {
+    let folders = build_folder_descriptions(db, new_files)?;
+    if folders.is_empty() {
+        return Ok(0);
+    }
+    println!("Embedding {} folders", folders.len());
+
+    let descriptions: Vec<String> = folders.iter().map(|f| f.description.clone()).collect();
+    let app_clone = app.clone();
+    let embeddings =
+        embed_and_store::get_batch_embeddings_with_progress(&descriptions, move |current, total| {
+            app_clone
+                .emit(
+                    "scan_progress",
+                    serde_json::json!({
+                        "current": current, "total": total, "stage": "embedding_folders",
+                        "current_file": format!("Processing folder {} of {}", current, total)
+                    }),
+                )
+                .ok();
+        })
+        .map_err(|e| e.to_string())?;
+
+    rt.block_on(store_folder_embeddings(&folders, &embeddings))
+}
+
+/// Claims the single scan slot until the guard drops. Index export and import claim it too,
+/// so they never copy or replace the stores mid-scan.
+pub fn claim_scan_slot() -> Option<ScanGuard> {
+    IS_SCANNING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .ok()
+        .map(|_| ScanGuard(()))
+}
+
+pub struct ScanGuard(());
 impl Drop for ScanGuard {
     fn drop(&mut self) {
         IS_SCANNING.store(false, Ordering::SeqCst);
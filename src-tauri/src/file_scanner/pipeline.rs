@@ -1,17 +1,33 @@
 // The core scanning pipeline stages
-use super::content::{chunk_text, create_metadata_string, read_file_content_with_category};
-use super::db::{file_exists, insert_file_embedding, insert_file_metadata};
-use super::lancedb::{insert_file_metadata_lancedb, insert_file_embedding_lancedb};
-use super::scoring::{calculate_file_score, check_phase1_rules};
-use super::types::{FileCategory, FileContent};
+use super::content::{
+    chunk_text, create_folder_metadata_string, create_metadata_string,
+    read_file_content_with_category,
+};
+use super::db::{
+    content_hash, file_exists, insert_file_chunk, insert_file_metadata, mark_vector_indexed,
+};
+use super::lancedb::{
+    get_lancedb_folder_table, get_lancedb_tables, insert_file_embedding_batch,
+    upsert_folder_embedding_batch,
+};
+use super::rule_engine::RuleSet;
+use super::scoring::{calculate_file_score, load_scoring_profile};
+use super::types::{FileCategory, FileContent, FolderDescription};
 use super::utils::emit_scan_progress;
-use rusqlite::Connection;
+use super::volume_registry::{load_tracked_volumes, policy_for_path, VolumePolicy};
+use crate::database::journal::{self, JournalOp};
+use crate::database::rules::RuleAction;
+use crate::database::settings::load_settings;
+use crate::database::vector_index::{IndexedVector, LanceVectorIndex, VectorIndex};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeSet;
 use std::fs;
 use std::path::Path;
 use tauri::AppHandle;
 use tokio::runtime::Runtime;
 
 const BATCH_SIZE: usize = 1000;
+const MAX_FOLDER_CHILDREN: usize = 40;
 
 /// Stage 1: Filters a list of paths for new files and reads their content based on scan rules.
 pub fn prepare_files_for_processing(
@@ -23,6 +39,10 @@ pub fn prepare_files_for_processing(
     rt: &Runtime,
     app: &AppHandle,
 ) -> Result<Vec<FileContent>, String> {
+    let scoring_profile = load_scoring_profile(db);
+    let volumes = load_tracked_volumes(db).map_err(|e| e.to_string())?;
+    let rules = RuleSet::load(db)?;
+    let settings = load_settings(db);
     let mut new_files_to_process = Vec::new();
     for (i, path) in paths.iter().enumerate() {
         emit_scan_progress(
@@ -37,22 +57,40 @@ pub fn prepare_files_for_processing(
             continue;
         }
 
-        let score = match fs::metadata(path) {
-            Ok(metadata) => calculate_file_score(path, &metadata, included_paths),
-            Err(_) => 0.0,
+        // Nested mounts can put a "never" volume inside an included path
+        let policy = policy_for_path(&volumes, path);
+        if policy == VolumePolicy::Never {
+            continue;
+        }
+
+        let (score, file_size) = match fs::metadata(path) {
+            Ok(metadata) => (
+                calculate_file_score(
+                    &scoring_profile,
+                    path,
+                    metadata.modified().ok(),
+                    metadata.len(),
+                    included_paths,
+                ),
+                metadata.len(),
+            ),
+            Err(_) => (0.0, 0),
         };
 
-        let (should_crawl_content, category, content) = if is_phase2 {
+        let metadata_only = is_phase2 || policy == VolumePolicy::Metadata;
+        let (should_crawl_content, category, content) = if metadata_only {
             let path_obj = Path::new(path);
             let extension = path_obj.extension().and_then(|e| e.to_str()).unwrap_or("");
             let category = FileCategory::from_extension(extension);
             (false, category, String::new())
         } else {
-            let (should_crawl, _) = check_phase1_rules(db, path)?;
+            let should_crawl = rules.evaluate(Path::new(path), false).action == RuleAction::Full
+                && file_size <= settings.max_file_size_bytes();
             let (content, category) = match rt.block_on(read_file_content_with_category(
                 path,
                 max_chars,
                 should_crawl,
+                &settings,
             )) {
                 Ok(result) => result,
                 Err(e) => {
@@ -104,12 +142,11 @@ pub fn build_embedding_chunks(files: &[FileContent]) -> (Vec<String>, Vec<(Strin
     (all_chunks, file_chunk_map)
 }
 
-/// Stage 3: Stores file metadata and embeddings in the database in batches.
-use super::lancedb::{get_lancedb_tables, insert_file_embedding_batch, insert_file_metadata_batch};
-
-/// Stage 3: Stores file metadata and embeddings in the database in batches.
+/// Stage 3: Stores file metadata and embeddings in SQLite and LanceDB.
+/// SQLite assigns the ids (`files.id`, `file_chunks.id`) and the LanceDB rows reuse them, so
+/// the two stores join on id. The SQLite rows are committed only after LanceDB took the batch.
+/// The writer connection is only held while SQLite is written, never across LanceDB I/O.
 pub async fn store_results(
-    db: &Connection,
     files: &[FileContent],
     all_chunks: &[String], // This is required to get the text for embeddings
     embeddings: &[Vec<f32>],
@@ -117,11 +154,11 @@ pub async fn store_results(
     app: &AppHandle,
 ) -> Result<usize, String> {
     // 1. Open LanceDB tables ONCE.
-    let (files_table, file_emb_table) = get_lancedb_tables()
+    let vector_index = LanceVectorIndex::open().await?;
+    let (_, file_emb_table) = get_lancedb_tables()
         .await
         .map_err(|e| format!("Failed to open LanceDB tables: {}", e))?;
 
-    // --- Batch insert file metadata ---
     emit_scan_progress(
         app,
         1,
@@ -130,57 +167,228 @@ pub async fn store_results(
         "storing file metadata",
     );
 
-    // Associate the correct top-level embedding (usually for metadata) with each file.
-    let file_vectors: Vec<Option<Vec<f32>>> = files
-        .iter()
-        .map(|file| {
-            file_chunk_map
+    let mut stored_files: Vec<IndexedVector> = Vec::new();
+    let mut embedding_data_batch = Vec::new();
+    let mut file_ids = Vec::new();
+    let mut chunk_ids = Vec::new();
+
+    // The writer is held for the SQLite half only; LanceDB below runs without it
+    let journal_id = {
+        let db = crate::database::get_connection();
+        let mut tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
+        for file in files {
+            let chunk_indices = file_chunk_map
                 .iter()
                 .find(|(path, _)| path == &file.path)
-                .and_then(|(_, indices)| indices.get(0)) // Get first chunk index (metadata)
-                .and_then(|&idx| embeddings.get(idx).cloned())
-        })
-        .collect();
+                .map_or(&[][..], |(_, indices)| indices.as_slice());
+            // (chunk index, text, vector) of the chunks that were embedded
+            let chunks: Vec<(usize, &String, &Vec<f32>)> = chunk_indices
+                .iter()
+                .enumerate()
+                .filter_map(|(chunk_index, &idx)| {
+                    Some((chunk_index, all_chunks.get(idx)?, embeddings.get(idx)?))
+                })
+                .filter(|(_, _, vector)| !vector.is_empty())
+                .collect();
 
-    // Use the batch metadata insertion function
-    let inserted_file_ids = insert_file_metadata_batch(&files_table, files, file_vectors)
-        .await
-        .map_err(|e| format!("Batch metadata insert failed: {}", e))?;
+            // A file that fails leaves nothing behind: dropping the savepoint rolls back its rows
+            let savepoint = tx.savepoint().map_err(|e| e.to_string())?;
+            let (file_id, file_chunk_ids) = match insert_file_rows(&savepoint, file, &chunks) {
+                Ok(ids) => ids,
+                Err(e) => {
+                    eprintln!("Failed to store file {}: {}", file.path, e);
+                    continue;
+                }
+            };
+            savepoint.commit().map_err(|e| e.to_string())?;
+
+            let lance_file_id = lancedb_id(file_id)?;
+            let chunk_rows = file_chunk_ids.iter().zip(&chunks);
+            for (&chunk_id, (chunk_index, chunk_text, vector)) in chunk_rows {
+                embedding_data_batch.push((
+                    lancedb_id(chunk_id)?,
+                    lance_file_id,
+                    *chunk_index as i32,
+                    (*chunk_text).clone(),
+                    (*vector).clone(),
+                ));
+            }
+            chunk_ids.extend(file_chunk_ids);
+            file_ids.push(file_id);
+            stored_files.push(IndexedVector {
+                file_id,
+                path: file.path.clone(),
+                content: file.content.clone(),
+                content_hash: content_hash(file),
+                // The first chunk is the metadata string; its vector stands for the whole file
+                vector: chunk_indices.first().and_then(|&idx| embeddings.get(idx).cloned()),
+            });
+        }
+
+        if stored_files.is_empty() {
+            return Ok(0);
+        }
+
+        // SQLite commits first, with a journal entry that lets a crash before LanceDB is done
+        // be undone
+        let op = JournalOp::InsertFiles {
+            file_ids: file_ids.clone(),
+            chunk_ids: chunk_ids.clone(),
+        };
+        let journal_id = journal::begin(&tx, &op).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        journal_id
+    };
+
+    let lancedb_result = async {
+        vector_index.insert(&stored_files).await?;
+
+        emit_scan_progress(
+            app,
+            2,
+            3,
+            "".to_string(),
+            "storing content embeddings",
+        );
+
+        if !embedding_data_batch.is_empty() {
+            insert_file_embedding_batch(&file_emb_table, &embedding_data_batch)
+                .await
+                .map_err(|e| format!("Batch embedding insert failed: {}", e))?;
+        }
+        Ok::<_, String>(())
+    }
+    .await;
+
+    if let Err(e) = lancedb_result {
+        let rollback = journal::roll_back_insert(journal_id, &file_ids, &chunk_ids).await;
+        if let Err(rollback_error) = rollback {
+            // Left pending; startup recovery retries the rollback
+            eprintln!("Failed to roll back batch {}: {}", journal_id, rollback_error);
+        }
+        return Err(e);
+    }
+    journal::complete(&crate::database::get_connection(), journal_id).map_err(|e| e.to_string())?;
+    emit_scan_progress(app, 3, 3, "".to_string(), "storage complete");
+
+    Ok(stored_files.len())
+}
 
-    // Create a map from path to new LanceDB file ID for associating embeddings
-    let path_to_new_id: std::collections::HashMap<_, _> = files
+/// Inserts one file and its chunk rows. Returns the file id and the chunk ids, in the order
+/// of `chunks`; both are checked to fit LanceDB ids.
+fn insert_file_rows(
+    conn: &Connection,
+    file: &FileContent,
+    chunks: &[(usize, &String, &Vec<f32>)],
+) -> Result<(i64, Vec<i64>), String> {
+    let file_id = insert_file_metadata(conn, file).map_err(|e| e.to_string())?;
+    lancedb_id(file_id)?;
+    let mut chunk_ids = Vec::with_capacity(chunks.len());
+    for (chunk_index, _, _) in chunks {
+        let chunk_id = insert_file_chunk(conn, file_id, *chunk_index).map_err(|e| e.to_string())?;
+        lancedb_id(chunk_id)?;
+        chunk_ids.push(chunk_id);
+    }
+    if !chunk_ids.is_empty() {
+        mark_vector_indexed(conn, file_id).map_err(|e| e.to_string())?;
+    }
+    Ok((file_id, chunk_ids))
+}
+
+/// LanceDB ids are 32-bit; SQLite rowids only outgrow that on a database with billions of rows.
+pub(crate) fn lancedb_id(id: i64) -> Result<i32, String> {
+    i32::try_from(id).map_err(|_| format!("Id {} is outside the LanceDB id range", id))
+}
+
+
+/// Stage 4: Builds semantic descriptions for the folders that contain the given files.
+/// Only folders already recorded in SQLite during the walk are described.
+pub fn build_folder_descriptions(
+    db: &Connection,
+    files: &[FileContent],
+) -> Result<Vec<FolderDescription>, String> {
+    let touched_folders: BTreeSet<String> = files
         .iter()
-        .map(|f| f.path.clone())
-        .zip(inserted_file_ids)
+        .filter_map(|f| Path::new(&f.path).parent())
+        .map(|p| p.to_string_lossy().into_owned())
         .collect();
 
-    // --- Batch insert file embeddings ---
-    emit_scan_progress(
-        app,
-        2,
-        3,
-        "".to_string(),
-        "storing content embeddings",
-    );
+    let mut stmt = db
+        .prepare("SELECT id, name, created_at FROM folders WHERE path = ?1")
+        .map_err(|e| e.to_string())?;
 
-    let mut embedding_data_batch = Vec::new();
-    for (path, chunk_indices) in file_chunk_map {
-        if let Some(file_id) = path_to_new_id.get(path) {
-            for &chunk_idx in chunk_indices {
-                if let (Some(chunk_text), Some(vector)) = (all_chunks.get(chunk_idx), embeddings.get(chunk_idx)) {
-                    embedding_data_batch.push((*file_id, chunk_text.clone(), vector.clone()));
-                }
+    let mut descriptions = Vec::new();
+    for folder_path in touched_folders {
+        let row = stmt
+            .query_row(params![&folder_path], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .optional()
+            .map_err(|e| e.to_string())?;
+
+        let Some((id, name, created_at)) = row else {
+            continue;
+        };
+
+        let (child_files, child_folders) = list_folder_children(Path::new(&folder_path));
+        let description =
+            create_folder_metadata_string(Path::new(&folder_path), &child_files, &child_folders);
+
+        descriptions.push(FolderDescription {
+            id,
+            name,
+            path: folder_path,
+            created_at,
+            description,
+        });
+    }
+    Ok(descriptions)
+}
+
+/// Returns the (file names, subfolder names) directly inside a folder, capped for embedding.
+fn list_folder_children(path: &Path) -> (Vec<String>, Vec<String>) {
+    let mut child_files = Vec::new();
+    let mut child_folders = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(ft) if ft.is_dir() => child_folders.push(name),
+                Ok(ft) if ft.is_file() => child_files.push(name),
+                _ => {}
             }
         }
     }
 
-    if !embedding_data_batch.is_empty() {
-        insert_file_embedding_batch(&file_emb_table, &embedding_data_batch)
-            .await
-            .map_err(|e| format!("Batch embedding insert failed: {}", e))?;
+    child_files.sort();
+    child_folders.sort();
+    child_files.truncate(MAX_FOLDER_CHILDREN);
+    child_folders.truncate(MAX_FOLDER_CHILDREN);
+    (child_files, child_folders)
+}
+
+/// Stage 5: Stores folder embeddings in the LanceDB `folder` table.
+pub async fn store_folder_embeddings(
+    folders: &[FolderDescription],
+    embeddings: &[Vec<f32>],
+) -> Result<usize, String> {
+    if folders.is_empty() {
+        return Ok(0);
     }
 
-    emit_scan_progress(app, 3, 3, "".to_string(), "storage complete");
+    let folder_table = get_lancedb_folder_table()
+        .await
+        .map_err(|e| format!("Failed to open LanceDB folder table: {}", e))?;
 
-    Ok(files.len())
+    upsert_folder_embedding_batch(&folder_table, folders, embeddings)
+        .await
+        .map_err(|e| format!("Folder embedding insert failed: {}", e))
 }
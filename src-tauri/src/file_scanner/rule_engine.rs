@@ -0,0 +1,444 @@
+// The single place scan rules are evaluated, for discovery walks, Phase 1 content
+// decisions and Phase 2 metadata walks.
+//
+// Every matching rule proposes an action; the winner is decided by, in order:
+//   1. priority (higher wins)
+//   2. specificity: rules on the file itself (filename, extension) beat folder and path
+//      rules, and a match on a deeper folder beats a match on a shallower one
+//   3. severity: skip, then metadata, then full
+//   4. age (the older rule wins)
+// Nothing matching means metadata only. Include-extension rules without an explicit action
+// are an allow-list rather than competitors: when any exist, a full-content outcome for a
+// file with another extension is lowered to metadata.
+use crate::database::rules::{
+    load_scan_rules_sync, MatchType, RuleAction, RuleCategory, RuleType, ScanRule,
+};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use rusqlite::Connection;
+use std::cmp::Reverse;
+use std::path::Path;
+
+// Paths compare case-insensitively only where the filesystem usually does
+const CASE_INSENSITIVE_PATHS: bool = cfg!(windows);
+
+enum Pattern {
+    Exact(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+fn compile_pattern(match_type: MatchType, category: RuleCategory, pattern: &str) -> Result<Pattern, String> {
+    let pattern = pattern.trim();
+    let is_path = category == RuleCategory::Path;
+    let case_insensitive = !is_path || CASE_INSENSITIVE_PATHS;
+    match match_type {
+        MatchType::Exact => Ok(Pattern::Exact(match category {
+            RuleCategory::Extension => pattern.trim_start_matches('.').to_string(),
+            _ => pattern.to_string(),
+        })),
+        MatchType::Glob => GlobBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .literal_separator(is_path)
+            .build()
+            .map(|glob| Pattern::Glob(glob.compile_matcher()))
+            .map_err(|e| format!("Invalid glob '{}': {}", pattern, e)),
+        MatchType::Regex => RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map(Pattern::Regex)
+            .map_err(|e| format!("Invalid regex '{}': {}", pattern, e)),
+    }
+}
+
+/// Checks that a rule pattern compiles before it is stored.
+pub fn validate_pattern(match_type: MatchType, category: RuleCategory, pattern: &str) -> Result<(), String> {
+    compile_pattern(match_type, category, pattern).map(|_| ())
+}
+
+impl Pattern {
+    /// Names (folder, file name, extension) compare case-insensitively.
+    fn matches_name(&self, name: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => exact.eq_ignore_ascii_case(name),
+            Pattern::Glob(glob) => glob.is_match(name),
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+
+    fn matches_path(&self, path: &Path) -> bool {
+        match self {
+            Pattern::Exact(exact) => path == Path::new(exact),
+            Pattern::Glob(glob) => glob.is_match(path),
+            Pattern::Regex(regex) => regex.is_match(&path.to_string_lossy()),
+        }
+    }
+}
+
+struct CompiledRule {
+    rule: ScanRule,
+    pattern: Pattern,
+    // Built-in hidden-entry rule: also applies to a dot-named file itself
+    matches_files_by_name: bool,
+}
+
+impl CompiledRule {
+    /// Specificity of the match, or `None` when the rule doesn't apply to `path`.
+    fn specificity(&self, path: &Path, is_dir: bool) -> Option<usize> {
+        let depth = path.components().count();
+        match self.rule.category {
+            RuleCategory::Extension => {
+                let extension = path.extension()?.to_str()?;
+                (!is_dir && self.pattern.matches_name(extension)).then_some(depth + 1)
+            }
+            RuleCategory::Filename => {
+                let name = path.file_name()?.to_str()?;
+                (!is_dir && self.pattern.matches_name(name)).then_some(depth + 1)
+            }
+            RuleCategory::Path | RuleCategory::Folder => {
+                // The deepest matching ancestor decides; the rule reaches `depth_limit` levels below it
+                let limit = self.rule.depth_limit().map(|d| d as usize);
+                for (levels_below, ancestor) in path.ancestors().enumerate() {
+                    if levels_below == 0 && !is_dir && !self.matches_files_by_name {
+                        continue;
+                    }
+                    let matched = match self.rule.category {
+                        RuleCategory::Path => self.pattern.matches_path(ancestor),
+                        _ => ancestor
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|name| self.pattern.matches_name(name)),
+                    };
+                    if matched {
+                        return limit
+                            .is_none_or(|limit| levels_below <= limit)
+                            .then(|| ancestor.components().count());
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+/// The outcome for one path, with the rule that decided it.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RuleDecision {
+    pub action: RuleAction,
+    /// `None` when no rule matched and the default applied
+    pub rule: Option<ScanRule>,
+    /// Full content was lowered to metadata because the extension isn't on the allow-list
+    pub limited_by_extension_allow_list: bool,
+}
+
+/// All scan rules, compiled once per walk.
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+    content_extensions: Vec<CompiledRule>,
+    include_roots: Vec<String>,
+}
+
+impl RuleSet {
+    pub fn load(db: &Connection) -> Result<Self, String> {
+        let stored = load_scan_rules_sync(db).map_err(|e| e.to_string())?;
+        Ok(Self::from_rules(stored))
+    }
+
+    pub fn from_rules(stored: Vec<ScanRule>) -> Self {
+        let mut rules = vec![hidden_entries_rule()];
+        let mut content_extensions = Vec::new();
+        let mut include_roots = Vec::new();
+
+        for rule in stored {
+            let pattern = match compile_pattern(rule.match_type, rule.category, &rule.pattern) {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    eprintln!("Skipping {:?} rule {}: {}", rule.category, rule.id, e);
+                    continue;
+                }
+            };
+            if rule.effective_action() != RuleAction::Skip {
+                include_roots.extend(rule.walk_root());
+            }
+            let compiled = CompiledRule {
+                rule,
+                pattern,
+                matches_files_by_name: false,
+            };
+            let is_allow_list = compiled.rule.category == RuleCategory::Extension
+                && compiled.rule.rule_type == RuleType::Include
+                && compiled.rule.action.is_none();
+            if is_allow_list {
+                content_extensions.push(compiled);
+            } else {
+                rules.push(compiled);
+            }
+        }
+
+        RuleSet {
+            rules,
+            content_extensions,
+            include_roots,
+        }
+    }
+
+    /// Decides what to do with `path`. This is the only rule evaluation discovery and
+    /// scoring go through.
+    pub fn evaluate(&self, path: &Path, is_dir: bool) -> RuleDecision {
+        let winner = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.specificity(path, is_dir).map(|spec| (rule, spec)))
+            .max_by_key(|(rule, spec)| {
+                (
+                    rule.rule.priority,
+                    *spec,
+                    rule.rule.effective_action(),
+                    Reverse(rule.rule.id),
+                )
+            })
+            .map(|(rule, _)| rule);
+
+        let mut action = winner.map_or(RuleAction::Metadata, |r| r.rule.effective_action());
+        let mut limited = false;
+        if action == RuleAction::Full && !is_dir && !self.content_extensions.is_empty() {
+            // An extension rule that explicitly asks for full content counts as allowed
+            let allowed = winner.is_some_and(|r| r.rule.category == RuleCategory::Extension)
+                || self
+                    .content_extensions
+                    .iter()
+                    .any(|rule| rule.specificity(path, false).is_some());
+            if !allowed {
+                action = RuleAction::Metadata;
+                limited = true;
+            }
+        }
+
+        RuleDecision {
+            action,
+            rule: winner.map(|r| r.rule.clone()),
+            limited_by_extension_allow_list: limited,
+        }
+    }
+
+    /// Whether a walk may prune `dir`: it is skipped by a rule covering its whole subtree
+    /// and no included path lies below it.
+    pub fn skips_subtree(&self, dir: &Path) -> bool {
+        let decision = self.evaluate(dir, true);
+        decision.action == RuleAction::Skip
+            && decision.rule.as_ref().is_some_and(|r| r.depth_limit().is_none())
+            && !self
+                .include_roots
+                .iter()
+                .any(|root| Path::new(root).starts_with(dir) && Path::new(root) != dir)
+    }
+
+    pub fn is_skipped(&self, path: &Path, is_dir: bool) -> bool {
+        self.evaluate(path, is_dir).action == RuleAction::Skip
+    }
+}
+
+/// Dot-prefixed files and folders are skipped unless a rule says otherwise. It is an ordinary
+/// priority-0 rule, so an include with a higher priority can bring a hidden folder back.
+fn hidden_entries_rule() -> CompiledRule {
+    CompiledRule {
+        rule: ScanRule {
+            id: 0,
+            category: RuleCategory::Folder,
+            pattern: ".*".to_string(),
+            match_type: MatchType::Glob,
+            rule_type: RuleType::Exclude,
+            action: Some(RuleAction::Skip),
+            priority: 0,
+            is_recursive: true,
+            max_depth: None,
+        },
+        pattern: Pattern::Glob(
+            GlobBuilder::new(".*")
+                .build()
+                .expect("hidden entry glob is valid")
+                .compile_matcher(),
+        ),
+        matches_files_by_name: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(
+        id: i64,
+        category: RuleCategory,
+        pattern: &str,
+        rule_type: RuleType,
+        action: RuleAction,
+        priority: i64,
+    ) -> ScanRule {
+        ScanRule {
+            id,
+            category,
+            pattern: pattern.to_string(),
+            match_type: MatchType::Exact,
+            rule_type,
+            action: Some(action),
+            priority,
+            is_recursive: true,
+            max_depth: None,
+        }
+    }
+
+    fn decide(rules: Vec<ScanRule>, path: &str) -> (RuleAction, Option<i64>) {
+        let decision = RuleSet::from_rules(rules).evaluate(Path::new(path), false);
+        (decision.action, decision.rule.map(|r| r.id))
+    }
+
+    #[test]
+    fn priority_wins_over_everything_else() {
+        let rules = vec![
+            rule(
+                1,
+                RuleCategory::Extension,
+                "txt",
+                RuleType::Exclude,
+                RuleAction::Skip,
+                0,
+            ),
+            rule(
+                2,
+                RuleCategory::Path,
+                "/data",
+                RuleType::Include,
+                RuleAction::Full,
+                5,
+            ),
+        ];
+        assert_eq!(
+            decide(rules, "/data/a/notes.txt"),
+            (RuleAction::Full, Some(2))
+        );
+    }
+
+    #[test]
+    fn more_specific_match_wins_at_equal_priority() {
+        // A rule on the file itself beats a folder rule
+        let rules = vec![
+            rule(
+                1,
+                RuleCategory::Path,
+                "/data",
+                RuleType::Include,
+                RuleAction::Full,
+                0,
+            ),
+            rule(
+                2,
+                RuleCategory::Extension,
+                "log",
+                RuleType::Exclude,
+                RuleAction::Skip,
+                0,
+            ),
+        ];
+        assert_eq!(decide(rules, "/data/app.log"), (RuleAction::Skip, Some(2)));
+
+        // A deeper folder beats a shallower one, whatever its severity
+        let rules = vec![
+            rule(
+                1,
+                RuleCategory::Path,
+                "/data",
+                RuleType::Exclude,
+                RuleAction::Skip,
+                0,
+            ),
+            rule(
+                2,
+                RuleCategory::Path,
+                "/data/keep",
+                RuleType::Include,
+                RuleAction::Full,
+                0,
+            ),
+        ];
+        assert_eq!(
+            decide(rules, "/data/keep/a.txt"),
+            (RuleAction::Full, Some(2))
+        );
+    }
+
+    #[test]
+    fn most_severe_action_wins_at_equal_specificity() {
+        let rules = vec![
+            rule(
+                1,
+                RuleCategory::Path,
+                "/data",
+                RuleType::Include,
+                RuleAction::Full,
+                0,
+            ),
+            rule(
+                2,
+                RuleCategory::Folder,
+                "data",
+                RuleType::Include,
+                RuleAction::Metadata,
+                0,
+            ),
+        ];
+        assert_eq!(
+            decide(rules, "/data/a.txt"),
+            (RuleAction::Metadata, Some(2))
+        );
+    }
+
+    #[test]
+    fn older_rule_breaks_remaining_ties() {
+        let rules = vec![
+            rule(
+                7,
+                RuleCategory::Folder,
+                "data",
+                RuleType::Exclude,
+                RuleAction::Skip,
+                0,
+            ),
+            rule(
+                3,
+                RuleCategory::Path,
+                "/data",
+                RuleType::Exclude,
+                RuleAction::Skip,
+                0,
+            ),
+        ];
+        assert_eq!(decide(rules, "/data/a.txt"), (RuleAction::Skip, Some(3)));
+    }
+
+    #[test]
+    fn glob_includes_protect_their_literal_prefix_from_pruning() {
+        let mut include = rule(
+            2,
+            RuleCategory::Path,
+            "/data/skip/**/keep/*.txt",
+            RuleType::Include,
+            RuleAction::Full,
+            5,
+        );
+        include.match_type = MatchType::Glob;
+        let rules = RuleSet::from_rules(vec![
+            rule(
+                1,
+                RuleCategory::Path,
+                "/data",
+                RuleType::Exclude,
+                RuleAction::Skip,
+                0,
+            ),
+            include,
+        ]);
+        assert!(!rules.skips_subtree(Path::new("/data")));
+        assert!(rules.skips_subtree(Path::new("/data/other")));
+    }
+}
@@ -1,93 +1,220 @@
 // File scoring and rule checking
 use super::types::FileCategory;
-use chrono::Duration;
-use rusqlite::Connection;
-use std::collections::HashSet;
-use std::fs;
+use chrono::{DateTime, Duration};
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
 use std::path::Path;
 use std::time::SystemTime;
 
-/// Calculates a file's score based on a set of rules.
+pub const SCORING_PROFILE_SETTING: &str = "scoring_profile";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecencyBucket {
+    pub max_age_days: i64,
+    pub points: f64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SizePenalty {
+    pub min_size_mb: f64,
+    pub penalty: f64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BonusWord {
+    pub word: String,
+    pub points: f64,
+}
+
+/// The tunable weights behind `calculate_file_score`, stored as JSON in the settings table.
+/// Missing fields fall back to the defaults, so partial profiles can be imported.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ScoringProfile {
+    /// Points per `FileCategory` name ("Document", "Code", ...)
+    pub category_points: HashMap<String, f64>,
+    /// Files under a folder with one of these names get `low_value_points` instead
+    pub low_value_folders: Vec<String>,
+    pub low_value_points: f64,
+    /// Bonus for files inside an included path
+    pub included_path_points: f64,
+    /// Checked youngest first; the first bucket the file's age fits in wins
+    pub recency_buckets: Vec<RecencyBucket>,
+    /// Checked largest first; the first threshold the file exceeds applies
+    pub size_penalties: Vec<SizePenalty>,
+    /// Added when the file name contains the word, capped at `max_bonus_points`
+    pub bonus_words: Vec<BonusWord>,
+    pub max_bonus_points: f64,
+}
+
+impl Default for ScoringProfile {
+    fn default() -> Self {
+        let category_points = [
+            (FileCategory::Document, 4.0),
+            (FileCategory::Code, 3.0),
+            (FileCategory::Spreadsheet, 3.0),
+            (FileCategory::Database, 3.0),
+            (FileCategory::Config, 2.0),
+            (FileCategory::Media, 1.0),
+            (FileCategory::Archive, 1.0),
+            (FileCategory::Binary, 1.0),
+            (FileCategory::Unknown, 0.0),
+        ]
+        .into_iter()
+        .map(|(category, points)| (format!("{:?}", category), points))
+        .collect();
+
+        Self {
+            category_points,
+            low_value_folders: vec!["log".into(), "logs".into(), "tmp".into()],
+            low_value_points: 0.5,
+            included_path_points: 3.0,
+            recency_buckets: vec![
+                RecencyBucket { max_age_days: 7, points: 2.0 },
+                RecencyBucket { max_age_days: 30, points: 1.5 },
+                RecencyBucket { max_age_days: 182, points: 1.0 },
+            ],
+            size_penalties: vec![
+                SizePenalty { min_size_mb: 500.0, penalty: -1.0 },
+                SizePenalty { min_size_mb: 100.0, penalty: -0.5 },
+            ],
+            bonus_words: ["project", "report", "final", "db"]
+                .into_iter()
+                .map(|word| BonusWord { word: word.to_string(), points: 0.5 })
+                .collect(),
+            max_bonus_points: 1.0,
+        }
+    }
+}
+
+impl ScoringProfile {
+    /// Rejects non-finite numbers and puts the buckets in the order scoring expects.
+    pub fn normalized(mut self) -> Result<Self, String> {
+        let numbers = self
+            .category_points
+            .values()
+            .copied()
+            .chain([self.low_value_points, self.included_path_points, self.max_bonus_points])
+            .chain(self.recency_buckets.iter().map(|b| b.points))
+            .chain(self.size_penalties.iter().flat_map(|p| [p.min_size_mb, p.penalty]))
+            .chain(self.bonus_words.iter().map(|w| w.points));
+        for value in numbers {
+            if !value.is_finite() {
+                return Err("Scoring profile contains an invalid number".to_string());
+            }
+        }
+
+        self.recency_buckets.sort_by_key(|b| b.max_age_days);
+        self.size_penalties
+            .sort_by(|a, b| b.min_size_mb.partial_cmp(&a.min_size_mb).unwrap_or(std::cmp::Ordering::Equal));
+        for word in &mut self.bonus_words {
+            word.word = word.word.trim().to_lowercase();
+        }
+        self.bonus_words.retain(|w| !w.word.is_empty());
+        for folder in &mut self.low_value_folders {
+            *folder = folder.trim().to_lowercase();
+        }
+        self.low_value_folders.retain(|f| !f.is_empty());
+        Ok(self)
+    }
+}
+
+/// Loads the active profile, falling back to the defaults when none is stored or it is unreadable.
+pub fn load_scoring_profile(db: &Connection) -> ScoringProfile {
+    let stored = crate::database::rules::get_setting_sync(db, SCORING_PROFILE_SETTING)
+        .ok()
+        .flatten();
+    match stored.map(|json| serde_json::from_str::<ScoringProfile>(&json)) {
+        Some(Ok(profile)) => profile.normalized().unwrap_or_default(),
+        Some(Err(e)) => {
+            eprintln!("Invalid scoring profile, using defaults: {}", e);
+            ScoringProfile::default()
+        }
+        None => ScoringProfile::default(),
+    }
+}
+
+pub fn save_scoring_profile(db: &Connection, profile: ScoringProfile) -> Result<ScoringProfile, String> {
+    let profile = profile.normalized()?;
+    let json = serde_json::to_string(&profile).map_err(|e| e.to_string())?;
+    crate::database::rules::set_setting_sync(db, SCORING_PROFILE_SETTING, &json)
+        .map_err(|e| e.to_string())?;
+    Ok(profile)
+}
+
+/// Calculates a file's score based on the scoring profile.
+/// Takes the modification time and size directly so scores can be recomputed from the index.
 /// The score is clamped between 0.0 and 10.0.
 pub fn calculate_file_score(
+    profile: &ScoringProfile,
     path_str: &str,
-    metadata: &fs::Metadata,
+    modified: Option<SystemTime>,
+    size_bytes: u64,
     included_paths: &[String],
 ) -> f64 {
     let path = Path::new(path_str);
     let now = SystemTime::now();
 
-    // 1. Base Category Score (0-4 points)
-    let mut category_score = 0.0;
+    // 1. Base Category Score
     let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
     let category = FileCategory::from_extension(extension);
 
-    let path_lower = path_str.to_lowercase();
-    if path_lower.contains("/log/") || path_lower.contains("/logs/") || path_lower.contains("/tmp/")
-    {
-        category_score = 0.5;
+    let in_low_value_folder = path
+        .parent()
+        .map(|parent| {
+            parent.components().any(|c| {
+                let name = c.as_os_str().to_string_lossy().to_lowercase();
+                profile.low_value_folders.contains(&name)
+            })
+        })
+        .unwrap_or(false);
+    let category_score = if in_low_value_folder {
+        profile.low_value_points
     } else {
-        category_score = match category {
-            FileCategory::Document => 4.0,
-            FileCategory::Code => 3.0,
-            FileCategory::Spreadsheet | FileCategory::Database => 3.0,
-            FileCategory::Config => 2.0,
-            FileCategory::Media | FileCategory::Archive | FileCategory::Binary => 1.0,
-            FileCategory::Unknown => 0.0,
-        };
-    }
-
-    // 2. Path Importance (0-3 points)
-    let mut path_score = 0.0;
-    if included_paths.iter().any(|p| path_str.starts_with(p)) {
-        path_score = 3.0;
-    }
+        profile
+            .category_points
+            .get(&format!("{:?}", category))
+            .copied()
+            .unwrap_or(0.0)
+    };
 
-    // 3. Recency Score (0-2 points)
-    let recency_score = if let Ok(modified) = metadata.modified() {
-        let age = now.duration_since(modified).unwrap_or_default();
-        if age < Duration::days(7).to_std().unwrap_or_default() {
-            2.0
-        } else if age < Duration::days(30).to_std().unwrap_or_default() {
-            1.5
-        } else if age < Duration::days(182).to_std().unwrap_or_default() {
-            1.0
-        } else {
-            0.0
-        }
+    // 2. Path Importance
+    let path_score = if included_paths.iter().any(|p| path_str.starts_with(p)) {
+        profile.included_path_points
     } else {
         0.0
     };
 
+    // 3. Recency Score
+    let recency_score = modified
+        .map(|modified| {
+            let age = now.duration_since(modified).unwrap_or_default();
+            profile
+                .recency_buckets
+                .iter()
+                .find(|b| age < Duration::days(b.max_age_days).to_std().unwrap_or_default())
+                .map_or(0.0, |b| b.points)
+        })
+        .unwrap_or(0.0);
+
     // 4. File Size Penalty (deductions)
-    let size_penalty = {
-        let size_mb = metadata.len() as f64 / (1024.0 * 1024.0);
-        if size_mb > 500.0 {
-            -1.0
-        } else if size_mb > 100.0 {
-            -0.5
-        } else {
-            0.0
-        }
-    };
+    let size_mb = size_bytes as f64 / (1024.0 * 1024.0);
+    let size_penalty = profile
+        .size_penalties
+        .iter()
+        .find(|p| size_mb > p.min_size_mb)
+        .map_or(0.0, |p| p.penalty);
 
-    // 5. Special Bonus (max +1 point)
+    // 5. Special Bonus
     let bonus_score = if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
         let name_lower = file_name.to_lowercase();
-        let mut bonus: f64 = 0.0;
-        if name_lower.contains("project") {
-            bonus += 0.5;
-        }
-        if name_lower.contains("report") {
-            bonus += 0.5;
-        }
-        if name_lower.contains("final") {
-            bonus += 0.5;
-        }
-        if name_lower.contains("db") {
-            bonus += 0.5;
-        }
-        bonus.min(1.0)
+        let bonus: f64 = profile
+            .bonus_words
+            .iter()
+            .filter(|w| name_lower.contains(&w.word))
+            .map(|w| w.points)
+            .sum();
+        bonus.min(profile.max_bonus_points)
     } else {
         0.0
     };
@@ -97,46 +224,49 @@ pub fn calculate_file_score(
     (clamped * 10.0).round() / 10.0
 }
 
-/// Phase 1: Check if file should have content processed based on included/excluded paths.
-pub fn check_phase1_rules(db: &Connection, file_path: &str) -> Result<(bool, bool), String> {
-    let include_paths =
-        crate::database::rules::get_included_paths_sync(db).map_err(|e| e.to_string())?;
-    let exclude_folders =
-        crate::database::rules::get_excluded_folder_sync(db).map_err(|e| e.to_string())?;
-    let include_exts: HashSet<String> = crate::database::rules::get_included_extensions_sync(db)
+/// Recomputes `files.score` from the stored metadata and then the folder scores,
+/// without touching the filesystem. Returns the number of files rescored.
+pub fn recompute_all_scores(db: &Connection) -> Result<usize, String> {
+    let profile = load_scoring_profile(db);
+    let included_paths: Vec<String> = crate::database::rules::get_included_paths_sync(db)
         .map_err(|e| e.to_string())?
         .into_iter()
         .collect();
-    let path_obj = Path::new(file_path);
 
-    if !include_paths.iter().any(|p| file_path.starts_with(p)) {
-        return Ok((false, false));
-    }
-
-    let is_in_excluded_folder = path_obj.ancestors().any(|ancestor| {
-        ancestor
-            .file_name()
-            .and_then(|n| n.to_str())
-            .map_or(false, |name| {
-                exclude_folders
-                    .iter()
-                    .any(|ex| ex.eq_ignore_ascii_case(name))
-            })
-    });
-
-    if is_in_excluded_folder {
-        return Ok((false, true));
-    }
-
-    let extension = path_obj.extension().and_then(|s| s.to_str()).unwrap_or("");
+    let mut stmt = db
+        .prepare("SELECT id, path, updated_at, file_size FROM files")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-    if !include_exts.is_empty()
-        && !include_exts
-            .iter()
-            .any(|inc| inc.eq_ignore_ascii_case(extension))
+    let tx = db.unchecked_transaction().map_err(|e| e.to_string())?;
     {
-        return Ok((false, false));
+        let mut update = tx
+            .prepare("UPDATE files SET score = ?1 WHERE id = ?2")
+            .map_err(|e| e.to_string())?;
+        for (id, path, updated_at, file_size) in &rows {
+            let modified = updated_at
+                .as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(SystemTime::from);
+            let size = file_size.unwrap_or(0).max(0) as u64;
+            let score = calculate_file_score(&profile, path, modified, size, &included_paths);
+            update.execute(params![score, id]).map_err(|e| e.to_string())?;
+        }
     }
+    tx.commit().map_err(|e| e.to_string())?;
 
-    Ok((true, false))
+    crate::database::update_folder_scores(db).map_err(|e| e.to_string())?;
+    println!("Recomputed scores for {} files", rows.len());
+    Ok(rows.len())
 }
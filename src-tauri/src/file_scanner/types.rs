@@ -66,3 +66,13 @@ pub struct ScannedFile {
     pub path: String,
     pub content_processed: bool,
 }
+
+/// A folder prepared for semantic embedding into the LanceDB `folder` table.
+#[derive(Debug, Clone)]
+pub struct FolderDescription {
+    pub id: i64,
+    pub name: String,
+    pub path: String,
+    pub created_at: String,
+    pub description: String,
+}
@@ -0,0 +1,594 @@
+// Remembers every volume by a stable identity, with its indexing policy and whether it is
+// connected, so unplugged drives keep their index and come back under any mount point
+use super::lancedb::{get_lancedb_tables, remap_path_prefix_lancedb};
+use super::volumes::{discover_volumes, Volume};
+use crate::database::journal::{self, JournalOp};
+use crate::database::search::SearchResult;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+// How often the watcher looks for plugged and unplugged volumes
+const WATCH_INTERVAL: Duration = Duration::from_secs(15);
+
+// Path columns rewritten when a volume comes back under a different mount point, or an
+// imported index is moved to a new home folder
+const PATH_COLUMNS: &[(&str, &str)] = &[
+    ("files", "path"),
+    ("folders", "path"),
+    ("path_rules", "path"),
+    ("file_interactions", "path"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumePolicy {
+    Never,    // Not walked and never indexed
+    Metadata, // Names, sizes and dates only, even inside Phase 1 included paths
+    Full,     // Content is read wherever the scan rules allow it
+}
+
+impl VolumePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VolumePolicy::Never => "never",
+            VolumePolicy::Metadata => "metadata",
+            VolumePolicy::Full => "full",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "never" => Some(VolumePolicy::Never),
+            "metadata" => Some(VolumePolicy::Metadata),
+            "full" => Some(VolumePolicy::Full),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TrackedVolume {
+    pub id: i64,
+    pub volume_key: String,
+    pub label: Option<String>,
+    pub mount_point: String, // Last known mount point
+    pub file_system: String,
+    pub is_removable: bool,
+    pub is_network: bool,
+    pub policy: VolumePolicy,
+    pub is_online: bool,
+    pub last_seen: String,
+}
+
+/// A volume that came back under a different mount point.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Remount {
+    pub volume_key: String,
+    pub old_mount_point: String,
+    pub new_mount_point: String,
+    #[serde(skip)]
+    pub journal_id: i64,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct VolumeChanges {
+    pub came_online: Vec<TrackedVolume>,
+    pub went_offline: Vec<TrackedVolume>,
+    pub remounted: Vec<Remount>,
+}
+
+impl VolumeChanges {
+    pub fn is_empty(&self) -> bool {
+        self.came_online.is_empty() && self.went_offline.is_empty() && self.remounted.is_empty()
+    }
+}
+
+fn row_to_tracked_volume(row: &rusqlite::Row) -> rusqlite::Result<TrackedVolume> {
+    let policy: String = row.get(7)?;
+    Ok(TrackedVolume {
+        id: row.get(0)?,
+        volume_key: row.get(1)?,
+        label: row.get(2)?,
+        mount_point: row.get(3)?,
+        file_system: row.get(4)?,
+        is_removable: row.get(5)?,
+        is_network: row.get(6)?,
+        policy: VolumePolicy::parse(&policy).unwrap_or(VolumePolicy::Full),
+        is_online: row.get(8)?,
+        last_seen: row.get(9)?,
+    })
+}
+
+pub fn load_tracked_volumes(conn: &Connection) -> rusqlite::Result<Vec<TrackedVolume>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, volume_key, label, mount_point, file_system, is_removable, is_network,
+                policy, is_online, last_seen
+         FROM volumes ORDER BY mount_point",
+    )?;
+    let rows = stmt.query_map([], row_to_tracked_volume)?;
+    rows.collect()
+}
+
+pub fn set_volume_policy(
+    conn: &Connection,
+    volume_key: &str,
+    policy: VolumePolicy,
+) -> Result<(), String> {
+    let updated = conn
+        .execute(
+            "UPDATE volumes SET policy = ?1 WHERE volume_key = ?2",
+            params![policy.as_str(), volume_key],
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+    if updated == 0 {
+        return Err(format!("Volume {} not found", volume_key));
+    }
+    Ok(())
+}
+
+/// The volume a path lives on: the deepest mount point containing it. When an offline volume
+/// and an online one share a mount point, the online one wins.
+pub fn owning_volume<'a>(volumes: &'a [TrackedVolume], path: &str) -> Option<&'a TrackedVolume> {
+    let path = Path::new(path);
+    volumes
+        .iter()
+        .filter(|v| path.starts_with(&v.mount_point))
+        .max_by_key(|v| (Path::new(&v.mount_point).components().count(), v.is_online))
+}
+
+/// Policy for a path; paths on volumes the registry hasn't seen yet are fully indexed.
+pub fn policy_for_path(volumes: &[TrackedVolume], path: &str) -> VolumePolicy {
+    owning_volume(volumes, path).map_or(VolumePolicy::Full, |v| v.policy)
+}
+
+/// Flags results whose volume is currently disconnected. They stay in the index so they
+/// come back as soon as the volume does.
+pub fn mark_availability(conn: &Connection, results: &mut [SearchResult]) -> rusqlite::Result<()> {
+    let volumes = load_tracked_volumes(conn)?;
+    if volumes.iter().all(|v| v.is_online) {
+        return Ok(());
+    }
+    for result in results.iter_mut() {
+        result.available = owning_volume(&volumes, &result.path).is_none_or(|v| v.is_online);
+    }
+    Ok(())
+}
+
+/// Mount point with a trailing separator, so `/media/usb` doesn't match `/media/usb2`.
+pub fn mount_prefix(mount_point: &str) -> String {
+    if mount_point.ends_with('/') || mount_point.ends_with('\\') {
+        mount_point.to_string()
+    } else {
+        format!("{}{}", mount_point, std::path::MAIN_SEPARATOR)
+    }
+}
+
+/// Rewrites every indexed SQLite path under the folder `old` to live under `new` instead.
+pub fn remap_path_root(conn: &Connection, old: &str, new: &str) -> rusqlite::Result<()> {
+    let old_prefix = mount_prefix(old);
+    let new_prefix = mount_prefix(new);
+    // SQLite's substr counts characters, not bytes
+    let old_len = old_prefix.chars().count() as i64;
+    let new_len = new_prefix.chars().count() as i64;
+    // With the new root inside the old one, paths already under it are left alone, so running
+    // the same remap twice changes nothing
+    let nested = new_prefix.starts_with(&old_prefix);
+
+    for (table, column) in PATH_COLUMNS {
+        // OR IGNORE: a path already indexed under the new root keeps its own row
+        conn.execute(
+            &format!(
+                "UPDATE OR IGNORE {table} SET {column} = ?1 || substr({column}, ?2)
+                 WHERE substr({column}, 1, ?3) = ?4 AND NOT (?5 AND substr({column}, 1, ?6) = ?1)"
+            ),
+            params![new_prefix, old_len + 1, old_len, old_prefix, nested, new_len],
+        )?;
+        conn.execute(
+            &format!("UPDATE OR IGNORE {table} SET {column} = ?1 WHERE {column} = ?2"),
+            params![new, old],
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether one mount point lies inside the other. Paths can't be moved between such mounts
+/// safely: the old prefix also matches paths that are already under the new one.
+fn mounts_nested(a: &str, b: &str) -> bool {
+    let (a, b) = (mount_prefix(a), mount_prefix(b));
+    a.starts_with(&b) || b.starts_with(&a)
+}
+
+/// Moves indexed SQLite paths from mount point `old` to `new`. Returns the journal entry
+/// for the matching LanceDB rewrite.
+fn remap_mount_point(conn: &Connection, old: &str, new: &str) -> rusqlite::Result<i64> {
+    let tx = conn.unchecked_transaction()?;
+    remap_path_root(&tx, old, new)?;
+    let journal_id = journal::begin(
+        &tx,
+        &JournalOp::RemapPaths {
+            old_prefix: mount_prefix(old),
+            new_prefix: mount_prefix(new),
+        },
+    )?;
+    tx.commit()?;
+    Ok(journal_id)
+}
+
+/// One mount per volume key, and whether the key was seen at several mount points. btrfs
+/// subvolumes and bind mounts expose one filesystem at several places; the tracked mount point
+/// is kept while it is still mounted, otherwise the shortest one.
+fn canonical_mounts<'a>(
+    discovered: &'a [Volume],
+    known: &[TrackedVolume],
+) -> Vec<(&'a Volume, bool)> {
+    let mut by_key: BTreeMap<&str, Vec<&Volume>> = BTreeMap::new();
+    for volume in discovered {
+        by_key
+            .entry(volume.volume_key.as_str())
+            .or_default()
+            .push(volume);
+    }
+
+    by_key
+        .into_iter()
+        .filter_map(|(key, mounts)| {
+            let tracked = known.iter().find(|t| t.volume_key == key);
+            let canonical = mounts
+                .iter()
+                .find(|v| tracked.is_some_and(|t| t.mount_point == v.mount_point))
+                .or_else(|| {
+                    mounts.iter().min_by_key(|v| {
+                        (
+                            Path::new(&v.mount_point).components().count(),
+                            v.mount_point.as_str(),
+                        )
+                    })
+                })?;
+            Some((*canonical, mounts.len() > 1))
+        })
+        .collect()
+}
+
+/// Reconciles the registry with the volumes mounted right now: registers new volumes,
+/// follows known ones to their new mount point and marks missing ones offline.
+pub fn sync_volumes(conn: &Connection, discovered: &[Volume]) -> Result<VolumeChanges, String> {
+    let mut known = load_tracked_volumes(conn).map_err(|e| e.to_string())?;
+    let now = Utc::now().to_rfc3339();
+    let mut changes = VolumeChanges::default();
+
+    for (volume, ambiguous) in canonical_mounts(discovered, &known) {
+        let Some(tracked) = known
+            .iter()
+            .find(|t| t.volume_key == volume.volume_key)
+            .cloned()
+        else {
+            conn.execute(
+                "INSERT INTO volumes (volume_key, label, mount_point, file_system, is_removable,
+                                      is_network, policy, is_online, last_seen, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 1, ?8, ?8)",
+                params![
+                    volume.volume_key,
+                    volume.label,
+                    volume.mount_point,
+                    volume.file_system,
+                    volume.is_removable,
+                    volume.is_network,
+                    VolumePolicy::Full.as_str(),
+                    now
+                ],
+            )
+            .map_err(|e| format!("Database error: {}", e))?;
+            println!(
+                "Registered volume {} at {}",
+                volume.volume_key, volume.mount_point
+            );
+            known = load_tracked_volumes(conn).map_err(|e| e.to_string())?;
+            continue;
+        };
+
+        // With several mounts there is no telling which one the old paths moved to
+        let mount_point = if tracked.mount_point == volume.mount_point {
+            &volume.mount_point
+        } else if ambiguous {
+            // Logged on every refresh while the mounts stay like this
+            log::debug!(
+                "Volume {} is mounted at several places, keeping {}",
+                tracked.volume_key, tracked.mount_point
+            );
+            &tracked.mount_point
+        } else if mounts_nested(&tracked.mount_point, &volume.mount_point) {
+            log::debug!(
+                "Volume {} moved from {} to {} inside it, keeping the old mount point",
+                tracked.volume_key, tracked.mount_point, volume.mount_point
+            );
+            &tracked.mount_point
+        } else {
+            println!(
+                "Volume {} moved from {} to {}",
+                tracked.volume_key, tracked.mount_point, volume.mount_point
+            );
+            let journal_id = remap_mount_point(conn, &tracked.mount_point, &volume.mount_point)
+                .map_err(|e| format!("Failed to remap {}: {}", tracked.mount_point, e))?;
+            changes.remounted.push(Remount {
+                volume_key: tracked.volume_key.clone(),
+                old_mount_point: tracked.mount_point.clone(),
+                new_mount_point: volume.mount_point.clone(),
+                journal_id,
+            });
+            &volume.mount_point
+        };
+
+        conn.execute(
+            "UPDATE volumes SET label = ?1, mount_point = ?2, file_system = ?3, is_removable = ?4,
+                                is_network = ?5, is_online = 1, last_seen = ?6
+             WHERE id = ?7",
+            params![
+                volume.label,
+                mount_point,
+                volume.file_system,
+                volume.is_removable,
+                volume.is_network,
+                now,
+                tracked.id
+            ],
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+
+        if !tracked.is_online {
+            changes.came_online.push(TrackedVolume {
+                label: volume.label.clone(),
+                mount_point: mount_point.clone(),
+                is_online: true,
+                last_seen: now.clone(),
+                ..tracked.clone()
+            });
+        }
+        known = load_tracked_volumes(conn).map_err(|e| e.to_string())?;
+    }
+
+    for tracked in known.iter().filter(|t| t.is_online) {
+        if discovered
+            .iter()
+            .any(|v| v.volume_key == tracked.volume_key)
+        {
+            continue;
+        }
+        conn.execute(
+            "UPDATE volumes SET is_online = 0 WHERE id = ?1",
+            params![tracked.id],
+        )
+        .map_err(|e| format!("Database error: {}", e))?;
+        println!(
+            "Volume {} at {} went offline",
+            tracked.volume_key, tracked.mount_point
+        );
+        changes.went_offline.push(TrackedVolume {
+            is_online: false,
+            ..tracked.clone()
+        });
+    }
+
+    Ok(changes)
+}
+
+/// LanceDB keeps its own copy of each file path, so remounts are applied there too.
+pub async fn remap_lancedb_paths(remounts: &[Remount]) -> Result<(), String> {
+    let (files_table, _) = get_lancedb_tables().await.map_err(|e| e.to_string())?;
+    for remount in remounts {
+        remap_path_prefix_lancedb(
+            &files_table,
+            &mount_prefix(&remount.old_mount_point),
+            &mount_prefix(&remount.new_mount_point),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let conn = crate::database::get_connection();
+        journal::complete(&conn, remount.journal_id).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Discovers the mounted volumes and reconciles the registry with them.
+/// Must not be called from an async context: it blocks on the LanceDB remap.
+pub fn refresh_volumes() -> Result<VolumeChanges, String> {
+    // Discovery can stall on a slow network share, so it runs before taking the DB lock
+    let discovered = discover_volumes();
+    let changes = {
+        let db = crate::database::get_connection();
+        sync_volumes(&db, &discovered)?
+    };
+    if !changes.remounted.is_empty() {
+        if let Err(e) = tauri::async_runtime::block_on(remap_lancedb_paths(&changes.remounted)) {
+            // The journal entry stays pending and is replayed at the next start
+            eprintln!("Failed to remap LanceDB paths: {}", e);
+        }
+    }
+    Ok(changes)
+}
+
+/// Polls for plugged and unplugged volumes, notifies the UI and resumes indexing on
+/// volumes that come back.
+pub fn start_volume_watcher(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        match refresh_volumes() {
+            Ok(changes) if !changes.is_empty() => {
+                if let Err(e) = app.emit("volumes_changed", &changes) {
+                    eprintln!("Failed to emit volumes_changed: {}", e);
+                }
+                for volume in changes.came_online {
+                    if volume.policy != VolumePolicy::Never {
+                        resume_indexing(&app, &volume);
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Volume refresh failed: {}", e),
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    });
+}
+
+/// Picks up files added while the volume was away. Already-indexed paths are skipped by the
+/// scanner, so this only processes what is new.
+fn resume_indexing(app: &AppHandle, volume: &TrackedVolume) {
+    println!("Resuming indexing on {}", volume.mount_point);
+    if let Err(e) = super::scan_volume(&volume.mount_point, app.clone()) {
+        eprintln!("Failed to resume indexing on {}: {}", volume.mount_point, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::database::schema::create_all_sql())
+            .unwrap();
+        conn
+    }
+
+    fn mounted(volume_key: &str, mount_point: &str) -> Volume {
+        Volume {
+            mount_point: mount_point.to_string(),
+            name: mount_point.to_string(),
+            file_system: "btrfs".to_string(),
+            is_removable: false,
+            is_network: false,
+            is_read_only: false,
+            total_bytes: 0,
+            available_bytes: 0,
+            uuid: None,
+            label: None,
+            volume_key: volume_key.to_string(),
+            selected: false,
+        }
+    }
+
+    fn mount_points(conn: &Connection) -> Vec<(String, String)> {
+        load_tracked_volumes(conn)
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.volume_key, t.mount_point))
+            .collect()
+    }
+
+    #[test]
+    fn btrfs_subvolumes_register_once_and_never_flip() {
+        let conn = open_db();
+        let discovered = [mounted("uuid:a", "/home"), mounted("uuid:a", "/")];
+
+        let changes = sync_volumes(&conn, &discovered).unwrap();
+        assert!(changes.remounted.is_empty());
+        assert_eq!(
+            mount_points(&conn),
+            [("uuid:a".to_string(), "/".to_string())]
+        );
+
+        // Discovery order must not move the volume between its subvolumes
+        let reversed = [mounted("uuid:a", "/"), mounted("uuid:a", "/home")];
+        for round in [&discovered, &reversed] {
+            let changes = sync_volumes(&conn, round).unwrap();
+            assert!(changes.is_empty());
+            assert_eq!(
+                mount_points(&conn),
+                [("uuid:a".to_string(), "/".to_string())]
+            );
+        }
+    }
+
+    #[test]
+    fn bind_mounts_keep_the_tracked_mount_point() {
+        let conn = open_db();
+        sync_volumes(&conn, &[mounted("uuid:b", "/srv/data/share")]).unwrap();
+
+        // A shorter bind mount of the same filesystem shows up next to the tracked one
+        let discovered = [
+            mounted("uuid:b", "/data"),
+            mounted("uuid:b", "/srv/data/share"),
+        ];
+        let changes = sync_volumes(&conn, &discovered).unwrap();
+        assert!(changes.remounted.is_empty());
+        assert_eq!(
+            mount_points(&conn),
+            [("uuid:b".to_string(), "/srv/data/share".to_string())]
+        );
+    }
+
+    #[test]
+    fn ambiguous_keys_are_never_remapped() {
+        let conn = open_db();
+        sync_volumes(&conn, &[mounted("label:usb:vfat:1", "/media/usb")]).unwrap();
+        conn.execute(
+            "INSERT INTO files (name, extension, path, content, created_at, updated_at)
+             VALUES ('a.txt', 'txt', '/media/usb/a.txt', '', '', '')",
+            [],
+        )
+        .unwrap();
+
+        // Two identical sticks: neither mount can claim the indexed paths
+        let discovered = [
+            mounted("label:usb:vfat:1", "/media/usb1"),
+            mounted("label:usb:vfat:1", "/media/usb2"),
+        ];
+        let changes = sync_volumes(&conn, &discovered).unwrap();
+        assert!(changes.remounted.is_empty());
+        assert_eq!(
+            mount_points(&conn),
+            [("label:usb:vfat:1".to_string(), "/media/usb".to_string())]
+        );
+        let path: String = conn
+            .query_row("SELECT path FROM files", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(path, "/media/usb/a.txt");
+
+        // Once only one is left it is followed as usual
+        let changes = sync_volumes(&conn, &[mounted("label:usb:vfat:1", "/media/usb2")]).unwrap();
+        assert_eq!(changes.remounted.len(), 1);
+        let path: String = conn
+            .query_row("SELECT path FROM files", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(path, "/media/usb2/a.txt");
+    }
+
+    #[test]
+    fn nested_remaps_are_idempotent() {
+        let conn = open_db();
+        conn.execute(
+            "INSERT INTO files (name, extension, path, content, created_at, updated_at)
+             VALUES ('a.txt', 'txt', '/data/a.txt', '', '', ''),
+                    ('b.txt', 'txt', '/data/disk/b.txt', '', '', '')",
+            [],
+        )
+        .unwrap();
+
+        // A replayed journal entry runs the same remap again
+        for _ in 0..2 {
+            remap_path_root(&conn, "/data", "/data/disk").unwrap();
+        }
+        let mut stmt = conn.prepare("SELECT path FROM files ORDER BY path").unwrap();
+        let paths: Vec<String> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(paths, ["/data/disk/a.txt", "/data/disk/b.txt"]);
+    }
+
+    #[test]
+    fn nested_mount_points_are_not_remapped() {
+        let conn = open_db();
+        sync_volumes(&conn, &[mounted("uuid:c", "/mnt")]).unwrap();
+
+        let changes = sync_volumes(&conn, &[mounted("uuid:c", "/mnt/disk")]).unwrap();
+        assert!(changes.remounted.is_empty());
+        assert_eq!(
+            mount_points(&conn),
+            [("uuid:c".to_string(), "/mnt".to_string())]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+// Cross-platform volume discovery for Phase 2 scanning
+use super::volume_registry::{load_tracked_volumes, VolumePolicy};
+use rusqlite::Connection;
+use std::collections::HashSet;
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+use sysinfo::Disks;
+
+pub const SELECTED_VOLUMES_SETTING: &str = "selected_volumes";
+
+// Kernel and virtual filesystems that never hold user files
+const PSEUDO_FILE_SYSTEMS: &[&str] = &[
+    "proc", "sysfs", "tmpfs", "devtmpfs", "devpts", "devfs", "overlay", "squashfs", "cgroup",
+    "cgroup2", "securityfs", "pstore", "bpf", "tracefs", "debugfs", "configfs", "fusectl",
+    "mqueue", "hugetlbfs", "autofs", "binfmt_misc", "efivarfs", "ramfs", "nsfs", "rpc_pipefs",
+    "nullfs", "fuse.portal", "fuse.gvfsd-fuse", "fuse.snapfuse", "fuse.lxcfs",
+];
+
+// Mount points under these prefixes belong to the OS, containers or snap packages
+const SYSTEM_MOUNT_PREFIXES: &[&str] = &[
+    "/proc", "/sys", "/dev", "/run/user", "/run/snapd", "/snap", "/boot", "/var/lib/docker",
+    "/var/snap", "/System/Volumes", "/private/var/vm",
+];
+
+const NETWORK_FILE_SYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smbfs", "smb3", "afpfs", "webdav", "davfs", "9p", "fuse.sshfs",
+    "sshfs", "fuse.rclone", "ncpfs",
+];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Volume {
+    pub mount_point: String,
+    pub name: String,
+    pub file_system: String,
+    pub is_removable: bool,
+    pub is_network: bool,
+    pub is_read_only: bool,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    /// Filesystem UUID, where the OS exposes one
+    pub uuid: Option<String>,
+    /// Filesystem label, where the OS exposes one
+    pub label: Option<String>,
+    /// Stable identity that survives remounts at a different mount point or drive letter
+    pub volume_key: String,
+    /// Whether Phase 2 scans this volume (the user's choice, or the default policy)
+    pub selected: bool,
+}
+
+impl Volume {
+    /// Without an explicit choice, only local, writable, fixed volumes are indexed.
+    pub fn selected_by_default(&self) -> bool {
+        !self.is_removable && !self.is_network && !self.is_read_only
+    }
+}
+
+fn is_pseudo(file_system: &str, mount_point: &str) -> bool {
+    let fs = file_system.to_lowercase();
+    PSEUDO_FILE_SYSTEMS.contains(&fs.as_str())
+        || SYSTEM_MOUNT_PREFIXES
+            .iter()
+            .any(|prefix| mount_point == *prefix || mount_point.starts_with(&format!("{}/", prefix)))
+}
+
+/// Prefers the filesystem UUID, then the share address for network mounts, then the label
+/// together with filesystem and capacity. The mount point is the last resort since it is
+/// exactly what changes when a drive is plugged into another port or gets a new letter.
+fn volume_key(
+    uuid: Option<&str>,
+    label: Option<&str>,
+    network_source: Option<&str>,
+    file_system: &str,
+    total_bytes: u64,
+    mount_point: &str,
+) -> String {
+    if let Some(uuid) = uuid {
+        return format!("uuid:{}", uuid.to_lowercase());
+    }
+    if let Some(source) = network_source {
+        return format!("net:{}", source);
+    }
+    match label {
+        Some(label) => format!("label:{}:{}:{}", label, file_system.to_lowercase(), total_bytes),
+        None => format!("mount:{}", mount_point),
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let trimmed = value.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+fn is_network(file_system: &str) -> bool {
+    let fs = file_system.to_lowercase();
+    NETWORK_FILE_SYSTEMS.contains(&fs.as_str()) || fs.starts_with("nfs") || fs.starts_with("smb")
+}
+
+/// One entry of `/proc/self/mounts`.
+#[cfg(target_os = "linux")]
+struct MountEntry {
+    device: String,
+    mount_point: String,
+    file_system: String,
+    read_only: bool,
+}
+
+/// Mount points in `/proc/self/mounts` escape spaces and tabs as octal (`\040`).
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let digits: String = chars.clone().take(3).collect();
+            if digits.len() == 3 && digits.chars().all(|d| ('0'..='7').contains(&d)) {
+                if let Ok(code) = u8::from_str_radix(&digits, 8) {
+                    out.push(code as char);
+                    for _ in 0..3 {
+                        chars.next();
+                    }
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Names in `/dev/disk/by-label` escape unsafe bytes as `\xHH`.
+#[cfg(target_os = "linux")]
+fn unescape_udev_name(name: &str) -> String {
+    let mut bytes = Vec::with_capacity(name.len());
+    let raw = name.as_bytes();
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'\\' && raw.get(i + 1) == Some(&b'x') && i + 3 < raw.len() {
+            if let Ok(byte) = u8::from_str_radix(&name[i + 2..i + 4], 16) {
+                bytes.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        bytes.push(raw[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Maps each block device (canonical path) to the names of its `/dev/disk/by-*` symlinks.
+#[cfg(target_os = "linux")]
+fn read_disk_links(dir: &str) -> HashMap<PathBuf, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let device = std::fs::canonicalize(entry.path()).ok()?;
+            let name = unescape_udev_name(entry.file_name().to_str()?);
+            Some((device, name))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn read_linux_mounts() -> Vec<MountEntry> {
+    let Ok(contents) = std::fs::read_to_string("/proc/self/mounts") else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = unescape_mount_field(fields.next()?);
+            let mount_point = unescape_mount_field(fields.next()?);
+            let file_system = fields.next()?.to_string();
+            let options = fields.next().unwrap_or("");
+            Some(MountEntry {
+                device,
+                mount_point,
+                file_system,
+                read_only: options.split(',').any(|o| o == "ro"),
+            })
+        })
+        .collect()
+}
+
+/// Linux: /proc/self/mounts also lists network shares and read-only flags, which sysinfo doesn't.
+#[cfg(target_os = "linux")]
+fn read_linux_volumes(disks: &Disks, seen: &mut HashSet<String>) -> Vec<Volume> {
+    let uuids = read_disk_links("/dev/disk/by-uuid");
+    let labels = read_disk_links("/dev/disk/by-label");
+    let mut volumes = Vec::new();
+    for entry in read_linux_mounts() {
+        if is_pseudo(&entry.file_system, &entry.mount_point) || !seen.insert(entry.mount_point.clone()) {
+            continue;
+        }
+        let disk = disks
+            .list()
+            .iter()
+            .find(|d| d.mount_point().to_string_lossy() == entry.mount_point);
+        let device = std::fs::canonicalize(&entry.device).ok();
+        let uuid = device.as_ref().and_then(|d| uuids.get(d)).cloned();
+        let label = device.as_ref().and_then(|d| labels.get(d)).cloned();
+        let network = is_network(&entry.file_system);
+        let total_bytes = disk.map_or(0, |d| d.total_space());
+        volumes.push(Volume {
+            volume_key: volume_key(
+                uuid.as_deref(),
+                label.as_deref(),
+                network.then_some(entry.device.as_str()),
+                &entry.file_system,
+                total_bytes,
+                &entry.mount_point,
+            ),
+            uuid,
+            label,
+            name: disk
+                .map(|d| d.name().to_string_lossy().to_string())
+                .unwrap_or_else(|| entry.device.clone()),
+            is_removable: disk.is_some_and(|d| d.is_removable()),
+            is_network: network,
+            is_read_only: entry.read_only,
+            total_bytes,
+            available_bytes: disk.map_or(0, |d| d.available_space()),
+            mount_point: entry.mount_point,
+            file_system: entry.file_system,
+            selected: false,
+        });
+    }
+    volumes
+}
+
+/// Lists the mounted volumes that can hold user files, with their capacity and flags.
+/// `selected` is not filled in here; see `discover_volumes_with_selection`.
+pub fn discover_volumes() -> Vec<Volume> {
+    let disks = Disks::new_with_refreshed_list();
+    let mut volumes: Vec<Volume> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+
+    #[cfg(target_os = "linux")]
+    volumes.extend(read_linux_volumes(&disks, &mut seen));
+
+    for disk in disks.list() {
+        let mount_point = disk.mount_point().to_string_lossy().to_string();
+        let file_system = disk.file_system().to_string_lossy().to_string();
+        if is_pseudo(&file_system, &mount_point) || !seen.insert(mount_point.clone()) {
+            continue;
+        }
+        let is_read_only = std::fs::metadata(disk.mount_point())
+            .map(|m| m.permissions().readonly())
+            .unwrap_or(false);
+        // sysinfo reports the volume label as the disk name on Windows and macOS
+        let label = non_empty(disk.name().to_string_lossy().to_string());
+        volumes.push(Volume {
+            volume_key: volume_key(
+                None,
+                label.as_deref(),
+                None,
+                &file_system,
+                disk.total_space(),
+                &mount_point,
+            ),
+            uuid: None,
+            label,
+            name: disk.name().to_string_lossy().to_string(),
+            is_removable: disk.is_removable(),
+            is_network: is_network(&file_system),
+            is_read_only,
+            total_bytes: disk.total_space(),
+            available_bytes: disk.available_space(),
+            mount_point,
+            file_system,
+            selected: false,
+        });
+    }
+
+    volumes.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    volumes
+}
+
+/// The user's volume choice, or `None` when they never made one.
+pub fn load_selected_mount_points(db: &Connection) -> Option<Vec<String>> {
+    let stored = crate::database::rules::get_setting_sync(db, SELECTED_VOLUMES_SETTING)
+        .ok()
+        .flatten()?;
+    serde_json::from_str(&stored).ok()
+}
+
+pub fn save_selected_mount_points(db: &Connection, mount_points: &[String]) -> Result<(), String> {
+    let json = serde_json::to_string(mount_points).map_err(|e| e.to_string())?;
+    crate::database::rules::set_setting_sync(db, SELECTED_VOLUMES_SETTING, &json)
+        .map_err(|e| e.to_string())
+}
+
+pub fn discover_volumes_with_selection(db: &Connection) -> Vec<Volume> {
+    let selection = load_selected_mount_points(db);
+    let mut volumes = discover_volumes();
+    for volume in &mut volumes {
+        volume.selected = match &selection {
+            Some(selected) => selected.contains(&volume.mount_point),
+            None => volume.selected_by_default(),
+        };
+    }
+    volumes
+}
+
+/// Mount points Phase 2 should walk. Each one is walked without crossing into other filesystems.
+/// Volumes whose policy is "never" are left out even when selected.
+pub fn selected_volume_paths(db: &Connection) -> Vec<String> {
+    let tracked = load_tracked_volumes(db).unwrap_or_default();
+    discover_volumes_with_selection(db)
+        .into_iter()
+        .filter(|v| v.selected)
+        .filter(|v| {
+            !tracked
+                .iter()
+                .any(|t| t.volume_key == v.volume_key && t.policy == VolumePolicy::Never)
+        })
+        .map(|v| v.mount_point)
+        .collect()
+}
@@ -0,0 +1,302 @@
+// Ignore-file support for directory walks: .gitignore, .ignore and .fileaiignore
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use rusqlite::Connection;
+use std::path::Path;
+use walkdir::DirEntry;
+
+pub const RESPECT_IGNORE_FILES_SETTING: &str = "respect_ignore_files";
+
+// Read in this order from each directory; on conflicting patterns the later file wins
+pub const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore", ".fileaiignore"];
+
+/// Ignore files are honoured unless the user turned them off.
+pub fn respect_ignore_files(db: &Connection) -> bool {
+    let value = crate::database::rules::get_setting_sync(db, RESPECT_IGNORE_FILES_SETTING)
+        .ok()
+        .flatten();
+    value.as_deref() != Some("false")
+}
+
+/// Whether `dir` is the top of a git repository (`.git` is a file in worktrees).
+fn is_repo_root(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// The ignore files of one directory, combined into a single matcher. Like git, and like
+/// `ignore`'s `require_git(true)`, a .gitignore only counts inside a repository.
+fn load_dir_ignores(dir: &Path, in_repo: bool) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut found = false;
+    for name in IGNORE_FILE_NAMES {
+        if *name == ".gitignore" && !in_repo {
+            continue;
+        }
+        let file = dir.join(name);
+        if file.is_file() {
+            if let Some(e) = builder.add(&file) {
+                eprintln!("Invalid pattern in {}: {}", file.display(), e);
+            }
+            found = true;
+        }
+    }
+    if !found {
+        return None;
+    }
+    match builder.build() {
+        Ok(matcher) if !matcher.is_empty() => Some(matcher),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Failed to load ignore files in {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+struct Frame {
+    // Entries at this walk depth or deeper are governed by the matcher
+    min_depth: usize,
+    matcher: Gitignore,
+}
+
+/// Ignore rules in effect at the current position of a depth-first `WalkDir` walk.
+/// Deeper ignore files override shallower ones, and `!pattern` re-includes a path.
+pub struct IgnoreStack {
+    enabled: bool,
+    frames: Vec<Frame>,
+    // Entries at this walk depth or deeper are inside a git repository
+    repo_min_depth: Option<usize>,
+}
+
+impl IgnoreStack {
+    /// `root` is the walk root; ignore files in its ancestors apply to the whole walk.
+    pub fn new(root: &Path, enabled: bool) -> Self {
+        let mut frames = Vec::new();
+        let mut in_repo = false;
+        if enabled {
+            let mut ancestors: Vec<&Path> = root.ancestors().skip(1).collect();
+            ancestors.reverse();
+            for ancestor in ancestors {
+                in_repo |= is_repo_root(ancestor);
+                if let Some(matcher) = load_dir_ignores(ancestor, in_repo) {
+                    frames.push(Frame { min_depth: 0, matcher });
+                }
+            }
+        }
+        IgnoreStack {
+            enabled,
+            frames,
+            repo_min_depth: in_repo.then_some(0),
+        }
+    }
+
+    /// Whether `entry` is ignored. Must be called for every entry the walk yields, in order,
+    /// so directories can contribute their own ignore files to their subtree.
+    pub fn is_ignored(&mut self, entry: &DirEntry) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let depth = entry.depth();
+        self.frames.retain(|frame| frame.min_depth <= depth);
+        if self.repo_min_depth.is_some_and(|d| d > depth) {
+            self.repo_min_depth = None;
+        }
+
+        let is_dir = entry.file_type().is_dir();
+        let ignored = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| frame.matcher.matched(entry.path(), is_dir))
+            .find(|m| !m.is_none())
+            .is_some_and(|m| matches!(m, Match::Ignore(_)));
+
+        if is_dir && !ignored {
+            if self.repo_min_depth.is_none() && is_repo_root(entry.path()) {
+                self.repo_min_depth = Some(depth + 1);
+            }
+            let in_repo = self.repo_min_depth.is_some_and(|d| d <= depth + 1);
+            if let Some(matcher) = load_dir_ignores(entry.path(), in_repo) {
+                self.frames.push(Frame {
+                    min_depth: depth + 1,
+                    matcher,
+                });
+            }
+        }
+        ignored
+    }
+}
+
+/// The ignore-file pattern that excludes `path` (itself or one of its folders), described as
+/// `pattern (in file)`. Answers the same question as a walk would, for a single path.
+pub fn ignored_by(path: &Path) -> Option<String> {
+    let mut chain: Vec<&Path> = path.ancestors().collect();
+    chain.reverse();
+
+    let mut matchers: Vec<Gitignore> = Vec::new();
+    let mut in_repo = false;
+    for (i, current) in chain.iter().enumerate() {
+        let is_dir = i + 1 < chain.len() || current.is_dir();
+        let decided = matchers
+            .iter()
+            .rev()
+            .map(|matcher| matcher.matched(current, is_dir))
+            .find(|m| !m.is_none());
+        if let Some(Match::Ignore(glob)) = decided {
+            let source = glob
+                .from()
+                .map(|f| f.display().to_string())
+                .unwrap_or_default();
+            return Some(format!("{} (in {})", glob.original(), source));
+        }
+        if is_dir {
+            in_repo |= is_repo_root(current);
+            matchers.extend(load_dir_ignores(current, in_repo));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use walkdir::WalkDir;
+
+    /// A fresh directory holding `files` (relative path, contents).
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("walker-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&root).ok();
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    /// Files a walk keeps, relative to `root`, sorted.
+    fn walk(root: &Path) -> Vec<String> {
+        let mut ignores = IgnoreStack::new(root, true);
+        let mut kept: Vec<String> = WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| !ignores.is_ignored(e))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| relative(root, e.path()))
+            .collect();
+        kept.sort();
+        kept
+    }
+
+    fn relative(root: &Path, path: &Path) -> String {
+        path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/")
+    }
+
+    #[test]
+    fn nested_ignore_files_override_their_parent() {
+        let root = tree(
+            "nested",
+            &[
+                (".fileaiignore", "*.log\n"),
+                ("a.log", ""),
+                ("keep/.fileaiignore", "!important.log\n"),
+                ("keep/important.log", ""),
+                ("keep/other.log", ""),
+                ("other/important.log", ""),
+            ],
+        );
+        assert_eq!(
+            walk(&root),
+            vec![".fileaiignore", "keep/.fileaiignore", "keep/important.log"]
+        );
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn negated_patterns_re_include_files() {
+        let root = tree(
+            "negated",
+            &[(".ignore", "*.tmp\n!keep.tmp\n"), ("drop.tmp", ""), ("keep.tmp", "")],
+        );
+        assert_eq!(walk(&root), vec![".ignore", "keep.tmp"]);
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn gitignore_only_counts_inside_a_repository() {
+        let root = tree(
+            "git",
+            &[
+                ("plain/.gitignore", "*.txt\n"),
+                ("plain/a.txt", ""),
+                ("repo/.git/HEAD", ""),
+                ("repo/.gitignore", "*.txt\n"),
+                ("repo/b.txt", ""),
+                ("repo/sub/c.txt", ""),
+            ],
+        );
+        let kept = walk(&root);
+        assert!(kept.contains(&"plain/a.txt".to_string()));
+        assert!(!kept.contains(&"repo/b.txt".to_string()));
+        assert!(!kept.contains(&"repo/sub/c.txt".to_string()));
+
+        // A walk rooted inside the repository still sees its .gitignore
+        assert!(walk(&root.join("repo/sub")).is_empty());
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn ignored_directories_are_pruned_with_their_subtree() {
+        let root = tree(
+            "pruned",
+            &[
+                (".fileaiignore", "build/\n"),
+                ("build/out.bin", ""),
+                // Like git, a re-include can't reach into an excluded directory
+                ("build/sub/.fileaiignore", "!x\n"),
+                ("build/sub/x", ""),
+                ("src/build.rs", ""),
+            ],
+        );
+        assert_eq!(walk(&root), vec![".fileaiignore", "src/build.rs"]);
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn ignored_by_agrees_with_a_walk() {
+        let root = tree(
+            "explain",
+            &[
+                (".fileaiignore", "*.log\nbuild/\n"),
+                ("a.log", ""),
+                ("notes.txt", ""),
+                ("build/sub/x", ""),
+                ("keep/.fileaiignore", "!important.log\n"),
+                ("keep/important.log", ""),
+                ("keep/other.log", ""),
+                ("repo/.git/HEAD", ""),
+                ("repo/.gitignore", "secret*\n"),
+                ("repo/secret.txt", ""),
+                ("plain/.gitignore", "*\n"),
+                ("plain/open.txt", ""),
+            ],
+        );
+        let kept = walk(&root);
+        for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = relative(&root, entry.path());
+            if path.starts_with("repo/.git/") {
+                continue;
+            }
+            assert_eq!(ignored_by(entry.path()).is_none(), kept.contains(&path), "{}", path);
+        }
+
+        let reason = ignored_by(&root.join("build/sub/x")).unwrap();
+        assert!(reason.starts_with("build/ (in "), "{}", reason);
+        assert!(reason.ends_with(".fileaiignore)"), "{}", reason);
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
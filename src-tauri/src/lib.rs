@@ -13,6 +13,11 @@ mod embed_and_store;
 use tauri::async_runtime::block_on;
 
 // use crate::test::{debug_database_rules, test_embedding, test_file_filtering};
+use crate::database::history::{
+    delete_saved_search, delete_search_history_entry, get_query_suggestions, get_search_history,
+    get_search_history_enabled, list_saved_searches, purge_search_history, rename_saved_search,
+    save_search, set_search_history_enabled,
+};
 use crate::database::rules::{
     add_excluded_folder,
     remove_excluded_folder,
@@ -28,7 +33,16 @@ use crate::database::rules::{
     remove_included_folder,
     remove_excluded_extension,
     remove_excluded_filename,
+    list_scan_rules,
+    add_scan_rule,
+    update_scan_rule,
+    delete_scan_rule,
 };
+use crate::database::archive::{export_index, import_index};
+use crate::database::consistency::{check_index, check_index_consistency, repair_index};
+use crate::database::settings::{get_settings, update_settings};
+use crate::database::lancedb_maintenance::optimize_lancedb;
+use crate::database::storage::{apply_storage_budget, get_storage_stats};
 use crate::database::lancedb_ops::create_local_lancedb;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -53,6 +67,17 @@ pub fn run() {
                     let user_service = UserService::new();
                     app_handle.manage(Arc::new(user_service));
                     println!("Database initialized");
+                    if let Err(e) = block_on(database::migrations::run_lancedb_migrations()) {
+                        eprintln!("LanceDB migration error: {}", e);
+                        std::process::exit(1);
+                    }
+                    // Finish or undo SQLite/LanceDB writes a crash interrupted
+                    match block_on(database::journal::recover_pending_writes()) {
+                        Ok(report) => println!("Write journal recovery: {:?}", report),
+                        Err(e) => eprintln!("Write journal recovery failed: {}", e),
+                    }
+                    // Tracks plugged/unplugged volumes and resumes indexing when they return
+                    crate::file_scanner::volume_registry::start_volume_watcher(app_handle.clone());
                 }
                 Err(e) => {
                     eprintln!("Database error: {}", e);
@@ -76,8 +101,39 @@ pub fn run() {
             commands::scan_text_files,
             commands::scan_and_store_files,
             commands::scan_drives_metadata, // New
+            commands::discover_system_drives, // Volumes with flags and Phase 2 selection
+            commands::set_selected_volumes,
+            commands::list_tracked_volumes, // Known volumes, online status and policy
+            commands::set_volume_policy,
+            get_settings, // Typed app settings; every setter emits "settings_changed"
+            update_settings,
+            commands::get_respect_ignore_files, // Honour .gitignore/.ignore/.fileaiignore in scans
+            commands::set_respect_ignore_files,
+            commands::explain_path, // Why a path is (not) indexed
+            check_index_consistency, // SQLite/LanceDB rows that no longer match up
+            check_index, // Orphans, missing/zero/wrong-dimension vectors, stale FTS rows
+            repair_index, // Fixes what it can; requeues files whose vectors are unusable
+            optimize_lancedb, // Compaction, index update and version cleanup; reports disk size
+            get_storage_stats, // Disk usage by store, table, category and root
+            apply_storage_budget, // Degrades the lowest-scored files until the index fits the budget
+            export_index, // Versioned archive of both stores, rules and settings
+            import_index, // Validates model/dimension and remaps paths before replacing the index
             commands::search_files,
             commands::search_indexed_files, // New search command
+            commands::search_files_streaming, // Emits "search_results" partial/final updates
+            commands::replay_search_history_entry,
+            commands::run_saved_search,
+            // search history and saved searches
+            get_search_history,
+            delete_search_history_entry,
+            purge_search_history,
+            get_search_history_enabled,
+            set_search_history_enabled,
+            get_query_suggestions,
+            list_saved_searches,
+            save_search,
+            rename_saved_search,
+            delete_saved_search,
             // open file and open file with commands
             commands::open_file,             // New file opening command
             commands::open_file_with,        // New open with command
@@ -112,6 +168,10 @@ pub fn run() {
             remove_included_folder,
             remove_excluded_extension,
             remove_excluded_filename,
+            list_scan_rules, // Glob/regex rules with priority, depth and action
+            add_scan_rule,
+            update_scan_rule,
+            delete_scan_rule,
             commands::get_included_extensions,
             commands::get_included_folders,
             commands::get_excluded_extensions,
@@ -120,6 +180,13 @@ pub fn run() {
             commands::get_excluded_filenames,
             commands::get_excluded_folders,
             commands::get_matching_file_count,
+            // scoring profile commands
+            commands::get_scoring_profile,
+            commands::update_scoring_profile,
+            commands::reset_scoring_profile,
+            commands::import_scoring_profile,
+            commands::export_scoring_profile,
+            commands::recompute_scores,
             commands::run_full_scan_and_index
         ])
         .run(tauri::generate_context!())
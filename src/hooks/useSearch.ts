@@ -10,6 +10,7 @@ interface BackendSearchResult {
   relevance_score: number;
   match_type: 'Vector' | 'Text' | { Hybrid: [number, number] };
   snippet?: string;
+  available: boolean; // false while the file's volume is disconnected
 }
 
 // Frontend types
@@ -21,6 +22,7 @@ export interface SearchResult {
   relevance_score?: number;
   type: 'file' | 'folder' | 'content';
   snippet?: string;
+  available?: boolean;
 }
 
 export function useSearch() {
@@ -69,7 +71,8 @@ export function useSearch() {
           path: result.path,
           snippet: result.snippet || generateSnippet(result.title, query), // Use title as fallback for snippet generation
           type: type,
-          relevance_score: result.relevance_score
+          relevance_score: result.relevance_score,
+          available: result.available
         };
       });
 
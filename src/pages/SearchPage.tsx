@@ -75,7 +75,7 @@ export default function SearchPage() {
 
     const handleOpenFile = async (filePath: string) => {
         try {
-            await invoke("open_file", { filePath });
+            await invoke("open_file", { filePath, query });
             handleClose();
         } catch (error) {
             console.error("Failed to open file:", error);
@@ -84,7 +84,7 @@ export default function SearchPage() {
 
     const handleOpenWith = async (filePath: string, application: string) => {
         try {
-            await invoke("open_file_with", { filePath, application });
+            await invoke("open_file_with", { filePath, application, query });
             handleClose();
         } catch (error) {
             console.error("Failed to open file with application:", error);
@@ -93,7 +93,7 @@ export default function SearchPage() {
 
     const handleShowInExplorer = async (filePath: string) => {
         try {
-            await invoke("show_file_in_explorer", { filePath });
+            await invoke("show_file_in_explorer", { filePath, query });
         } catch (error) {
             console.error("Failed to show file in explorer:", error);
         }